@@ -0,0 +1,22 @@
+"""A bot that follows the nearest ball along its side.
+
+Every line of its standard input is the state of the match, it answers each one
+with the movement of its paddle, from -1 to 1.
+"""
+
+import json
+import sys
+
+for line in sys.stdin:
+    state = json.loads(line)
+    side = state["side"]
+    paddle = next(paddle for paddle in state["paddles"] if paddle["side"] == side)
+    # The left and right paddles move along y, the top and bottom ones along x.
+    axis = 1 if side in ("Left", "Right") else 0
+    balls = state["balls"] or [{"position": paddle["position"]}]
+    ball = min(
+        balls,
+        key=lambda ball: abs(ball["position"][1 - axis] - paddle["position"][1 - axis]),
+    )
+    distance = ball["position"][axis] - paddle["position"][axis]
+    print(max(-1.0, min(1.0, distance / 10.0)), flush=True)
@@ -0,0 +1,316 @@
+use std::str::FromStr;
+
+use amethyst::ecs::prelude::{Component, DenseVecStorage};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::sim::{BallState, Side, Simulation};
+
+/// Time, in seconds, the computer gives itself to reach its target height.
+const APPROACH_TIME: f32 = 0.1;
+
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Eq)]
+pub enum Difficulty {
+    Easy,
+    Normal,
+    Hard,
+}
+
+impl FromStr for Difficulty {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "easy" => Ok(Difficulty::Easy),
+            "normal" => Ok(Difficulty::Normal),
+            "hard" => Ok(Difficulty::Hard),
+            _ => Err(format!(
+                "unknown difficulty `{}`, expected easy, normal or hard",
+                s
+            )),
+        }
+    }
+}
+
+/// The tuning values behind a difficulty preset.
+#[derive(Clone, Copy, Debug)]
+pub struct AiSettings {
+    /// Seconds between two decisions of the computer.
+    pub reaction_delay: f32,
+    /// Fraction of the paddle speed the computer is allowed to use.
+    pub max_speed: f32,
+    /// Largest distance between the aimed and the real ball position, as a
+    /// fraction of the paddle length. An error past half the length and the
+    /// ball radius lets the ball through, so every difficulty can miss.
+    pub prediction_error: f32,
+    /// Whether the computer follows the ball trajectory or just the ball height.
+    pub predict: bool,
+}
+
+impl Difficulty {
+    pub fn settings(self) -> AiSettings {
+        match self {
+            Difficulty::Easy => AiSettings {
+                reaction_delay: 0.4,
+                max_speed: 0.5,
+                prediction_error: 1.5,
+                predict: false,
+            },
+            Difficulty::Normal => AiSettings {
+                reaction_delay: 0.2,
+                max_speed: 0.75,
+                prediction_error: 1.2,
+                predict: true,
+            },
+            Difficulty::Hard => AiSettings {
+                reaction_delay: 0.05,
+                max_speed: 1.0,
+                prediction_error: 0.95,
+                predict: true,
+            },
+        }
+    }
+}
+
+/// AiConfig tells which paddles are played by the computer. It is loaded from
+/// `config/ai.ron` and can be overridden with `--ai-left`, `--ai-right`,
+/// `--ai-top` and `--ai-bottom`.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AiConfig {
+    pub left: Option<Difficulty>,
+    pub right: Option<Difficulty>,
+    /// The top and bottom paddles only play in four player matches.
+    pub top: Option<Difficulty>,
+    pub bottom: Option<Difficulty>,
+}
+
+impl AiConfig {
+    /// Difficulty of the computer playing `side`, `None` for a human player.
+    pub fn get(&self, side: Side) -> Option<Difficulty> {
+        match side {
+            Side::Left => self.left,
+            Side::Right => self.right,
+            Side::Top => self.top,
+            Side::Bottom => self.bottom,
+        }
+    }
+
+    /// Gives `side` to the computer, or to a human player with `None`.
+    pub fn set(&mut self, side: Side, difficulty: Option<Difficulty>) {
+        match side {
+            Side::Left => self.left = difficulty,
+            Side::Right => self.right = difficulty,
+            Side::Top => self.top = difficulty,
+            Side::Bottom => self.bottom = difficulty,
+        }
+    }
+
+    /// Returns the same config with `side` given back to a human player.
+    pub fn without(mut self, side: Side) -> AiConfig {
+        self.set(side, None);
+        self
+    }
+
+    /// Applies the `--ai-left <difficulty|off>` and `--ai-right <difficulty|off>`
+    /// arguments, and their `--ai-top` and `--ai-bottom` counterparts.
+    pub fn apply_args<I: Iterator<Item = String>>(&mut self, mut args: I) -> Result<(), String> {
+        while let Some(arg) = args.next() {
+            let slot = match arg.as_str() {
+                "--ai-left" => &mut self.left,
+                "--ai-right" => &mut self.right,
+                "--ai-top" => &mut self.top,
+                "--ai-bottom" => &mut self.bottom,
+                _ => continue,
+            };
+            let value = args
+                .next()
+                .ok_or_else(|| format!("missing difficulty after `{}`", arg))?;
+            *slot = if value == "off" {
+                None
+            } else {
+                Some(value.parse()?)
+            };
+        }
+        Ok(())
+    }
+}
+
+/// AiController makes the computer play the paddle it is attached to.
+pub struct AiController {
+    pub settings: AiSettings,
+    /// Position, along its side, the paddle is currently heading to.
+    pub target: f32,
+    /// Time left before the next decision.
+    pub reaction_timer: f32,
+    /// Movement sent to the paddle this tick, in the same range as an input axis.
+    pub movement: f32,
+}
+
+impl AiController {
+    pub fn new(difficulty: Difficulty, initial: f32) -> AiController {
+        AiController {
+            settings: difficulty.settings(),
+            target: initial,
+            reaction_timer: 0.0,
+            movement: 0.0,
+        }
+    }
+
+    /// Decides the movement of the `side` paddle for the next `time_delta`
+    /// seconds. The result is stored in `movement`.
+    pub fn update<R: Rng>(&mut self, sim: &Simulation, side: Side, time_delta: f32, rng: &mut R) {
+        let paddle = match sim.paddle(side) {
+            Some(paddle) => paddle,
+            None => return,
+        };
+
+        self.reaction_timer -= time_delta;
+        if self.reaction_timer <= 0.0 {
+            self.reaction_timer = self.settings.reaction_delay;
+
+            // Ball positions are looked at across and along the side of the
+            // paddle, x and y for the left and right paddles.
+            let (across, axis) = (side.across(), side.axis());
+            let flip = |vector: [f32; 2]| [vector[across], vector[axis]];
+            // The face of the paddle is where the ball center is when it gets hit.
+            let thickness = flip([paddle.width, paddle.height])[0];
+            let face = paddle.position[across] + thickness * 0.5 * side.direction();
+            let predict = self.settings.predict;
+            let span = flip([sim.rules.arena_width, sim.rules.arena_height])[1];
+            // The ends of the side are walls, unless they are goals of other players.
+            let walls = sim.rules.sides().iter().all(|other| other.axis() == axis);
+
+            // Follow the first ball that will reach this paddle, otherwise go
+            // back to the middle of the side and wait.
+            let incoming = sim
+                .balls
+                .iter()
+                .filter(|ball| ball.state == BallState::Moving)
+                .filter_map(|ball| {
+                    let (position, velocity) = (flip(ball.position), flip(ball.velocity));
+                    let target = face + ball.radius * side.direction();
+                    let time_to_reach = (target - position[0]) / velocity[0];
+                    let along = if predict && walls {
+                        predict_ball_y(position, velocity, ball.radius, target, span)?
+                    } else if time_to_reach <= 0.0 {
+                        return None;
+                    } else if predict {
+                        // Nothing bounces the ball on the way.
+                        position[1] + velocity[1] * time_to_reach
+                    } else {
+                        position[1]
+                    };
+                    Some((time_to_reach, along))
+                })
+                .min_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+
+            self.target = match incoming {
+                Some((_, along)) => {
+                    let error = self.settings.prediction_error * paddle.length();
+                    if error > 0.0 {
+                        along + rng.gen_range(-error, error)
+                    } else {
+                        along
+                    }
+                }
+                None => span * 0.5,
+            };
+        }
+
+        // Move at most `max_speed`, slowing down close to the target so the
+        // paddle does not overshoot it.
+        let wanted_speed = (self.target - paddle.position[side.axis()]) / APPROACH_TIME;
+        let max_speed = self.settings.max_speed;
+        self.movement = (wanted_speed / paddle.max_speed)
+            .max(-max_speed)
+            .min(max_speed);
+    }
+}
+
+impl Component for AiController {
+    type Storage = DenseVecStorage<Self>;
+}
+
+/// Returns the height of the ball once it reaches `target_x`, following its
+/// bounces on the top and bottom of the arena. Returns `None` if the ball is
+/// not moving towards `target_x`.
+pub fn predict_ball_y(
+    position: [f32; 2],
+    velocity: [f32; 2],
+    radius: f32,
+    target_x: f32,
+    arena_height: f32,
+) -> Option<f32> {
+    let distance = target_x - position[0];
+    if velocity[0] == 0.0 || distance.signum() != velocity[0].signum() {
+        return None;
+    }
+    let time = distance / velocity[0];
+    let unfolded_y = position[1] + velocity[1] * time;
+
+    // The ball center moves between `radius` and `arena_height - radius`, every
+    // wall bounce mirrors its path, so the trajectory is folded back in that range.
+    let span = arena_height - radius * 2.0;
+    let folded = (unfolded_y - radius).rem_euclid(span * 2.0);
+    let y = if folded > span {
+        span * 2.0 - folded
+    } else {
+        folded
+    };
+    Some(radius + y)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rules::GameRules;
+
+    const RADIUS: f32 = 2.0;
+    const HEIGHT: f32 = 100.0;
+
+    #[test]
+    fn straight_ball_keeps_its_height() {
+        let y = predict_ball_y([50.0, 30.0], [-60.0, 0.0], RADIUS, 6.0, HEIGHT);
+        assert_eq!(y, Some(30.0));
+    }
+
+    #[test]
+    fn ball_bouncing_on_a_wall_is_folded_back() {
+        // Unfolded, the ball would end 20 past the bottom of the arena: it
+        // bounces at `RADIUS` and comes back up by the same distance.
+        let y = predict_ball_y([50.0, 22.0], [-44.0, -40.0], RADIUS, 6.0, HEIGHT).unwrap();
+        assert!((y - 22.0).abs() < 1e-4, "{}", y);
+
+        // Twice the span takes the ball to the top wall and back.
+        let y = predict_ball_y([50.0, 50.0], [-44.0, 192.0], RADIUS, 6.0, HEIGHT).unwrap();
+        assert!((y - 50.0).abs() < 1e-3, "{}", y);
+    }
+
+    #[test]
+    fn ball_moving_away_is_not_predicted() {
+        assert_eq!(
+            predict_ball_y([50.0, 50.0], [60.0, 10.0], RADIUS, 6.0, HEIGHT),
+            None
+        );
+        assert_eq!(
+            predict_ball_y([50.0, 50.0], [0.0, 10.0], RADIUS, 6.0, HEIGHT),
+            None
+        );
+    }
+
+    #[test]
+    fn harder_presets_predict_with_less_error() {
+        let [easy, normal, hard] =
+            [Difficulty::Easy, Difficulty::Normal, Difficulty::Hard].map(Difficulty::settings);
+        assert!(easy.prediction_error > normal.prediction_error);
+        assert!(normal.prediction_error > hard.prediction_error);
+        assert!(easy.reaction_delay > normal.reaction_delay);
+        assert!(normal.reaction_delay > hard.reaction_delay);
+
+        // Past half the paddle and the ball radius, the aimed paddle misses the ball.
+        let rules = GameRules::default();
+        let reach = (rules.paddle_height * 0.5 + rules.ball_radius) / rules.paddle_height;
+        assert!(hard.prediction_error > reach);
+    }
+}
@@ -6,9 +6,13 @@ use amethyst::{
     ecs::{World, WorldExt},
 };
 
+use crate::settings::Settings;
+
 pub struct Sounds {
     pub score_sfx: SourceHandle,
     pub bounce_sfx: SourceHandle,
+    /// Volume of the sound effects, from the `Settings`.
+    pub volume: f32,
 }
 
 pub struct Music {
@@ -33,21 +37,23 @@ const AUDIO_MUSIC: &[&str] = &[
 pub fn initialise_audio(world: &mut World) {
     let (sound_effects, music) = {
         let loader = world.read_resource::<Loader>();
+        let settings = world.read_resource::<Settings>();
 
         let mut sink = world.write_resource::<AudioSink>();
-        sink.set_volume(0.25); // Music is a bit loud, reduce the volume.
+        sink.set_volume(settings.music_volume);
 
         let music = AUDIO_MUSIC
             .iter()
-            .map(|file| load_audio_track(&loader, &world, file))
+            .map(|file| load_audio_track(&loader, world, file))
             .collect::<Vec<_>>()
             .into_iter()
             .cycle();
         let music = Music { music };
 
         let sound = Sounds {
-            bounce_sfx: load_audio_track(&loader, &world, AUDIO_BOUNCE),
-            score_sfx: load_audio_track(&loader, &world, AUDIO_SCORE),
+            bounce_sfx: load_audio_track(&loader, world, AUDIO_BOUNCE),
+            score_sfx: load_audio_track(&loader, world, AUDIO_SCORE),
+            volume: settings.sfx_volume,
         };
 
         (sound, music)
@@ -61,17 +67,17 @@ pub fn initialise_audio(world: &mut World) {
 
 /// Plays the bounce sound when a ball hits a side or a paddle.
 pub fn play_bounce(sounds: &Sounds, storage: &AssetStorage<Source>, output: Option<&Output>) {
-    if let Some(ref output) = output.as_ref() {
+    if let Some(output) = output.as_ref() {
         if let Some(sound) = storage.get(&sounds.bounce_sfx) {
-            output.play_once(sound, 1.0);
+            output.play_once(sound, sounds.volume);
         }
     }
 }
 
 pub fn play_score_sound(sounds: &Sounds, storage: &AssetStorage<Source>, output: Option<&Output>) {
-    if let Some(ref output) = output.as_ref() {
+    if let Some(output) = output.as_ref() {
         if let Some(sound) = storage.get(&sounds.score_sfx) {
-            output.play_once(sound, 1.0);
+            output.play_once(sound, sounds.volume);
         }
     }
 }
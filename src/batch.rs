@@ -0,0 +1,423 @@
+//! Batches of matches between computer players, played headless to compare
+//! rule variants: the `batch` mode of the command line.
+
+use std::{
+    error::Error,
+    fmt,
+    io::{self, Write},
+    str::FromStr,
+};
+
+use serde::Serialize;
+
+use crate::ai::AiConfig;
+use crate::headless::AiMatch;
+use crate::level::Level;
+use crate::rules::{GameRules, RulesError};
+use crate::sim::{Side, SimEvent, TICK, TICK_RATE};
+
+/// Matches played for every variant when `--matches` is not given.
+pub const DEFAULT_MATCHES: u32 = 100;
+/// Ticks after which a match still running is stopped when `--frames` is not
+/// given, ten minutes of play. It is counted as unfinished.
+pub const MAX_MATCH_TICKS: u64 = TICK_RATE as u64 * 600;
+
+/// Format of the report of a batch.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    Json,
+    Csv,
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "json" => Ok(Format::Json),
+            "csv" => Ok(Format::Csv),
+            _ => Err(format!("unknown format `{}`, expected json or csv", s)),
+        }
+    }
+}
+
+/// Vary gives a rule the values it takes in the variants, from
+/// `--vary <FIELD>=<VALUE>,<VALUE>...`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Vary {
+    pub field: String,
+    pub values: Vec<String>,
+}
+
+impl FromStr for Vary {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.splitn(2, '=');
+        match (parts.next(), parts.next()) {
+            (Some(field), Some(values)) if !field.is_empty() && !values.is_empty() => Ok(Vary {
+                field: field.to_string(),
+                values: values.split(',').map(str::to_string).collect(),
+            }),
+            _ => Err(format!(
+                "invalid variation `{}`, expected <FIELD>=<VALUE>,<VALUE>...",
+                s
+            )),
+        }
+    }
+}
+
+/// A set of rules played by a batch, named after the values it changes.
+#[derive(Clone, Debug)]
+pub struct Variant {
+    pub name: String,
+    pub rules: GameRules,
+}
+
+/// Returns a variant of `rules` for every combination of the values of
+/// `vary`, or `rules` alone without any. Every variant is validated.
+pub fn variants(rules: &GameRules, vary: &[Vary]) -> Result<Vec<Variant>, RulesError> {
+    let mut variants = vec![Variant {
+        name: String::new(),
+        rules: rules.clone(),
+    }];
+    for vary in vary {
+        let mut combined = Vec::new();
+        for variant in variants.iter() {
+            for value in vary.values.iter() {
+                let mut rules = variant.rules.clone();
+                rules.set(&vary.field, value)?;
+                let separator = if variant.name.is_empty() { "" } else { " " };
+                combined.push(Variant {
+                    name: format!("{}{}{}={}", variant.name, separator, vary.field, value),
+                    rules,
+                });
+            }
+        }
+        variants = combined;
+    }
+    for variant in variants.iter_mut() {
+        variant.rules.validate()?;
+        if variant.name.is_empty() {
+            variant.name = "base".to_string();
+        }
+    }
+    Ok(variants)
+}
+
+/// What a batch keeps of the matches of a variant.
+#[derive(Debug, Default)]
+struct Samples {
+    matches: u32,
+    unfinished: u32,
+    wins: [u32; 4],
+    points: [u32; 4],
+    /// Paddle hits of every point played.
+    rallies: Vec<f32>,
+    /// Speed of the ball on the tick before it scored, in units per second.
+    scoring_speeds: Vec<f32>,
+    /// Seconds of play of every match.
+    durations: Vec<f32>,
+}
+
+impl Samples {
+    fn play(&mut self, mut game: AiMatch, max_ticks: u64) {
+        // Paddle hits since the serve, for every ball.
+        let mut hits = vec![0; game.sim.balls.len()];
+        while !game.sim.is_over() && game.ticks < max_ticks {
+            let speeds = game
+                .sim
+                .balls
+                .iter()
+                .map(|ball| ball.velocity[0].hypot(ball.velocity[1]))
+                .collect::<Vec<_>>();
+            let (_, events) = game.step();
+            hits.resize(game.sim.balls.len().max(hits.len()), 0);
+            for event in events {
+                match event {
+                    SimEvent::Serve { ball } => hits[ball] = 0,
+                    SimEvent::PaddleHit { ball, .. } => hits[ball] += 1,
+                    SimEvent::Goal { ball, scorer } => {
+                        self.points[scorer.index()] += 1;
+                        self.rallies.push(hits[ball] as f32);
+                        hits[ball] = 0;
+                        if let Some(speed) = speeds.get(ball) {
+                            self.scoring_speeds.push(*speed);
+                        }
+                    }
+                    SimEvent::MatchOver { winner } => self.wins[winner.index()] += 1,
+                    _ => {}
+                }
+            }
+        }
+        self.matches += 1;
+        if !game.sim.is_over() {
+            self.unfinished += 1;
+        }
+        self.durations.push(game.ticks as f32 * TICK);
+    }
+}
+
+/// Report of the matches of a variant.
+#[derive(Clone, Debug, Serialize)]
+pub struct VariantReport {
+    pub variant: String,
+    pub matches: u32,
+    /// Matches stopped after their last tick without a winner.
+    pub unfinished: u32,
+    /// Matches and points won by every side, in the order of `Side::ALL`.
+    pub wins: [u32; 4],
+    pub points: [u32; 4],
+    /// Paddle hits per point.
+    pub rally_length: Summary,
+    /// Number of points played with each rally length, indexed by the length.
+    pub rally_histogram: Vec<u32>,
+    /// Ball speed when a point is scored, in units per second.
+    pub scoring_speed: Summary,
+    /// Seconds of play per match.
+    pub match_duration: Summary,
+}
+
+/// Summary of a set of samples, all zero without any.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct Summary {
+    pub mean: f32,
+    pub min: f32,
+    pub median: f32,
+    pub p90: f32,
+    pub max: f32,
+}
+
+impl Summary {
+    fn of(samples: &[f32]) -> Summary {
+        if samples.is_empty() {
+            return Summary::default();
+        }
+        let mut sorted = samples.to_vec();
+        sorted.sort_by(|a, b| a.partial_cmp(b).expect("samples are numbers"));
+        let quantile = |q: f32| sorted[((sorted.len() - 1) as f32 * q).round() as usize];
+        Summary {
+            mean: sorted.iter().sum::<f32>() / sorted.len() as f32,
+            min: sorted[0],
+            median: quantile(0.5),
+            p90: quantile(0.9),
+            max: sorted[sorted.len() - 1],
+        }
+    }
+}
+
+/// Report of a whole batch.
+#[derive(Clone, Debug, Serialize)]
+pub struct BatchReport {
+    /// Seed of the first match of every variant, the next ones follow it.
+    pub seed: u64,
+    pub variants: Vec<VariantReport>,
+}
+
+/// Plays `matches` matches of every variant between the computer players of
+/// `ai`, in the empty arena so only the rules change, each one stopped after
+/// `max_ticks`. Every variant plays the same seeds, starting at `seed`.
+/// `progress` is called before the matches of every variant are played.
+pub fn run<F: FnMut(&Variant)>(
+    variants: &[Variant],
+    ai: &AiConfig,
+    matches: u32,
+    seed: u64,
+    max_ticks: u64,
+    mut progress: F,
+) -> BatchReport {
+    let reports = variants
+        .iter()
+        .map(|variant| {
+            progress(variant);
+            let mut samples = Samples::default();
+            for index in 0..matches {
+                let seed = seed.wrapping_add(u64::from(index));
+                let game = AiMatch::new(variant.rules.clone(), Level::default(), ai, seed);
+                samples.play(game, max_ticks);
+            }
+            report(&variant.name, &samples)
+        })
+        .collect();
+    BatchReport {
+        seed,
+        variants: reports,
+    }
+}
+
+fn report(variant: &str, samples: &Samples) -> VariantReport {
+    let mut rally_histogram = Vec::new();
+    for rally in samples.rallies.iter() {
+        let length = *rally as usize;
+        if rally_histogram.len() <= length {
+            rally_histogram.resize(length + 1, 0);
+        }
+        rally_histogram[length] += 1;
+    }
+    VariantReport {
+        variant: variant.to_string(),
+        matches: samples.matches,
+        unfinished: samples.unfinished,
+        wins: samples.wins,
+        points: samples.points,
+        rally_length: Summary::of(&samples.rallies),
+        rally_histogram,
+        scoring_speed: Summary::of(&samples.scoring_speeds),
+        match_duration: Summary::of(&samples.durations),
+    }
+}
+
+impl BatchReport {
+    /// Writes the report as JSON, or as CSV with a line per variant. The CSV
+    /// leaves the rally histogram out.
+    pub fn write<W: Write>(&self, format: Format, out: &mut W) -> Result<(), BatchError> {
+        match format {
+            Format::Json => {
+                serde_json::to_writer_pretty(&mut *out, self).map_err(BatchError::Json)?;
+                writeln!(out)?;
+            }
+            Format::Csv => {
+                let mut header = vec!["variant".to_string(), "matches".into(), "unfinished".into()];
+                for prefix in &["wins", "points"] {
+                    header.extend(
+                        Side::ALL
+                            .iter()
+                            .map(|side| format!("{}_{:?}", prefix, side).to_lowercase()),
+                    );
+                }
+                for prefix in &["rally_length", "scoring_speed", "match_duration"] {
+                    header.extend(
+                        ["mean", "min", "median", "p90", "max"]
+                            .iter()
+                            .map(|stat| format!("{}_{}", prefix, stat)),
+                    );
+                }
+                writeln!(out, "{}", header.join(","))?;
+
+                for report in self.variants.iter() {
+                    // Variant names have no commas, they come from the values.
+                    let mut line = vec![
+                        report.variant.clone(),
+                        report.matches.to_string(),
+                        report.unfinished.to_string(),
+                    ];
+                    line.extend(report.wins.iter().map(u32::to_string));
+                    line.extend(report.points.iter().map(u32::to_string));
+                    for summary in &[
+                        &report.rally_length,
+                        &report.scoring_speed,
+                        &report.match_duration,
+                    ] {
+                        line.extend(
+                            [
+                                summary.mean,
+                                summary.min,
+                                summary.median,
+                                summary.p90,
+                                summary.max,
+                            ]
+                            .iter()
+                            .map(|value| format!("{:.3}", value)),
+                        );
+                    }
+                    writeln!(out, "{}", line.join(","))?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Errors that can happen while writing the report of a batch.
+#[derive(Debug)]
+pub enum BatchError {
+    Io(io::Error),
+    Json(serde_json::Error),
+}
+
+impl From<io::Error> for BatchError {
+    fn from(e: io::Error) -> Self {
+        BatchError::Io(e)
+    }
+}
+
+impl fmt::Display for BatchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BatchError::Io(e) => write!(f, "could not write the batch report: {}", e),
+            BatchError::Json(e) => write!(f, "could not write the batch report: {}", e),
+        }
+    }
+}
+
+impl Error for BatchError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            BatchError::Io(e) => Some(e),
+            BatchError::Json(e) => Some(e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::Difficulty;
+
+    #[test]
+    fn variants_combine_every_value() {
+        let vary = vec![
+            "ball_acceleration=0.1,0.4".parse::<Vary>().unwrap(),
+            "paddle_height=12,20".parse::<Vary>().unwrap(),
+        ];
+        let list = variants(&GameRules::default(), &vary).unwrap();
+        let names = list.iter().map(|v| v.name.as_str()).collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            vec![
+                "ball_acceleration=0.1 paddle_height=12",
+                "ball_acceleration=0.1 paddle_height=20",
+                "ball_acceleration=0.4 paddle_height=12",
+                "ball_acceleration=0.4 paddle_height=20",
+            ]
+        );
+        assert_eq!(list[3].rules.paddle_height, 20.0);
+
+        assert!(variants(
+            &GameRules::default(),
+            &["paddle_height=-1".parse().unwrap()]
+        )
+        .is_err());
+        assert!(variants(&GameRules::default(), &["color=red".parse().unwrap()]).is_err());
+    }
+
+    #[test]
+    fn batch_counts_every_point() {
+        let rules = GameRules {
+            points_to_win: 3,
+            ..GameRules::default()
+        };
+        let ai = AiConfig {
+            left: Some(Difficulty::Normal),
+            right: Some(Difficulty::Normal),
+            ..AiConfig::default()
+        };
+        let variants = variants(&rules, &[]).unwrap();
+        let report = run(&variants, &ai, 4, 1, MAX_MATCH_TICKS, |_| {});
+        let variant = &report.variants[0];
+
+        assert_eq!(variant.variant, "base");
+        assert_eq!(variant.matches, 4);
+        assert_eq!(variant.wins.iter().sum::<u32>(), 4 - variant.unfinished);
+        let points = variant.points.iter().sum::<u32>();
+        // Every finished match took at least three points to win.
+        assert!(points >= 3 * (4 - variant.unfinished));
+        assert!(points > 0);
+        assert_eq!(variant.rally_histogram.iter().sum::<u32>(), points);
+        assert!(variant.scoring_speed.min > 0.0);
+
+        let mut csv = Vec::new();
+        report.write(Format::Csv, &mut csv).unwrap();
+        assert_eq!(String::from_utf8(csv).unwrap().lines().count(), 2);
+    }
+}
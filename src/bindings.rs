@@ -0,0 +1,321 @@
+//! Changes to the key and button bindings of `config/bindings.ron`, made from
+//! the controls screen.
+
+use std::{error::Error, fmt, path::PathBuf};
+
+use amethyst::input::{Axis, BindingError, Bindings, Button, StringBindings};
+
+/// BindingsPath is the file the bindings were loaded from, and where the
+/// controls screen saves them.
+#[derive(Clone, Debug, Default)]
+pub struct BindingsPath(pub PathBuf);
+
+/// A key or button of the bindings the controls screen can change: one end
+/// of the keyboard pair of an axis, or the first button of an action.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Control {
+    /// The button pushing the axis up, or right for a horizontal paddle.
+    Positive(String),
+    /// The button pushing the axis down, or left for a horizontal paddle.
+    Negative(String),
+    Action(String),
+}
+
+impl Control {
+    /// Every control of `bindings`, the axes first, each group sorted by name.
+    pub fn all(bindings: &Bindings<StringBindings>) -> Vec<Control> {
+        let mut axes = bindings
+            .axes()
+            .filter(|id| match bindings.axis(*id).and_then(emulated) {
+                // The D-pad axes of the gamepads are left as they are.
+                Some((Button::Controller(..), _)) | None => false,
+                Some(_) => true,
+            })
+            .cloned()
+            .collect::<Vec<_>>();
+        axes.sort();
+        let mut actions = bindings
+            .actions()
+            .filter(|id| first_button(bindings, id).is_some())
+            .cloned()
+            .collect::<Vec<_>>();
+        actions.sort();
+
+        axes.into_iter()
+            .flat_map(|id| vec![Control::Positive(id.clone()), Control::Negative(id)])
+            .chain(actions.into_iter().map(Control::Action))
+            .collect()
+    }
+
+    /// Name of the control on the controls screen, "left paddle up" for the
+    /// positive end of `left_paddle`.
+    pub fn label(&self) -> String {
+        let (id, direction) = match self {
+            Control::Positive(id) if is_horizontal(id) => (id, " right"),
+            Control::Negative(id) if is_horizontal(id) => (id, " left"),
+            Control::Positive(id) => (id, " up"),
+            Control::Negative(id) => (id, " down"),
+            Control::Action(id) => (id, ""),
+        };
+        format!("{}{}", id.replace('_', " "), direction)
+    }
+
+    /// Button currently bound to the control.
+    pub fn button(&self, bindings: &Bindings<StringBindings>) -> Option<Button> {
+        match self {
+            Control::Positive(id) => bindings.axis(id).and_then(emulated).map(|(pos, _)| pos),
+            Control::Negative(id) => bindings.axis(id).and_then(emulated).map(|(_, neg)| neg),
+            Control::Action(id) => first_button(bindings, id),
+        }
+    }
+}
+
+/// Returns `bindings` with `button` bound to `control`. A button already bound
+/// to another control is swapped with the previous button of `control`, so
+/// both stay bound; that other control is returned as well.
+pub fn rebind(
+    bindings: &Bindings<StringBindings>,
+    control: &Control,
+    button: Button,
+) -> Result<(Bindings<StringBindings>, Option<Control>), RebindError> {
+    let previous = match control.button(bindings) {
+        Some(previous) => previous,
+        None => return Err(RebindError::Unknown(control.label())),
+    };
+    if previous == button {
+        return Ok((bindings.clone(), None));
+    }
+
+    let swapped = Control::all(bindings)
+        .into_iter()
+        .find(|other| other != control && other.button(bindings) == Some(button));
+    let mut edits = vec![(control.clone(), button)];
+    match &swapped {
+        Some(other) => edits.push((other.clone(), previous)),
+        // The button may also be bound where the screen does not show it,
+        // like the D-pad axis of a paddle.
+        None => {
+            if let Some(id) = user(bindings, button) {
+                return Err(RebindError::Taken { button, by: id });
+            }
+        }
+    }
+
+    let mut rebuilt = rebuild(bindings, &edits).map_err(RebindError::Invalid)?;
+    rebuilt
+        .check_invariants()
+        .map_err(|e| RebindError::Invalid(e.to_string()))?;
+    Ok((rebuilt, swapped))
+}
+
+/// Short name of a button for the controls screen.
+pub fn button_name(button: Button) -> String {
+    match button {
+        Button::Key(key) => format!("{:?}", key),
+        Button::ScanCode(code) => format!("Scan code {}", code),
+        Button::Mouse(mouse) => format!("Mouse {:?}", mouse),
+        Button::MouseWheel(direction) => format!("Wheel {:?}", direction),
+        Button::Controller(pad, button) => format!("Pad {} {:?}", pad + 1, button),
+    }
+}
+
+/// Copies `bindings` with the `edits` made. Every axis and action is inserted
+/// again with its final buttons, a swap would conflict halfway otherwise.
+fn rebuild(
+    bindings: &Bindings<StringBindings>,
+    edits: &[(Control, Button)],
+) -> Result<Bindings<StringBindings>, String> {
+    let mut rebuilt = Bindings::new();
+    for id in bindings.axes() {
+        let mut axis = bindings
+            .axis(id)
+            .cloned()
+            .expect("axis listed by the bindings");
+        if let Some((pos, neg)) = emulated_mut(&mut axis) {
+            for (control, button) in edits.iter() {
+                match control {
+                    Control::Positive(edited) if edited == id => *pos = *button,
+                    Control::Negative(edited) if edited == id => *neg = *button,
+                    _ => {}
+                }
+            }
+        }
+        rebuilt
+            .insert_axis(id.clone(), axis)
+            .map_err(|e: BindingError<StringBindings>| e.to_string())?;
+    }
+    for id in bindings.actions() {
+        for (index, combo) in bindings.action_bindings(id).enumerate() {
+            let mut combo = combo.to_vec();
+            if index == 0 && combo.len() == 1 {
+                for (control, button) in edits.iter() {
+                    if *control == Control::Action(id.clone()) {
+                        combo[0] = *button;
+                    }
+                }
+            }
+            rebuilt
+                .insert_action_binding(id.clone(), combo)
+                .map_err(|e: BindingError<StringBindings>| e.to_string())?;
+        }
+    }
+    Ok(rebuilt)
+}
+
+/// The pair of buttons of an emulated axis.
+fn emulated(axis: &Axis) -> Option<(Button, Button)> {
+    match axis {
+        Axis::Emulated { pos, neg } => Some((*pos, *neg)),
+        _ => None,
+    }
+}
+
+fn emulated_mut(axis: &mut Axis) -> Option<(&mut Button, &mut Button)> {
+    match axis {
+        Axis::Emulated { pos, neg } => Some((pos, neg)),
+        _ => None,
+    }
+}
+
+/// The button of the first binding of an action, if it is a single button.
+fn first_button(bindings: &Bindings<StringBindings>, id: &str) -> Option<Button> {
+    match bindings.action_bindings(id).next() {
+        Some([button]) => Some(*button),
+        _ => None,
+    }
+}
+
+/// Returns the axis or action `button` is bound to anywhere in `bindings`.
+fn user(bindings: &Bindings<StringBindings>, button: Button) -> Option<String> {
+    let axis = bindings.axes().find(|id| {
+        bindings
+            .axis(*id)
+            .and_then(emulated)
+            .is_some_and(|(pos, neg)| pos == button || neg == button)
+    });
+    let action = bindings.actions().find(|id| {
+        bindings
+            .action_bindings(*id)
+            .any(|combo| combo.contains(&button))
+    });
+    axis.or(action).cloned()
+}
+
+/// The top and bottom paddles move left and right.
+fn is_horizontal(id: &str) -> bool {
+    id == "top_paddle" || id == "bottom_paddle"
+}
+
+/// Errors that can happen while changing a binding.
+#[derive(Debug)]
+pub enum RebindError {
+    /// The control is not in the bindings anymore.
+    Unknown(String),
+    /// The button is already bound where the controls screen can not swap it.
+    Taken { button: Button, by: String },
+    /// The changed bindings were refused by the input handler.
+    Invalid(String),
+}
+
+impl fmt::Display for RebindError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RebindError::Unknown(control) => write!(f, "`{}` is not bound", control),
+            RebindError::Taken { button, by } => write!(
+                f,
+                "{} is already used by {}",
+                button_name(*button),
+                by.replace('_', " ")
+            ),
+            RebindError::Invalid(reason) => write!(f, "invalid bindings: {}", reason),
+        }
+    }
+}
+
+impl Error for RebindError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use amethyst::input::{ControllerButton, VirtualKeyCode};
+
+    fn bindings() -> Bindings<StringBindings> {
+        let mut bindings = Bindings::new();
+        bindings
+            .insert_axis(
+                "left_paddle",
+                Axis::Emulated {
+                    pos: Button::Key(VirtualKeyCode::W),
+                    neg: Button::Key(VirtualKeyCode::S),
+                },
+            )
+            .unwrap();
+        bindings
+            .insert_axis(
+                "left_paddle_dpad",
+                Axis::Emulated {
+                    pos: Button::Controller(0, ControllerButton::DPadUp),
+                    neg: Button::Controller(0, ControllerButton::DPadDown),
+                },
+            )
+            .unwrap();
+        bindings
+            .insert_action_binding(
+                "back".to_string(),
+                vec![Button::Key(VirtualKeyCode::Escape)],
+            )
+            .unwrap();
+        bindings
+    }
+
+    #[test]
+    fn controls_are_listed_with_their_buttons() {
+        let bindings = bindings();
+        let controls = Control::all(&bindings);
+        assert_eq!(
+            controls,
+            vec![
+                Control::Positive("left_paddle".to_string()),
+                Control::Negative("left_paddle".to_string()),
+                Control::Action("back".to_string()),
+            ]
+        );
+        assert_eq!(controls[0].label(), "left paddle up");
+        assert_eq!(
+            controls[1].button(&bindings),
+            Some(Button::Key(VirtualKeyCode::S))
+        );
+    }
+
+    #[test]
+    fn free_button_is_bound() {
+        let up = Control::Positive("left_paddle".to_string());
+        let (bindings, swapped) = rebind(&bindings(), &up, Button::Key(VirtualKeyCode::Q)).unwrap();
+        assert_eq!(swapped, None);
+        assert_eq!(up.button(&bindings), Some(Button::Key(VirtualKeyCode::Q)));
+    }
+
+    #[test]
+    fn button_of_another_control_is_swapped() {
+        let up = Control::Positive("left_paddle".to_string());
+        let back = Control::Action("back".to_string());
+        let (bindings, swapped) =
+            rebind(&bindings(), &up, Button::Key(VirtualKeyCode::Escape)).unwrap();
+        assert_eq!(swapped, Some(back.clone()));
+        assert_eq!(
+            up.button(&bindings),
+            Some(Button::Key(VirtualKeyCode::Escape))
+        );
+        assert_eq!(back.button(&bindings), Some(Button::Key(VirtualKeyCode::W)));
+    }
+
+    #[test]
+    fn button_bound_out_of_sight_is_refused() {
+        let back = Control::Action("back".to_string());
+        let pad_up = Button::Controller(0, ControllerButton::DPadUp);
+        match rebind(&bindings(), &back, pad_up) {
+            Err(RebindError::Taken { by, .. }) => assert_eq!(by, "left_paddle_dpad"),
+            result => panic!("unexpected result {:?}", result.map(|(_, swapped)| swapped)),
+        }
+    }
+}
@@ -0,0 +1,505 @@
+//! Paddles played by programs of the players. A bot is a command started as a
+//! child process, or a program listening on a socket for `tcp:<ADDRESS>`.
+//!
+//! The game sends the state of the match as one JSON line, the observation of
+//! the `env` mode, and the bot answers every line with the movement of its
+//! paddle: a number from -1 to 1 on a line of its own. A bot that does not
+//! answer in time leaves its paddle still, and forfeits once it missed too
+//! many answers in a row, closed its connection or answered something else.
+
+use std::{
+    collections::VecDeque,
+    error::Error,
+    fmt,
+    io::{self, BufRead, BufReader, Read, Write},
+    net::{Shutdown, TcpStream, ToSocketAddrs},
+    process::{Child, Command, Stdio},
+    sync::{
+        mpsc::{self, Receiver, RecvTimeoutError, Sender, TryRecvError},
+        Mutex, PoisonError,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use amethyst::ecs::prelude::{Component, DenseVecStorage};
+
+use crate::environment::Observation;
+
+/// Prefix of a bot listening on a socket, the address follows it.
+pub const SOCKET_PREFIX: &str = "tcp:";
+
+/// BotPolicy tells how long bots are waited for.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BotPolicy {
+    /// Time a bot has to answer the first state, while the program starts.
+    pub startup: Duration,
+    /// Time a bot has to answer every other state.
+    pub timeout: Duration,
+    /// Answers a bot may miss in a row before it forfeits.
+    pub forfeit_after: u32,
+}
+
+impl Default for BotPolicy {
+    fn default() -> Self {
+        BotPolicy {
+            startup: Duration::from_secs(5),
+            timeout: Duration::from_millis(50),
+            forfeit_after: 30,
+        }
+    }
+}
+
+/// Why a bot stopped playing.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Forfeit {
+    /// The program could not be started or reached.
+    NotStarted(String),
+    /// It missed `forfeit_after` answers in a row.
+    TooSlow,
+    /// It exited or closed its connection.
+    Gone,
+    /// It answered something else than a movement.
+    InvalidAnswer(String),
+}
+
+impl fmt::Display for Forfeit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Forfeit::NotStarted(e) => write!(f, "could not be started: {}", e),
+            Forfeit::TooSlow => write!(f, "too slow to answer"),
+            Forfeit::Gone => write!(f, "exited or closed its connection"),
+            Forfeit::InvalidAnswer(answer) => {
+                write!(f, "answered `{}`, expected a number from -1 to 1", answer)
+            }
+        }
+    }
+}
+
+impl Error for Forfeit {}
+
+/// What is stopped when the bot is dropped.
+enum Connection {
+    Process(Child),
+    Socket(TcpStream),
+}
+
+/// Bot plays a paddle with a program. The states are written and the answers
+/// read on threads of their own: `play` never waits for the bot, and
+/// `movement` waits at most its timeout.
+pub struct Bot {
+    /// The command or address the bot was started with.
+    pub name: String,
+    policy: BotPolicy,
+    connection: Connection,
+    /// Lines for the writing thread.
+    states: Mutex<Sender<String>>,
+    /// Answers of the bot, or why it can not play anymore.
+    answers: Mutex<Receiver<Result<String, Forfeit>>>,
+    /// Whether the first state is still to be sent.
+    starting: bool,
+    /// Answers to states the bot was too slow for, they are skipped when they
+    /// come so the next answer is the one to the last state.
+    late: u32,
+    /// When the states still waiting for an answer were sent, oldest first,
+    /// while the bot plays without being waited for.
+    unanswered: VecDeque<Instant>,
+    /// Movement of the last answer, played until the next one.
+    last_movement: f32,
+    misses: u32,
+    forfeit: Option<Forfeit>,
+}
+
+impl Bot {
+    /// Starts the bot of `spec`: connects to the address of a `tcp:<ADDRESS>`
+    /// spec, or runs it as a command, its words split on whitespace. The
+    /// standard error of a command goes to the one of the game.
+    pub fn start(spec: &str, policy: BotPolicy) -> io::Result<Bot> {
+        if let Some(address) = spec.strip_prefix(SOCKET_PREFIX) {
+            let stream = connect(address, policy.startup)?;
+            stream.set_nodelay(true)?;
+            // A bot that does not read its states is as late as one that
+            // does not answer them.
+            stream.set_write_timeout(Some(policy.timeout))?;
+            let reader = stream.try_clone()?;
+            let writer = stream.try_clone()?;
+            return Ok(Bot::new(
+                spec,
+                Connection::Socket(stream),
+                reader,
+                writer,
+                policy,
+            ));
+        }
+
+        let mut words = spec.split_whitespace();
+        let program = words
+            .next()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "empty bot command"))?;
+        let mut child = Command::new(program)
+            .args(words)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::inherit())
+            .spawn()?;
+        let (stdin, stdout) = match (child.stdin.take(), child.stdout.take()) {
+            (Some(stdin), Some(stdout)) => (stdin, stdout),
+            _ => {
+                let _ = child.kill();
+                return Err(io::Error::new(
+                    io::ErrorKind::BrokenPipe,
+                    "the bot has no standard input or output",
+                ));
+            }
+        };
+        Ok(Bot::new(
+            spec,
+            Connection::Process(child),
+            stdout,
+            stdin,
+            policy,
+        ))
+    }
+
+    fn new<R, W>(
+        spec: &str,
+        connection: Connection,
+        reader: R,
+        mut writer: W,
+        policy: BotPolicy,
+    ) -> Bot
+    where
+        R: Read + Send + 'static,
+        W: Write + Send + 'static,
+    {
+        let (sender, answers) = mpsc::channel();
+        let failures = sender.clone();
+        thread::spawn(move || {
+            for line in BufReader::new(reader).lines() {
+                let line = match line {
+                    Ok(line) => line,
+                    Err(_) => break,
+                };
+                if sender.send(Ok(line)).is_err() {
+                    return;
+                }
+            }
+            let _ = sender.send(Err(Forfeit::Gone));
+        });
+
+        let (states, lines) = mpsc::channel::<String>();
+        thread::spawn(move || {
+            for line in lines {
+                if let Err(e) = writer
+                    .write_all(line.as_bytes())
+                    .and_then(|_| writer.flush())
+                {
+                    let forfeit = match e.kind() {
+                        io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut => Forfeit::TooSlow,
+                        _ => Forfeit::Gone,
+                    };
+                    let _ = failures.send(Err(forfeit));
+                    return;
+                }
+            }
+        });
+
+        Bot {
+            name: spec.to_string(),
+            policy,
+            connection,
+            states: Mutex::new(states),
+            answers: Mutex::new(answers),
+            starting: true,
+            late: 0,
+            unanswered: VecDeque::new(),
+            last_movement: 0.0,
+            misses: 0,
+            forfeit: None,
+        }
+    }
+
+    /// Why the bot stopped playing, once it did.
+    pub fn forfeit(&self) -> Option<&Forfeit> {
+        self.forfeit.as_ref()
+    }
+
+    /// Sends `state` to the bot and returns its movement. The paddle stands
+    /// still when the answer is late, and for good once the bot forfeited.
+    pub fn movement(&mut self, state: &Observation) -> f32 {
+        if self.forfeit.is_some() {
+            return 0.0;
+        }
+        match self.ask(state) {
+            Ok(Some(movement)) => {
+                self.misses = 0;
+                movement
+            }
+            Ok(None) => {
+                self.misses += 1;
+                if self.misses >= self.policy.forfeit_after {
+                    self.forfeit = Some(Forfeit::TooSlow);
+                }
+                0.0
+            }
+            Err(forfeit) => {
+                self.forfeit = Some(forfeit);
+                0.0
+            }
+        }
+    }
+
+    /// Sends `state` to the bot without waiting for its answer, and returns
+    /// the movement of the last answer received. A state left unanswered past
+    /// the timeout is a missed answer, the paddle stands still until the next
+    /// one.
+    pub fn play(&mut self, state: &Observation) -> f32 {
+        if self.forfeit.is_some() {
+            return 0.0;
+        }
+        self.send(state);
+        self.unanswered.push_back(Instant::now());
+        match self.receive() {
+            Ok(()) => self.last_movement,
+            Err(forfeit) => {
+                self.forfeit = Some(forfeit);
+                0.0
+            }
+        }
+    }
+
+    /// Takes the answers received so far, then counts the states still
+    /// unanswered past the timeout as missed.
+    fn receive(&mut self) -> Result<(), Forfeit> {
+        let answers = self
+            .answers
+            .get_mut()
+            .unwrap_or_else(PoisonError::into_inner);
+        loop {
+            match answers.try_recv() {
+                Ok(Err(forfeit)) => return Err(forfeit),
+                Ok(Ok(_)) if self.late > 0 => self.late -= 1,
+                Ok(Ok(answer)) => {
+                    self.unanswered.pop_front();
+                    self.last_movement = parse_movement(&answer)?;
+                    self.starting = false;
+                    self.misses = 0;
+                }
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => return Err(Forfeit::Gone),
+            }
+        }
+
+        while let Some(sent) = self.unanswered.front() {
+            if sent.elapsed() <= self.timeout() {
+                break;
+            }
+            self.unanswered.pop_front();
+            self.starting = false;
+            self.late += 1;
+            self.last_movement = 0.0;
+            self.misses += 1;
+            if self.misses >= self.policy.forfeit_after {
+                return Err(Forfeit::TooSlow);
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the answer to `state`, or `None` when it did not come in time.
+    fn ask(&mut self, state: &Observation) -> Result<Option<f32>, Forfeit> {
+        self.send(state);
+        let timeout = self.timeout();
+        self.starting = false;
+        let deadline = Instant::now() + timeout;
+        let answers = self
+            .answers
+            .get_mut()
+            .unwrap_or_else(PoisonError::into_inner);
+        loop {
+            match answers.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+                Ok(Err(forfeit)) => return Err(forfeit),
+                Ok(Ok(_)) if self.late > 0 => self.late -= 1,
+                Ok(Ok(answer)) => return parse_movement(&answer).map(Some),
+                Err(RecvTimeoutError::Timeout) => {
+                    self.late += 1;
+                    return Ok(None);
+                }
+                Err(RecvTimeoutError::Disconnected) => return Err(Forfeit::Gone),
+            }
+        }
+    }
+
+    fn send(&mut self, state: &Observation) {
+        let mut line = serde_json::to_string(state).expect("an observation is always valid JSON");
+        line.push('\n');
+        // The writing thread only stops after a failed write, it tells why
+        // with the answers.
+        let _ = self
+            .states
+            .get_mut()
+            .unwrap_or_else(PoisonError::into_inner)
+            .send(line);
+    }
+
+    /// Time the bot has to answer its next state.
+    fn timeout(&self) -> Duration {
+        if self.starting {
+            self.policy.startup
+        } else {
+            self.policy.timeout
+        }
+    }
+}
+
+impl Drop for Bot {
+    /// Stops the program, the threads reading and writing its lines end with it.
+    fn drop(&mut self) {
+        match &mut self.connection {
+            Connection::Process(child) => {
+                let _ = child.kill();
+                let _ = child.wait();
+            }
+            Connection::Socket(stream) => {
+                let _ = stream.shutdown(Shutdown::Both);
+            }
+        }
+    }
+}
+
+impl Component for Bot {
+    type Storage = DenseVecStorage<Self>;
+}
+
+fn connect(address: &str, timeout: Duration) -> io::Result<TcpStream> {
+    let mut last_error = None;
+    for address in address.to_socket_addrs()? {
+        match TcpStream::connect_timeout(&address, timeout) {
+            Ok(stream) => return Ok(stream),
+            Err(e) => last_error = Some(e),
+        }
+    }
+    Err(last_error.unwrap_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::AddrNotAvailable,
+            "the address resolves to nothing",
+        )
+    }))
+}
+
+fn parse_movement(answer: &str) -> Result<f32, Forfeit> {
+    match answer.trim().parse::<f32>() {
+        Ok(movement) if movement.is_finite() => Ok(movement.clamp(-1.0, 1.0)),
+        _ => Err(Forfeit::InvalidAnswer(answer.to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::TcpListener;
+
+    use super::*;
+    use crate::rules::GameRules;
+    use crate::sim::{Side, Simulation};
+
+    /// Starts a bot on a local socket that answers the state of every tick
+    /// with `answer`, and closes the connection when it returns `None`.
+    fn socket_bot(policy: BotPolicy, answer: fn(u64) -> Option<&'static str>) -> Bot {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut writer = stream.try_clone().unwrap();
+            // The game may hang up first, the bot stops with it.
+            for line in BufReader::new(stream).lines() {
+                let line = match line {
+                    Ok(line) => line,
+                    Err(_) => return,
+                };
+                let state: serde_json::Value = serde_json::from_str(&line).unwrap();
+                match answer(state["tick"].as_u64().unwrap()) {
+                    Some(movement) if writeln!(writer, "{}", movement).is_ok() => {}
+                    _ => return,
+                }
+            }
+        });
+        Bot::start(&format!("tcp:{}", address), policy).unwrap()
+    }
+
+    fn state(tick: u64) -> Observation {
+        Observation::new(&Simulation::new(GameRules::default()), tick, Side::Left)
+    }
+
+    #[test]
+    fn answers_move_the_paddle() {
+        let mut bot = socket_bot(BotPolicy::default(), |tick| match tick {
+            0 => Some("0.5"),
+            1 => Some("-3"),
+            _ => Some("up"),
+        });
+        assert_eq!(bot.movement(&state(0)), 0.5);
+        assert_eq!(bot.movement(&state(1)), -1.0);
+        assert_eq!(bot.movement(&state(2)), 0.0);
+        assert_eq!(
+            bot.forfeit(),
+            Some(&Forfeit::InvalidAnswer("up".to_string()))
+        );
+    }
+
+    #[test]
+    fn slow_and_gone_bots_forfeit() {
+        let policy = BotPolicy {
+            startup: Duration::from_millis(20),
+            timeout: Duration::from_millis(20),
+            forfeit_after: 3,
+        };
+        let mut slow = socket_bot(policy, |_| {
+            thread::sleep(Duration::from_millis(200));
+            Some("1")
+        });
+        for tick in 0..3 {
+            assert_eq!(slow.forfeit(), None);
+            assert_eq!(slow.movement(&state(tick)), 0.0);
+        }
+        assert_eq!(slow.forfeit(), Some(&Forfeit::TooSlow));
+
+        let mut gone = socket_bot(BotPolicy::default(), |_| None);
+        assert_eq!(gone.movement(&state(0)), 0.0);
+        assert_eq!(gone.forfeit(), Some(&Forfeit::Gone));
+    }
+
+    #[test]
+    fn playing_never_waits_for_the_answer() {
+        let policy = BotPolicy {
+            startup: Duration::from_millis(100),
+            timeout: Duration::from_millis(100),
+            forfeit_after: 3,
+        };
+        let mut bot = socket_bot(policy, |_| Some("0.5"));
+        let started = Instant::now();
+        let mut tick = 0;
+        while bot.play(&state(tick)) != 0.5 {
+            assert!(started.elapsed() < Duration::from_secs(5));
+            tick += 1;
+            thread::sleep(Duration::from_millis(1));
+        }
+
+        let mut slow = socket_bot(policy, |_| {
+            thread::sleep(Duration::from_secs(1));
+            Some("1")
+        });
+        let started = Instant::now();
+        for tick in 0..3 {
+            assert_eq!(slow.play(&state(tick)), 0.0);
+        }
+        assert!(started.elapsed() < Duration::from_millis(50));
+        assert_eq!(slow.forfeit(), None);
+
+        while slow.forfeit().is_none() {
+            assert!(started.elapsed() < Duration::from_millis(900));
+            slow.play(&state(3));
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(slow.forfeit(), Some(&Forfeit::TooSlow));
+    }
+}
@@ -0,0 +1,483 @@
+//! Command line of the game: the mode it starts in, the files it reads and
+//! the overrides of the config files.
+
+use std::{error::Error, fmt, path::PathBuf};
+
+use crate::ai::{AiConfig, Difficulty};
+use crate::batch::{Format, Vary};
+use crate::sim::Side;
+
+/// Port the host listens on, and a peer, when none is given.
+pub const DEFAULT_PORT: u16 = 7777;
+
+pub const USAGE: &str = "\
+Usage: rust-pong [MODE] [OPTIONS]
+
+Modes:
+  menu               Open the title screen (default)
+  pvp                Start a match between human players
+  vs-ai              Start a match against the computer
+  ai-vs-ai           Start a match between computer players
+  replay <FILE>      Play a recorded match
+  host [PORT]        Wait for a player to join a network match
+  join <ADDRESS>     Join the match of a host
+  peer <ADDRESS>     Play a rollback match with another peer
+  batch              Play computer matches headless and report statistics
+  env                Serve a training environment over stdin and stdout
+  tournament <BOT> <BOT>...
+                     Play matches between every pair of bots, headless
+
+Options:
+  --seed <N>         Seed of the matches, the same seed plays the same match
+  --rules <FILE>     Game rules to play with [default: config/rules.ron]
+  --bindings <FILE>  Keys and buttons [default: config/bindings.ron]
+  --display <FILE>   Window settings [default: config/display.ron]
+  --record <FILE>    Record the matches played to a replay file
+  --headless         Play without a window, in the ai-vs-ai and replay modes
+  --frames <N>       Quit after N frames; headless, a frame is a simulation tick
+                     and a batch stops every match after N
+  --ai-left <LEVEL>  Computer playing the left paddle: easy, normal, hard or off,
+                     --ai-right, --ai-top and --ai-bottom for the other paddles
+  --mouse <SIDE>     Play the paddle of SIDE with the mouse
+  --bot-left <BOT>   Bot playing the left paddle: a command or tcp:<ADDRESS>,
+                     --bot-right, --bot-top and --bot-bottom for the other paddles
+  --port <PORT>      Port a peer listens on [default: 7777]
+  --side <SIDE>      Paddle of a peer or of the agent of env, left or right
+                     [default: left]
+  --matches <N>      Matches of every rule variant of a batch [default: 100], or
+                     of every pair of bots of a tournament [default: 2]
+  --vary <FIELD>=<VALUE>,<VALUE>...
+                     Rule values a batch compares, every combination is played
+  --format <FORMAT>  Report of a batch, json or csv [default: json]
+  --output <FILE>    File the report of a batch goes to [default: stdout]
+  --ticks-per-step <N>
+                     Ticks played by every step of env [default: 4]
+  -h, --help         Print this help
+";
+
+/// Mode tells what the game starts with.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum Mode {
+    /// The title screen, every match starts from it.
+    #[default]
+    Menu,
+    /// A match between human players, every paddle on the keyboard and pads.
+    Pvp,
+    /// A match of the left paddle against the computer.
+    VsAi,
+    /// A match played by the computer alone.
+    AiVsAi,
+    Replay(PathBuf),
+    Host(u16),
+    Join(String),
+    Peer {
+        address: String,
+        port: u16,
+        side: Side,
+    },
+    /// Matches played by the computer alone, headless, for their statistics.
+    Batch,
+    /// A training environment driven over stdin and stdout, the agent plays
+    /// the paddle of `side`.
+    Env {
+        side: Side,
+    },
+    /// Matches between every pair of bots, headless.
+    Tournament(Vec<String>),
+}
+
+impl Mode {
+    /// Whether the mode can be played without a window.
+    pub fn is_headless(&self) -> bool {
+        matches!(
+            self,
+            Mode::AiVsAi | Mode::Replay(_) | Mode::Batch | Mode::Env { .. } | Mode::Tournament(_)
+        )
+    }
+
+    /// The computer players of the mode, out of those of `ai`: nobody in pvp,
+    /// every paddle but the left one in vs-ai, every paddle but the one of
+    /// the agent in env, and every paddle in ai-vs-ai and batch. A paddle
+    /// without a difficulty in `ai` gets a normal one. The other modes keep
+    /// `ai`.
+    pub fn computers(&self, ai: &AiConfig, sides: &[Side]) -> AiConfig {
+        let plays = |side: Side| match self {
+            Mode::Pvp => false,
+            Mode::VsAi => side != Side::Left,
+            Mode::Env { side: agent } => side != *agent,
+            _ => true,
+        };
+        match self {
+            Mode::Pvp | Mode::VsAi | Mode::AiVsAi | Mode::Batch | Mode::Env { .. } => {}
+            _ => return ai.clone(),
+        }
+        let mut computers = AiConfig::default();
+        for side in sides.iter().filter(|side| plays(**side)) {
+            computers.set(*side, Some(ai.get(*side).unwrap_or(Difficulty::Normal)));
+        }
+        computers
+    }
+}
+
+/// Args are the parsed command line.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Args {
+    pub mode: Mode,
+    pub seed: Option<u64>,
+    pub rules: Option<PathBuf>,
+    pub bindings: Option<PathBuf>,
+    pub display: Option<PathBuf>,
+    pub record: Option<PathBuf>,
+    pub headless: bool,
+    pub frames: Option<u64>,
+    /// The `--ai-*`, `--mouse` and `--bot-*` arguments, applied by
+    /// `AiConfig::apply_args` and `ControlConfig::apply_args`.
+    pub overrides: Vec<String>,
+    pub help: bool,
+    /// Options of the batch mode, `--matches` is one of the tournament mode
+    /// as well.
+    pub matches: Option<u32>,
+    pub vary: Vec<Vary>,
+    pub format: Option<Format>,
+    pub output: Option<PathBuf>,
+    /// Ticks played by every step of the env mode.
+    pub ticks_per_step: Option<u32>,
+}
+
+impl Args {
+    /// Parses the command line, without the name of the program.
+    pub fn parse<I: IntoIterator<Item = String>>(args: I) -> Result<Args, ArgsError> {
+        let mut parsed = Args::default();
+        let mut positionals = Vec::new();
+        let mut port = None;
+        let mut side = None;
+
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            let mut value = || {
+                args.next()
+                    .ok_or_else(|| ArgsError::MissingValue(arg.clone()))
+            };
+            match arg.as_str() {
+                "-h" | "--help" => parsed.help = true,
+                "--headless" => parsed.headless = true,
+                "--seed" => parsed.seed = Some(number(&arg, value()?)?),
+                "--frames" => {
+                    let frames = number(&arg, value()?)?;
+                    if frames == 0 {
+                        return Err(ArgsError::invalid(&arg, "0", "at least 1 frame"));
+                    }
+                    parsed.frames = Some(frames);
+                }
+                "--rules" => parsed.rules = Some(value()?.into()),
+                "--bindings" => parsed.bindings = Some(value()?.into()),
+                "--display" => parsed.display = Some(value()?.into()),
+                "--record" => parsed.record = Some(value()?.into()),
+                "--port" => port = Some(number(&arg, value()?)?),
+                "--side" => {
+                    side = Some(match value()?.as_str() {
+                        "left" => Side::Left,
+                        "right" => Side::Right,
+                        other => return Err(ArgsError::invalid(&arg, other, "left or right")),
+                    })
+                }
+                "--matches" => {
+                    let matches = number(&arg, value()?)?;
+                    if matches == 0 {
+                        return Err(ArgsError::invalid(&arg, "0", "at least 1 match"));
+                    }
+                    parsed.matches = Some(matches);
+                }
+                "--vary" => {
+                    let vary = value()?;
+                    parsed.vary.push(vary.parse().map_err(|_| {
+                        ArgsError::invalid(&arg, &vary, "<FIELD>=<VALUE>,<VALUE>...")
+                    })?)
+                }
+                "--format" => {
+                    let format = value()?;
+                    parsed.format = Some(
+                        format
+                            .parse()
+                            .map_err(|_| ArgsError::invalid(&arg, &format, "json or csv"))?,
+                    )
+                }
+                "--output" => parsed.output = Some(value()?.into()),
+                "--ticks-per-step" => {
+                    let ticks = number(&arg, value()?)?;
+                    if ticks == 0 {
+                        return Err(ArgsError::invalid(&arg, "0", "at least 1 tick"));
+                    }
+                    parsed.ticks_per_step = Some(ticks);
+                }
+                "--ai-left" | "--ai-right" | "--ai-top" | "--ai-bottom" | "--mouse"
+                | "--bot-left" | "--bot-right" | "--bot-top" | "--bot-bottom" => {
+                    let value = value()?;
+                    parsed.overrides.push(arg);
+                    parsed.overrides.push(value);
+                }
+                _ if arg.starts_with('-') => return Err(ArgsError::Unknown(arg)),
+                _ => positionals.push(arg),
+            }
+        }
+        if parsed.help {
+            return Ok(parsed);
+        }
+
+        let mut positionals = positionals.into_iter();
+        let mode = positionals.next();
+        let mut operand = |what: &str| {
+            positionals
+                .next()
+                .ok_or_else(|| ArgsError::MissingValue(format!("{} {}", mode_name(&mode), what)))
+        };
+        parsed.mode = match mode.as_deref() {
+            None | Some("menu") => Mode::Menu,
+            Some("pvp") => Mode::Pvp,
+            Some("vs-ai") => Mode::VsAi,
+            Some("ai-vs-ai") => Mode::AiVsAi,
+            Some("replay") => Mode::Replay(operand("<FILE>")?.into()),
+            Some("host") => match positionals.next() {
+                Some(port) => Mode::Host(number("host", port)?),
+                None => Mode::Host(DEFAULT_PORT),
+            },
+            Some("join") => Mode::Join(operand("<ADDRESS>")?),
+            Some("peer") => Mode::Peer {
+                address: operand("<ADDRESS>")?,
+                port: port.take().unwrap_or(DEFAULT_PORT),
+                side: side.take().unwrap_or(Side::Left),
+            },
+            Some("batch") => Mode::Batch,
+            Some("env") => Mode::Env {
+                side: side.take().unwrap_or(Side::Left),
+            },
+            Some("tournament") => {
+                let bots: Vec<String> = positionals.by_ref().collect();
+                if bots.len() < 2 {
+                    return Err(ArgsError::MissingValue("tournament <BOT> <BOT>".into()));
+                }
+                Mode::Tournament(bots)
+            }
+            Some(other) => return Err(ArgsError::UnknownMode(other.to_string())),
+        };
+        if let Some(extra) = positionals.next() {
+            return Err(ArgsError::Unexpected(extra));
+        }
+
+        // Options that only make sense in some modes are refused elsewhere,
+        // rather than silently ignored.
+        if port.is_some() {
+            return Err(ArgsError::Conflict(
+                "`--port` only applies to the peer mode".into(),
+            ));
+        }
+        if side.is_some() {
+            return Err(ArgsError::Conflict(
+                "`--side` only applies to the peer and env modes".into(),
+            ));
+        }
+        let batch_options =
+            !parsed.vary.is_empty() || parsed.format.is_some() || parsed.output.is_some();
+        if batch_options && parsed.mode != Mode::Batch {
+            return Err(ArgsError::Conflict(
+                "`--vary`, `--format` and `--output` only apply to the batch mode".into(),
+            ));
+        }
+        if parsed.matches.is_some() {
+            if let Mode::Batch | Mode::Tournament(_) = parsed.mode {
+            } else {
+                return Err(ArgsError::Conflict(
+                    "`--matches` only applies to the batch and tournament modes".into(),
+                ));
+            }
+        }
+        if parsed.ticks_per_step.is_some() {
+            if let Mode::Env { .. } = parsed.mode {
+            } else {
+                return Err(ArgsError::Conflict(
+                    "`--ticks-per-step` only applies to the env mode".into(),
+                ));
+            }
+        }
+        // Batches, environments and tournaments are always played headless.
+        if let Mode::Batch | Mode::Env { .. } | Mode::Tournament(_) = parsed.mode {
+            parsed.headless = true;
+        }
+        if parsed.headless && !parsed.mode.is_headless() {
+            return Err(ArgsError::Conflict(
+                "`--headless` needs the ai-vs-ai, replay, batch, env or tournament mode, nobody could play otherwise"
+                    .into(),
+            ));
+        }
+        if parsed.record.is_some() {
+            if let Mode::Replay(_)
+            | Mode::Join(_)
+            | Mode::Peer { .. }
+            | Mode::Batch
+            | Mode::Env { .. } = parsed.mode
+            {
+                return Err(ArgsError::Conflict(format!(
+                    "`--record` does not apply to the {} mode",
+                    mode_name(&mode)
+                )));
+            }
+        }
+        Ok(parsed)
+    }
+}
+
+fn mode_name(mode: &Option<String>) -> &str {
+    mode.as_deref().unwrap_or("menu")
+}
+
+fn number<T: std::str::FromStr>(name: &str, value: String) -> Result<T, ArgsError> {
+    value
+        .parse()
+        .map_err(|_| ArgsError::invalid(name, &value, "a positive number"))
+}
+
+/// Errors of the command line, reported with a pointer to `--help`.
+#[derive(Debug, PartialEq)]
+pub enum ArgsError {
+    Unknown(String),
+    UnknownMode(String),
+    /// A positional argument after the mode and its operand.
+    Unexpected(String),
+    /// An option or a mode without its value.
+    MissingValue(String),
+    Invalid {
+        name: String,
+        value: String,
+        expected: String,
+    },
+    /// Options that do not go together.
+    Conflict(String),
+}
+
+impl ArgsError {
+    fn invalid(name: &str, value: &str, expected: &str) -> ArgsError {
+        ArgsError::Invalid {
+            name: name.to_string(),
+            value: value.to_string(),
+            expected: expected.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for ArgsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ArgsError::Unknown(arg) => write!(f, "unknown option `{}`", arg),
+            ArgsError::UnknownMode(mode) => write!(
+                f,
+                "unknown mode `{}`, expected menu, pvp, vs-ai, ai-vs-ai, replay, host, join, peer, batch, env or tournament",
+                mode
+            ),
+            ArgsError::Unexpected(arg) => write!(f, "unexpected argument `{}`", arg),
+            ArgsError::MissingValue(name) => write!(f, "missing value after `{}`", name),
+            ArgsError::Invalid {
+                name,
+                value,
+                expected,
+            } => write!(f, "invalid `{}` value `{}`, expected {}", name, value, expected),
+            ArgsError::Conflict(reason) => f.write_str(reason),
+        }
+    }
+}
+
+impl Error for ArgsError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(line: &str) -> Result<Args, ArgsError> {
+        Args::parse(line.split_whitespace().map(String::from))
+    }
+
+    #[test]
+    fn mode_and_options_are_parsed() {
+        let args = parse("ai-vs-ai --seed 42 --headless --frames 600 --ai-left hard").unwrap();
+        assert_eq!(args.mode, Mode::AiVsAi);
+        assert_eq!(args.seed, Some(42));
+        assert!(args.headless);
+        assert_eq!(args.frames, Some(600));
+        assert_eq!(args.overrides, vec!["--ai-left", "hard"]);
+
+        assert_eq!(
+            parse("env --side right --ticks-per-step 2").unwrap().mode,
+            Mode::Env { side: Side::Right }
+        );
+        let args = parse("batch --matches 500 --vary paddle_height=12,16 --format csv").unwrap();
+        assert_eq!(args.mode, Mode::Batch);
+        assert!(args.headless);
+        assert_eq!(args.matches, Some(500));
+        assert_eq!(args.vary[0].values, vec!["12", "16"]);
+        assert_eq!(args.format, Some(Format::Csv));
+
+        let args = parse("--matches 4 tournament ./a ./b ./c").unwrap();
+        assert_eq!(
+            args.mode,
+            Mode::Tournament(vec!["./a".into(), "./b".into(), "./c".into()])
+        );
+        assert_eq!(args.matches, Some(4));
+        assert_eq!(
+            parse("pvp --bot-right tcp:localhost:9000")
+                .unwrap()
+                .overrides,
+            vec!["--bot-right", "tcp:localhost:9000"]
+        );
+
+        assert_eq!(parse("").unwrap().mode, Mode::Menu);
+        assert_eq!(parse("host").unwrap().mode, Mode::Host(DEFAULT_PORT));
+        assert_eq!(
+            parse("peer 10.0.0.2:7777 --side right").unwrap().mode,
+            Mode::Peer {
+                address: "10.0.0.2:7777".to_string(),
+                port: DEFAULT_PORT,
+                side: Side::Right,
+            }
+        );
+    }
+
+    #[test]
+    fn invalid_arguments_are_refused() {
+        assert_eq!(
+            parse("--sed 4"),
+            Err(ArgsError::Unknown("--sed".to_string()))
+        );
+        assert_eq!(
+            parse("pvp --seed"),
+            Err(ArgsError::MissingValue("--seed".to_string()))
+        );
+        assert_eq!(
+            parse("replay"),
+            Err(ArgsError::MissingValue("replay <FILE>".to_string()))
+        );
+        assert_eq!(
+            parse("tournament ./a"),
+            Err(ArgsError::MissingValue(
+                "tournament <BOT> <BOT>".to_string()
+            ))
+        );
+        assert_eq!(
+            parse("--frames -3"),
+            Err(ArgsError::invalid("--frames", "-3", "a positive number"))
+        );
+        for line in &[
+            "pvp --headless",
+            "vs-ai --port 3",
+            "replay a.ron --record b.ron",
+            "ai-vs-ai --matches 10",
+        ] {
+            match parse(line) {
+                Err(ArgsError::Conflict(_)) => {}
+                result => panic!("`{}` gave {:?}", line, result),
+            }
+        }
+        assert_eq!(
+            parse("pvp now"),
+            Err(ArgsError::Unexpected("now".to_string()))
+        );
+    }
+}
@@ -0,0 +1,183 @@
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::bot::BotPolicy;
+use crate::sim::{Paddle, Side};
+
+/// Time, in seconds, a paddle following the mouse gives itself to reach the
+/// pointer. It slows down on the way in instead of shaking around it.
+const APPROACH_TIME: f32 = 0.1;
+
+/// What a player moves a paddle with.
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+pub enum InputSource {
+    /// The `<side>_paddle` axes of `config/bindings.ron`: keys and gamepads.
+    #[default]
+    Bindings,
+    /// The paddle follows the mouse pointer along its side.
+    Mouse,
+    /// A program of the player moves the paddle, see the `bot` module: a
+    /// command, or `tcp:<ADDRESS>` for a bot listening on a socket.
+    Bot(String),
+}
+
+/// ControlConfig tells what every player who is not the computer plays with.
+/// It is loaded from `config/controls.ron`, `--mouse <side>` hands a paddle to
+/// the mouse and `--bot-<side> <BOT>` to a bot.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ControlConfig {
+    pub left: InputSource,
+    pub right: InputSource,
+    pub top: InputSource,
+    pub bottom: InputSource,
+    /// Top speed of a paddle following the mouse, as a fraction of the
+    /// paddle top speed.
+    pub mouse_speed: f32,
+    /// Seconds a bot has to answer the first state, while it starts.
+    pub bot_startup: f32,
+    /// Seconds a bot has to answer every other state.
+    pub bot_timeout: f32,
+    /// Answers a bot may miss in a row before it forfeits.
+    pub bot_forfeit_after: u32,
+}
+
+impl Default for ControlConfig {
+    fn default() -> Self {
+        ControlConfig {
+            left: InputSource::Bindings,
+            right: InputSource::Bindings,
+            top: InputSource::Bindings,
+            bottom: InputSource::Bindings,
+            mouse_speed: 0.9,
+            bot_startup: 5.0,
+            bot_timeout: 0.05,
+            bot_forfeit_after: 30,
+        }
+    }
+}
+
+impl ControlConfig {
+    pub fn get(&self, side: Side) -> &InputSource {
+        match side {
+            Side::Left => &self.left,
+            Side::Right => &self.right,
+            Side::Top => &self.top,
+            Side::Bottom => &self.bottom,
+        }
+    }
+
+    pub fn bot_policy(&self) -> BotPolicy {
+        BotPolicy {
+            startup: Duration::from_secs_f32(self.bot_startup),
+            timeout: Duration::from_secs_f32(self.bot_timeout),
+            forfeit_after: self.bot_forfeit_after,
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if !(self.mouse_speed > 0.0 && self.mouse_speed <= 1.0) {
+            return Err(format!(
+                "invalid control `mouse_speed`: {} must be greater than zero and at most 1",
+                self.mouse_speed
+            ));
+        }
+        if !(self.bot_startup > 0.0 && self.bot_timeout > 0.0) {
+            return Err(format!(
+                "invalid control `bot_startup` or `bot_timeout`: {} and {} must be greater than zero",
+                self.bot_startup, self.bot_timeout
+            ));
+        }
+        if self.bot_forfeit_after == 0 {
+            return Err("invalid control `bot_forfeit_after`: must be at least 1".to_string());
+        }
+        Ok(())
+    }
+
+    /// Applies the `--mouse <left|right|top|bottom>` and `--bot-<side> <BOT>`
+    /// arguments.
+    pub fn apply_args<I: Iterator<Item = String>>(&mut self, mut args: I) -> Result<(), String> {
+        while let Some(arg) = args.next() {
+            let bot_slot = match arg.as_str() {
+                "--mouse" => None,
+                "--bot-left" => Some(&mut self.left),
+                "--bot-right" => Some(&mut self.right),
+                "--bot-top" => Some(&mut self.top),
+                "--bot-bottom" => Some(&mut self.bottom),
+                _ => continue,
+            };
+            if let Some(slot) = bot_slot {
+                *slot = InputSource::Bot(
+                    args.next()
+                        .ok_or_else(|| format!("missing bot after `{}`", arg))?,
+                );
+                continue;
+            }
+            let value = args
+                .next()
+                .ok_or_else(|| format!("missing side after `{}`", arg))?;
+            let slot = match value.as_str() {
+                "left" => &mut self.left,
+                "right" => &mut self.right,
+                "top" => &mut self.top,
+                "bottom" => &mut self.bottom,
+                _ => {
+                    return Err(format!(
+                        "invalid side `{}`, expected left, right, top or bottom",
+                        value
+                    ))
+                }
+            };
+            *slot = InputSource::Mouse;
+        }
+        Ok(())
+    }
+}
+
+/// Returns the movement bringing `paddle` to `target` along its side, no
+/// faster than `max_speed` times its top speed.
+pub fn follow(paddle: &Paddle, target: f32, max_speed: f32) -> f32 {
+    let distance = target - paddle.position[paddle.side.axis()];
+    (distance / APPROACH_TIME / paddle.max_speed)
+        .max(-max_speed)
+        .min(max_speed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rules::GameRules;
+
+    fn args(line: &str) -> impl Iterator<Item = String> + '_ {
+        line.split_whitespace().map(String::from)
+    }
+
+    #[test]
+    fn mouse_argument_hands_a_side_to_the_mouse() {
+        let mut controls = ControlConfig::default();
+        controls.apply_args(args("pong pvp --mouse right")).unwrap();
+        assert_eq!(controls.left, InputSource::Bindings);
+        assert_eq!(controls.right, InputSource::Mouse);
+    }
+
+    #[test]
+    fn mouse_argument_needs_a_side() {
+        let mut controls = ControlConfig::default();
+        assert!(controls.apply_args(args("pong --mouse")).is_err());
+        assert!(controls.apply_args(args("pong --mouse middle")).is_err());
+        assert!(ControlConfig::default().validate().is_ok());
+    }
+
+    #[test]
+    fn paddle_heads_for_the_pointer_and_slows_down_near_it() {
+        let rules = GameRules::default();
+        let paddle = Paddle::new(Side::Left, &rules);
+        let y = paddle.position[1];
+        assert_eq!(follow(&paddle, y, 0.9), 0.0);
+        assert_eq!(follow(&paddle, y + rules.arena_height, 0.9), 0.9);
+        assert_eq!(follow(&paddle, y - rules.arena_height, 0.9), -0.9);
+        let near = follow(&paddle, y + 0.1, 0.9);
+        assert!(near > 0.0 && near < 0.9);
+    }
+}
@@ -0,0 +1,356 @@
+//! The headless simulation as an environment to train paddle agents, gym
+//! style, and the line based JSON protocol of the `env` mode that lets an
+//! external trainer drive it.
+
+use std::{
+    error::Error,
+    fmt,
+    io::{self, BufRead, Write},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::ai::AiConfig;
+use crate::headless::AiMatch;
+use crate::level::Level;
+use crate::rules::GameRules;
+use crate::sim::{BallState, Inputs, Side, SimEvent, Simulation};
+
+/// Ticks played by a step when `--ticks-per-step` is not given, the agent
+/// decides 30 times per second.
+pub const DEFAULT_TICKS_PER_STEP: u32 = 4;
+
+/// Environment is a match between an agent playing the paddle of one side
+/// and the computer playing the others. Every `step` plays the action of the
+/// agent and rewards it with 1 for every point it scores and -1 for every
+/// point another side scores.
+pub struct Environment {
+    rules: GameRules,
+    level: Level,
+    side: Side,
+    ai: AiConfig,
+    /// Ticks played by every step, the action is held during all of them.
+    ticks_per_step: u32,
+    /// Ticks after which an episode is done even without a winner.
+    max_ticks: Option<u64>,
+    game: Option<AiMatch>,
+}
+
+impl Environment {
+    pub fn new(rules: GameRules, level: Level, side: Side, ai: &AiConfig) -> Environment {
+        Environment {
+            rules,
+            level,
+            side,
+            ai: ai.clone().without(side),
+            ticks_per_step: DEFAULT_TICKS_PER_STEP,
+            max_ticks: None,
+            game: None,
+        }
+    }
+
+    pub fn with_ticks_per_step(mut self, ticks: u32) -> Environment {
+        self.ticks_per_step = ticks.max(1);
+        self
+    }
+
+    pub fn with_max_ticks(mut self, max_ticks: Option<u64>) -> Environment {
+        self.max_ticks = max_ticks;
+        self
+    }
+
+    /// Starts a new episode, the same seed plays the same match for the same
+    /// actions.
+    pub fn reset(&mut self, seed: u64) -> Observation {
+        let game = AiMatch::new(self.rules.clone(), self.level.clone(), &self.ai, seed);
+        let observation = Observation::new(&game.sim, game.ticks, self.side);
+        self.game = Some(game);
+        observation
+    }
+
+    /// Moves the paddle of the agent with `action`, from -1.0 to 1.0 like an
+    /// input axis, and returns what the agent sees next, its reward and
+    /// whether the episode is done.
+    pub fn step(&mut self, action: f32) -> Result<(Observation, f32, bool), EnvError> {
+        if !action.is_finite() {
+            return Err(EnvError::InvalidAction(action));
+        }
+        let side = self.side;
+        let max_ticks = self.max_ticks;
+        let game = self.game.as_mut().ok_or(EnvError::NotReset)?;
+        if is_done(game, max_ticks) {
+            return Err(EnvError::Done);
+        }
+
+        let mut inputs = Inputs::default();
+        inputs.set(side, action.clamp(-1.0, 1.0));
+        let mut reward = 0.0;
+        for _ in 0..self.ticks_per_step {
+            let (_, events) = game.step_with(inputs);
+            for event in events {
+                if let SimEvent::Goal { scorer, .. } = event {
+                    reward += if scorer == side { 1.0 } else { -1.0 };
+                }
+            }
+            if is_done(game, max_ticks) {
+                break;
+            }
+        }
+        Ok((
+            Observation::new(&game.sim, game.ticks, side),
+            reward,
+            is_done(game, max_ticks),
+        ))
+    }
+}
+
+fn is_done(game: &AiMatch, max_ticks: Option<u64>) -> bool {
+    game.sim.is_over() || max_ticks.is_some_and(|max_ticks| game.ticks >= max_ticks)
+}
+
+/// Observation is what the agent sees of the match, in arena units with the
+/// origin at the bottom left corner.
+#[derive(Clone, Debug, Serialize, PartialEq)]
+pub struct Observation {
+    /// Ticks played since the reset.
+    pub tick: u64,
+    /// Side of the paddle played by the agent.
+    pub side: Side,
+    /// Width and height of the arena.
+    pub arena: [f32; 2],
+    pub balls: Vec<BallObservation>,
+    pub paddles: Vec<PaddleObservation>,
+    /// Points and won sets of every side, in the order of `Side::ALL`. The
+    /// points go back to zero once a set is won.
+    pub points: [i32; 4],
+    pub sets: [i32; 4],
+}
+
+#[derive(Clone, Debug, Serialize, PartialEq)]
+pub struct BallObservation {
+    pub position: [f32; 2],
+    /// In units per second.
+    pub velocity: [f32; 2],
+    /// Whether the ball is in play, it waits in the middle before a serve.
+    pub moving: bool,
+}
+
+#[derive(Clone, Debug, Serialize, PartialEq)]
+pub struct PaddleObservation {
+    pub side: Side,
+    /// Center of the paddle.
+    pub position: [f32; 2],
+    /// Speed along its side, in units per second.
+    pub velocity: f32,
+    /// Size along its side, power-ups change it.
+    pub length: f32,
+}
+
+impl Observation {
+    /// What the paddle of `side` sees of `sim`, `tick` ticks into the match.
+    pub fn new(sim: &Simulation, tick: u64, side: Side) -> Observation {
+        Observation {
+            tick,
+            side,
+            arena: [sim.rules.arena_width, sim.rules.arena_height],
+            balls: sim
+                .balls
+                .iter()
+                .map(|ball| BallObservation {
+                    position: ball.position,
+                    velocity: ball.velocity,
+                    moving: ball.state == BallState::Moving,
+                })
+                .collect(),
+            paddles: sim
+                .paddles
+                .iter()
+                .map(|paddle| PaddleObservation {
+                    side: paddle.side,
+                    position: paddle.position,
+                    velocity: paddle.velocity,
+                    length: paddle.length(),
+                })
+                .collect(),
+            points: sim.score.points,
+            sets: sim.score.sets,
+        }
+    }
+}
+
+/// Errors of a step the environment can not play.
+#[derive(Debug, PartialEq)]
+pub enum EnvError {
+    /// A step before the first reset.
+    NotReset,
+    /// A step after the end of the episode.
+    Done,
+    InvalidAction(f32),
+}
+
+impl fmt::Display for EnvError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EnvError::NotReset => write!(f, "the environment must be reset before a step"),
+            EnvError::Done => write!(f, "the episode is done, the environment must be reset"),
+            EnvError::InvalidAction(action) => write!(
+                f,
+                "invalid action {}, expected a number from -1 to 1",
+                action
+            ),
+        }
+    }
+}
+
+impl Error for EnvError {}
+
+/// A line sent by the trainer: `{"reset": {"seed": 7}}`, `{"reset": {}}` or
+/// `{"step": {"action": -0.5}}`.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+enum Request {
+    Reset {
+        #[serde(default)]
+        seed: Option<u64>,
+    },
+    Step {
+        action: f32,
+    },
+}
+
+/// A line sent back, for every request.
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+enum Response {
+    Reset {
+        observation: Observation,
+    },
+    Step {
+        observation: Observation,
+        reward: f32,
+        done: bool,
+    },
+    Error {
+        error: String,
+    },
+}
+
+/// Answers every request of `input`, one JSON object per line, with a line
+/// on `output`, until `input` is closed. A reset without a seed takes `seed`,
+/// then the seeds following it. A bad request gets an error line, the
+/// environment is left as it was.
+pub fn serve<R: BufRead, W: Write>(
+    env: &mut Environment,
+    seed: u64,
+    input: R,
+    mut output: W,
+) -> io::Result<()> {
+    let mut next_seed = seed;
+    for line in input.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let response = match serde_json::from_str::<Request>(&line) {
+            Ok(Request::Reset { seed }) => {
+                let seed = seed.unwrap_or(next_seed);
+                next_seed = seed.wrapping_add(1);
+                Response::Reset {
+                    observation: env.reset(seed),
+                }
+            }
+            Ok(Request::Step { action }) => match env.step(action) {
+                Ok((observation, reward, done)) => Response::Step {
+                    observation,
+                    reward,
+                    done,
+                },
+                Err(e) => Response::Error {
+                    error: e.to_string(),
+                },
+            },
+            Err(e) => Response::Error {
+                error: format!("invalid request: {}", e),
+            },
+        };
+        serde_json::to_writer(&mut output, &response)?;
+        writeln!(output)?;
+        // The trainer waits for the answer before sending the next request.
+        output.flush()?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::Difficulty;
+    use serde_json::Value;
+
+    fn environment() -> Environment {
+        let rules = GameRules {
+            points_to_win: 2,
+            win_by_two: false,
+            ..GameRules::default()
+        };
+        let ai = AiConfig {
+            left: Some(Difficulty::Hard),
+            right: Some(Difficulty::Hard),
+            ..AiConfig::default()
+        };
+        Environment::new(rules, Level::default(), Side::Left, &ai)
+    }
+
+    #[test]
+    fn rewards_follow_the_score() {
+        let mut env = environment();
+        assert_eq!(env.step(0.0).unwrap_err(), EnvError::NotReset);
+
+        let observation = env.reset(3);
+        assert_eq!(observation.tick, 0);
+        assert_eq!(observation.paddles.len(), 2);
+        let mut total = 0.0;
+        let sets = loop {
+            let (observation, reward, done) = env.step(0.0).unwrap();
+            total += reward;
+            if done {
+                break observation.sets;
+            }
+        };
+        // The winner scored two points, the loser one at most.
+        let won = sets[Side::Left.index()] == 1;
+        assert_eq!(sets[Side::Right.index()], if won { 0 } else { 1 });
+        assert!(total == 1.0 || total == 2.0 || total == -1.0 || total == -2.0);
+        assert_eq!(total > 0.0, won);
+        assert_eq!(env.step(0.0).unwrap_err(), EnvError::Done);
+        assert_eq!(env.reset(4).points, [0; 4]);
+    }
+
+    #[test]
+    fn protocol_answers_every_line() {
+        let mut env = environment().with_max_ticks(Some(8));
+        let input = concat!(
+            "{\"reset\": {\"seed\": 5}}\n",
+            "{\"step\": {\"action\": 1.0}}\n",
+            "\n",
+            "{\"jump\": {}}\n",
+            "{\"step\": {\"action\": -1.0}}\n",
+            "{\"step\": {\"action\": 0.0}}\n",
+        );
+        let mut output = Vec::new();
+        serve(&mut env, 0, input.as_bytes(), &mut output).unwrap();
+
+        let lines = String::from_utf8(output)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str::<Value>(line).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(lines.len(), 5);
+        assert_eq!(lines[0]["observation"]["tick"], 0);
+        assert_eq!(lines[1]["observation"]["tick"], 4);
+        assert_eq!(lines[1]["done"], false);
+        assert!(lines[2]["error"].is_string());
+        assert_eq!(lines[3]["done"], true);
+        assert!(lines[4]["error"].is_string());
+    }
+}
@@ -0,0 +1,169 @@
+//! Matches played without a window, as fast as they can be simulated. Started
+//! with `--headless`, in the ai-vs-ai and replay modes.
+
+use std::path::PathBuf;
+
+use amethyst::config::ConfigError;
+
+use crate::ai::{AiConfig, AiController};
+use crate::level::Level;
+use crate::replay::{Recording, ReplayCheck};
+use crate::rules::GameRules;
+use crate::sim::{Inputs, Replay, ReplayCursor, Side, SimEvent, SimRng, Simulation, TICK};
+
+/// AiMatch is a match played by the computer players of an `AiConfig`, tick
+/// by tick. It plays exactly like the `SimulationSystem`: the same seed gives
+/// the same match.
+pub struct AiMatch {
+    pub sim: Simulation,
+    controllers: Vec<(Side, AiController)>,
+    rng: SimRng,
+    /// Number of ticks played.
+    pub ticks: u64,
+}
+
+impl AiMatch {
+    pub fn new(rules: GameRules, level: Level, ai: &AiConfig, seed: u64) -> AiMatch {
+        let sim = Simulation::seeded(rules, seed).with_level(level);
+        let controllers = sim
+            .paddles
+            .iter()
+            .filter_map(|paddle| {
+                let initial = paddle.position[paddle.side.axis()];
+                ai.get(paddle.side)
+                    .map(|difficulty| (paddle.side, AiController::new(difficulty, initial)))
+            })
+            .collect();
+        AiMatch {
+            sim,
+            controllers,
+            rng: SimRng::computer(seed),
+            ticks: 0,
+        }
+    }
+
+    /// Plays one tick and returns the inputs of the computer players with
+    /// what happened. Paddles without a computer player stand still.
+    pub fn step(&mut self) -> (Inputs, Vec<SimEvent>) {
+        self.step_with(Inputs::default())
+    }
+
+    /// Plays one tick with `inputs` for the paddles without a computer
+    /// player, and returns every input played with what happened.
+    pub fn step_with(&mut self, mut inputs: Inputs) -> (Inputs, Vec<SimEvent>) {
+        for (side, controller) in self.controllers.iter_mut() {
+            controller.update(&self.sim, *side, TICK, &mut self.rng);
+            inputs.set(*side, controller.movement);
+        }
+        self.ticks += 1;
+        let events = self.sim.step(TICK, &inputs);
+        (inputs, events)
+    }
+}
+
+/// Plays `game` until it has a winner, or for `max_ticks` ticks, then prints
+/// the score. The match is recorded to `record` when it is given.
+pub fn play_ai_vs_ai(
+    mut game: AiMatch,
+    seed: u64,
+    max_ticks: u64,
+    record: Option<PathBuf>,
+) -> Result<(), ConfigError> {
+    let mut recording = record.map(|path| Recording {
+        path,
+        replay: Replay::new(seed, game.sim.rules.clone(), game.sim.level.clone()),
+    });
+    while !game.sim.is_over() && game.ticks < max_ticks {
+        let (inputs, _) = game.step();
+        if let Some(recording) = recording.as_mut() {
+            recording.replay.record(&inputs);
+        }
+    }
+
+    let sim = &game.sim;
+    let result = match sim.score.winner {
+        Some(winner) => format!("{:?} wins", winner),
+        None => "no winner".to_string(),
+    };
+    println!(
+        "{}, final score {} after {} ticks ({:.1} s), seed {}",
+        result,
+        sim.score.describe(sim.rules.sides()),
+        game.ticks,
+        game.ticks as f32 * TICK,
+        seed
+    );
+    match recording {
+        Some(recording) => recording.save(&sim.score),
+        None => Ok(()),
+    }
+}
+
+/// Plays `replay` to the end, or for `frames` ticks, then prints whether the
+/// score matches the recorded one. Returns whether it does.
+pub fn check_replay(replay: &Replay, frames: Option<u64>) -> bool {
+    let mut sim =
+        Simulation::seeded(replay.rules.clone(), replay.seed).with_level(replay.level.clone());
+    let mut cursor = ReplayCursor::default();
+    let mut ticks = 0;
+    while frames.is_none_or(|frames| ticks < frames) {
+        match cursor.next(replay) {
+            Some(inputs) => sim.step(TICK, &inputs),
+            None => break,
+        };
+        ticks += 1;
+    }
+
+    let check = ReplayCheck {
+        expected: replay.score.clone(),
+        actual: sim.score.clone(),
+        sides: replay.rules.sides(),
+        finished: ticks == replay.ticks(),
+    };
+    let verdict = if check.matches() {
+        "matches the recording".to_string()
+    } else if !check.finished {
+        format!("stopped after {} of {} ticks", ticks, replay.ticks())
+    } else {
+        format!(
+            "does not match the recording ({})",
+            check.expected.describe(check.sides)
+        )
+    };
+    println!(
+        "final score {} {}",
+        check.actual.describe(check.sides),
+        verdict
+    );
+    check.matches()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::Difficulty;
+
+    #[test]
+    fn recorded_ai_match_replays_to_the_same_score() {
+        let rules = GameRules {
+            points_to_win: 2,
+            ..GameRules::default()
+        };
+        let ai = AiConfig {
+            left: Some(Difficulty::Easy),
+            right: Some(Difficulty::Hard),
+            ..AiConfig::default()
+        };
+        let mut game = AiMatch::new(rules.clone(), Level::default(), &ai, 9);
+        let mut replay = Replay::new(9, rules, Level::default());
+        while !game.sim.is_over() {
+            let (inputs, _) = game.step();
+            replay.record(&inputs);
+        }
+        replay.score = game.sim.score.clone();
+
+        assert_eq!(replay.ticks(), game.ticks);
+        assert!(check_replay(&replay, None));
+        assert!(!check_replay(&replay, Some(game.ticks / 2)));
+    }
+}
@@ -0,0 +1,369 @@
+//! Arena layouts: obstacles, portals and bumpers read from level files.
+//!
+//! `config/levels.ron` lists the level files of `config/levels/`, the matches
+//! go through them in turn. Without any level, matches are played in the
+//! empty arena.
+
+use std::{
+    error::Error,
+    fmt,
+    path::{Path, PathBuf},
+};
+
+use amethyst::config::{Config, ConfigError};
+use serde::{Deserialize, Serialize};
+
+use crate::rules::GameRules;
+use crate::sim::collision::Rect;
+
+/// More obstacles would not leave room to play.
+const MAX_OBSTACLES: usize = 16;
+const MAX_PORTALS: usize = 4;
+const MAX_BUMPERS: usize = 8;
+
+/// Level is the layout of an arena. It is loaded from a file of
+/// `config/levels/` and played by the `Simulation`.
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Level {
+    /// Name of the level, used in error messages.
+    pub name: String,
+    pub obstacles: Vec<Obstacle>,
+    pub portals: Vec<Portal>,
+    pub bumpers: Vec<Bumper>,
+}
+
+/// A rectangle the balls bounce on. A moving obstacle goes from `position` to
+/// `position + travel` and back in `period` seconds.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Obstacle {
+    /// Center of the obstacle when the match starts.
+    pub position: [f32; 2],
+    pub size: [f32; 2],
+    #[serde(default)]
+    pub travel: [f32; 2],
+    /// Seconds of a round trip, unused by an obstacle that does not move.
+    #[serde(default)]
+    pub period: f32,
+}
+
+impl Obstacle {
+    pub fn is_moving(&self) -> bool {
+        self.travel != [0.0, 0.0]
+    }
+
+    /// Center of the obstacle `time` seconds into the match.
+    pub fn center(&self, time: f32) -> [f32; 2] {
+        if !self.is_moving() {
+            return self.position;
+        }
+        // Out to the end of the travel in half a period, then back.
+        let phase = (time / self.period).rem_euclid(1.0);
+        let progress = 1.0 - (phase * 2.0 - 1.0).abs();
+        [
+            self.position[0] + self.travel[0] * progress,
+            self.position[1] + self.travel[1] * progress,
+        ]
+    }
+
+    pub fn rect(&self, time: f32) -> Rect {
+        Rect::from_center(self.center(time), self.size[0], self.size[1])
+    }
+
+    /// Every place the obstacle goes through during the match.
+    fn swept_rect(&self) -> Rect {
+        let start = self.rect(0.0);
+        let end = Rect::from_center(
+            [
+                self.position[0] + self.travel[0],
+                self.position[1] + self.travel[1],
+            ],
+            self.size[0],
+            self.size[1],
+        );
+        Rect {
+            left: start.left.min(end.left),
+            bottom: start.bottom.min(end.bottom),
+            right: start.right.max(end.right),
+            top: start.top.max(end.top),
+        }
+    }
+}
+
+/// Two linked holes: a ball going into one end comes out of the other one,
+/// with the same velocity.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Portal {
+    pub ends: [[f32; 2]; 2],
+    pub radius: f32,
+}
+
+/// A round obstacle that sends the balls back `boost` times faster.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Bumper {
+    pub position: [f32; 2],
+    pub radius: f32,
+    pub boost: f32,
+}
+
+impl Level {
+    /// Loads a level from a RON file and validates it against the rules.
+    pub fn from_file<P: AsRef<Path>>(path: P, rules: &GameRules) -> Result<Level, LevelError> {
+        let path = path.as_ref();
+        let mut level = Level::load(path).map_err(|e| LevelError::Load(path.to_path_buf(), e))?;
+        if level.name.is_empty() {
+            level.name = path.display().to_string();
+        }
+        level.validate(rules)?;
+        Ok(level)
+    }
+
+    /// Checks that the level fits in the arena and leaves the middle free for
+    /// the serves.
+    pub fn validate(&self, rules: &GameRules) -> Result<(), LevelError> {
+        let counts = [
+            ("obstacles", self.obstacles.len(), MAX_OBSTACLES),
+            ("portals", self.portals.len(), MAX_PORTALS),
+            ("bumpers", self.bumpers.len(), MAX_BUMPERS),
+        ];
+        for (field, count, max) in counts.iter() {
+            if count > max {
+                return Err(self.invalid(field, format!("must not have more than {} items", max)));
+            }
+        }
+
+        let arena = Rect {
+            left: 0.0,
+            bottom: 0.0,
+            right: rules.arena_width,
+            top: rules.arena_height,
+        };
+        let center = [rules.halve_width(), rules.halve_height()];
+        // Balls wait for their serve in the middle of the arena.
+        let serve = Rect::from_center(center, 0.0, 0.0).expand(rules.ball_radius);
+        let inside = |rect: &Rect| {
+            rect.left >= arena.left
+                && rect.bottom >= arena.bottom
+                && rect.right <= arena.right
+                && rect.top <= arena.top
+        };
+        let overlaps = |a: &Rect, b: &Rect| {
+            a.left < b.right && b.left < a.right && a.bottom < b.top && b.bottom < a.top
+        };
+
+        for (index, obstacle) in self.obstacles.iter().enumerate() {
+            let field = format!("obstacles[{}]", index);
+            let values = [
+                obstacle.position[0],
+                obstacle.position[1],
+                obstacle.size[0],
+                obstacle.size[1],
+                obstacle.travel[0],
+                obstacle.travel[1],
+                obstacle.period,
+            ];
+            if values.iter().any(|value| !value.is_finite()) {
+                return Err(self.invalid(&field, "must only hold finite numbers"));
+            }
+            if obstacle.size[0] <= 0.0 || obstacle.size[1] <= 0.0 {
+                return Err(self.invalid(&field, "must have a size greater than zero"));
+            }
+            if obstacle.is_moving() && obstacle.period <= 0.0 {
+                return Err(self.invalid(&field, "must have a period greater than zero"));
+            }
+            let swept = obstacle.swept_rect();
+            if !inside(&swept) {
+                return Err(self.invalid(&field, "must stay inside the arena"));
+            }
+            if overlaps(&swept, &serve) {
+                return Err(self.invalid(&field, "must not go through the middle of the arena"));
+            }
+        }
+
+        let circles = self
+            .portals
+            .iter()
+            .enumerate()
+            .flat_map(|(index, portal)| {
+                portal
+                    .ends
+                    .iter()
+                    .map(move |end| (format!("portals[{}]", index), *end, portal.radius, 1.0))
+            })
+            .chain(self.bumpers.iter().enumerate().map(|(index, bumper)| {
+                (
+                    format!("bumpers[{}]", index),
+                    bumper.position,
+                    bumper.radius,
+                    bumper.boost,
+                )
+            }));
+        for (field, position, radius, boost) in circles {
+            if [position[0], position[1], radius, boost]
+                .iter()
+                .any(|value| !value.is_finite())
+            {
+                return Err(self.invalid(&field, "must only hold finite numbers"));
+            }
+            if radius <= 0.0 || boost <= 0.0 {
+                return Err(
+                    self.invalid(&field, "must have a radius and a boost greater than zero")
+                );
+            }
+            let bounds = Rect::from_center(position, 0.0, 0.0).expand(radius);
+            if !inside(&bounds) {
+                return Err(self.invalid(&field, "must stay inside the arena"));
+            }
+            if overlaps(&bounds, &serve) {
+                return Err(self.invalid(&field, "must not cover the middle of the arena"));
+            }
+        }
+
+        for (index, portal) in self.portals.iter().enumerate() {
+            let [a, b] = portal.ends;
+            let distance = ((a[0] - b[0]).powi(2) + (a[1] - b[1]).powi(2)).sqrt();
+            if distance < portal.radius * 2.0 {
+                return Err(self.invalid(
+                    &format!("portals[{}]", index),
+                    "must have ends that do not overlap",
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn invalid<R: Into<String>>(&self, field: &str, reason: R) -> LevelError {
+        LevelError::Invalid {
+            level: self.name.clone(),
+            field: field.to_string(),
+            reason: reason.into(),
+        }
+    }
+}
+
+/// LevelList is the content of `config/levels.ron`: the level files of
+/// `config/levels/`, in the order they are played.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LevelList {
+    pub levels: Vec<String>,
+}
+
+/// Loads every level listed in `levels.ron` of the `config` directory.
+pub fn load_levels(config: &Path, rules: &GameRules) -> Result<Vec<Level>, LevelError> {
+    let list_path = config.join("levels.ron");
+    let list = LevelList::load(&list_path).map_err(|e| LevelError::Load(list_path, e))?;
+    list.levels
+        .iter()
+        .map(|name| Level::from_file(config.join("levels").join(name), rules))
+        .collect()
+}
+
+/// LevelRotation gives every match the next level of the list, and starts
+/// over after the last one.
+#[derive(Clone, Debug, Default)]
+pub struct LevelRotation {
+    levels: Vec<Level>,
+    next: usize,
+}
+
+impl LevelRotation {
+    pub fn new(levels: Vec<Level>) -> LevelRotation {
+        LevelRotation { levels, next: 0 }
+    }
+
+    /// Returns the level of the next match, the empty arena without levels.
+    pub fn next_level(&mut self) -> Level {
+        if self.levels.is_empty() {
+            return Level::default();
+        }
+        let level = self.levels[self.next].clone();
+        self.next = (self.next + 1) % self.levels.len();
+        level
+    }
+}
+
+/// Errors that can happen while loading the levels.
+#[derive(Debug)]
+pub enum LevelError {
+    /// A file could not be read or parsed.
+    Load(PathBuf, ConfigError),
+    /// A level was parsed but does not fit in the arena.
+    Invalid {
+        level: String,
+        field: String,
+        reason: String,
+    },
+}
+
+impl fmt::Display for LevelError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LevelError::Load(path, e) => {
+                write!(f, "could not load level {}: {}", path.display(), e)
+            }
+            LevelError::Invalid {
+                level,
+                field,
+                reason,
+            } => write!(f, "invalid level `{}`: `{}` {}", level, field, reason),
+        }
+    }
+}
+
+impl Error for LevelError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            LevelError::Load(_, e) => Some(e),
+            LevelError::Invalid { .. } => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn moving_obstacle_goes_back_and_forth() {
+        let obstacle = Obstacle {
+            position: [30.0, 75.0],
+            size: [10.0, 3.0],
+            travel: [40.0, 0.0],
+            period: 6.0,
+        };
+        assert_eq!(obstacle.center(0.0), [30.0, 75.0]);
+        assert_eq!(obstacle.center(1.5), [50.0, 75.0]);
+        assert_eq!(obstacle.center(3.0), [70.0, 75.0]);
+        assert_eq!(obstacle.center(4.5), [50.0, 75.0]);
+        assert_eq!(obstacle.center(6.0), [30.0, 75.0]);
+    }
+
+    #[test]
+    fn level_must_leave_the_serve_spot_free() {
+        let level = Level {
+            name: "blocked".to_string(),
+            bumpers: vec![Bumper {
+                position: [52.0, 50.0],
+                radius: 2.0,
+                boost: 1.0,
+            }],
+            ..Level::default()
+        };
+        match level.validate(&GameRules::default()) {
+            Err(LevelError::Invalid { field, .. }) => assert_eq!(field, "bumpers[0]"),
+            result => panic!("unexpected result {:?}", result),
+        }
+    }
+
+    #[test]
+    fn shipped_levels_are_valid() {
+        let config = Path::new(env!("CARGO_MANIFEST_DIR")).join("config");
+        let levels = load_levels(&config, &GameRules::default()).unwrap();
+        assert!(!levels.is_empty());
+    }
+}
@@ -1,3 +1,5 @@
+use std::{fs::File, io, path::Path, time::Duration};
+
 use amethyst::{
     audio::{AudioBundle, DjSystemDesc},
     core::transform::TransformBundle,
@@ -10,32 +12,161 @@ use amethyst::{
     },
     ui::{RenderUi, UiBundle},
     utils::application_root_dir,
+    window::DisplayConfig,
 };
 
+use crate::ai::AiConfig;
 use crate::audio::Music;
-use crate::pong::PongGame;
+use crate::batch::{Format, DEFAULT_MATCHES, MAX_MATCH_TICKS};
+use crate::bindings::BindingsPath;
+use crate::cli::{Args, Mode, USAGE};
+use crate::controls::ControlConfig;
+use crate::environment::{Environment, DEFAULT_TICKS_PER_STEP};
+use crate::headless::{check_replay, play_ai_vs_ai, AiMatch};
+use crate::level::{load_levels, LevelRotation};
+use crate::net::{Client, Host, NetSession, Peer};
+use crate::pong::MatchSeed;
+use crate::replay::{load_replay, RecordPath};
+use crate::rules::GameRules;
+use crate::settings::Settings;
+use crate::sim::Side;
+use crate::states::{MainMenu, ReplayViewer, RunState};
+use crate::tournament::Tournament;
 
+mod ai;
 mod audio;
+mod batch;
+mod bindings;
+mod bot;
+mod cli;
+mod controls;
+mod environment;
+mod headless;
+mod level;
+mod net;
 mod pong;
+mod replay;
+mod rules;
+mod settings;
+mod sim;
+mod states;
 mod systems;
+mod tournament;
 
-fn main() -> amethyst::Result<()> {
-    amethyst::start_logger(Default::default());
+/// Time a client waits for the host, or a peer for the other one, before giving up.
+const JOIN_TIMEOUT: Duration = Duration::from_secs(5);
 
+/// Exit code of a bad command line, other errors exit with 1.
+const USAGE_EXIT_CODE: i32 = 2;
+
+fn main() {
+    let args = match Args::parse(std::env::args().skip(1)) {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            eprintln!("Run `rust-pong --help` for the usage.");
+            std::process::exit(USAGE_EXIT_CODE);
+        }
+    };
+    if args.help {
+        print!("{}", USAGE);
+        return;
+    }
+    if let Err(e) = run(args) {
+        eprintln!("error: {}", e);
+        std::process::exit(1);
+    }
+}
+
+fn run(args: Args) -> amethyst::Result<()> {
     let app_root = application_root_dir()?;
-    let display_config_path = app_root.join("config").join("display.ron");
+    let config_dir = app_root.join("config");
+    let display_config_path = args
+        .display
+        .clone()
+        .unwrap_or_else(|| config_dir.join("display.ron"));
+
+    // The rules are checked before anything else, so a bad value is reported right away.
+    let rules_path = args
+        .rules
+        .clone()
+        .unwrap_or_else(|| config_dir.join("rules.ron"));
+    let mut rules = GameRules::from_file(rules_path)?;
+    let mut ai = AiConfig::load(config_dir.join("ai.ron"))?;
+    if args.headless {
+        return run_headless(&args, rules, &ai, &config_dir);
+    }
+    amethyst::start_logger(Default::default());
 
-    let binding_path = app_root.join("config").join("bindings.ron");
+    // The preferences saved from the settings screen go over the config files,
+    // the command line has the last word.
+    let mut display_config = DisplayConfig::load(&display_config_path)?;
+    let settings = Settings::load_or(&rules, &ai, display_config.dimensions);
+    settings.apply_to_match(&mut rules, &mut ai);
+    display_config.dimensions = Some(settings.window_size);
+
+    // Levels are checked against the rules they are played with. Network
+    // matches are always played in the empty arena.
+    let levels = LevelRotation::new(load_levels(&config_dir, &rules)?);
+
+    // A client plays with the rules of the host.
+    let net = match &args.mode {
+        Mode::Host(port) => {
+            let host = Host::bind(("0.0.0.0", *port), rules.clone())?;
+            println!("waiting for a player on port {}", port);
+            Some(NetSession::Host(host))
+        }
+        Mode::Join(address) => {
+            let client = Client::connect(address.as_str(), JOIN_TIMEOUT)?;
+            rules = client.simulation().rules.clone();
+            Some(NetSession::Client(client))
+        }
+        Mode::Peer {
+            address,
+            port,
+            side,
+        } => {
+            println!("waiting for the other player at {}", address);
+            let peer = Peer::connect(*port, address.as_str(), *side, rules.clone(), JOIN_TIMEOUT)?;
+            Some(NetSession::Peer(peer))
+        }
+        _ => None,
+    };
+
+    // Computer players come from the config file and the mode, the command
+    // line has the last word.
+    let mut ai = args.mode.computers(&ai, rules.sides());
+    ai.apply_args(args.overrides.iter().cloned())
+        .map_err(amethyst::Error::from_string)?;
+
+    // Human players use the keys and gamepads of the bindings unless told otherwise.
+    let mut controls = ControlConfig::load(config_dir.join("controls.ron"))?;
+    controls
+        .apply_args(args.overrides.iter().cloned())
+        .map_err(amethyst::Error::from_string)?;
+    controls.validate().map_err(amethyst::Error::from_string)?;
+
+    let replay = match &args.mode {
+        Mode::Replay(path) => Some(load_replay(path)?),
+        _ => None,
+    };
+
+    let binding_path = args
+        .bindings
+        .clone()
+        .unwrap_or_else(|| config_dir.join("bindings.ron"));
     let input_bundle =
-        InputBundle::<StringBindings>::new().with_bindings_from_file(binding_path)?;
+        InputBundle::<StringBindings>::new().with_bindings_from_file(&binding_path)?;
+    // The controls screen saves the bindings it changes to the same file.
+    let binding_path = BindingsPath(binding_path);
 
-    let game_data = GameDataBuilder::default()
+    let mut game_data = GameDataBuilder::default()
         .with_bundle(
             RenderingBundle::<DefaultBackend>::new()
                 // The RenderToWindow plugin provides all the scaffolding
                 // for opening a window and drawing on it
                 .with_plugin(
-                    RenderToWindow::from_config_path(display_config_path)?
+                    RenderToWindow::from_config(display_config)
                         .with_clear([0.0, 0.0, 0.0, 1.0]),
                 )
                 // RenderFlat2D plugin is used to render entities with a `SpriteRender` component.
@@ -53,20 +184,192 @@ fn main() -> amethyst::Result<()> {
             "dj_system",
             &[],
         )
-        .with(systems::PaddleSystem, "paddle_system", &["input_system"])
-        .with(systems::MoveBallsSystem, "ball_system", &[])
+        // Gameplay systems only run while a match is being played.
         .with(
-            systems::CyclingColorSystem,
+            systems::PaddleSystem.pausable(RunState::Running),
+            "paddle_system",
+            &["input_system"],
+        )
+        .with(
+            systems::SimulationSystem::default().pausable(RunState::Running),
+            "simulation_system",
+            &["paddle_system"],
+        )
+        .with(
+            systems::CyclingColorSystem::default().pausable(RunState::Running),
             "cycling_color_system",
-            &["ball_system"],
+            &["simulation_system"],
+        )
+        .with(
+            systems::PowerUpSystem.pausable(RunState::Running),
+            "powerup_system",
+            &["simulation_system"],
+        )
+        // Sounds, score texts and statistics follow the events of the simulation.
+        .with(
+            systems::SoundSystem::default().pausable(RunState::Running),
+            "sound_system",
+            &["simulation_system"],
         )
-        .with(systems::BounceSystem, "bounce_system", &[])
-        .with(systems::WinnerSystem, "winner_system", &["ball_system"]);
+        .with(
+            systems::ScoreSystem::default().pausable(RunState::Running),
+            "score_system",
+            &["simulation_system"],
+        )
+        .with(
+            systems::StatsSystem::default().pausable(RunState::Running),
+            "stats_system",
+            &["simulation_system"],
+        );
+    if let Some(frames) = args.frames {
+        game_data = game_data.with(
+            systems::FrameBudgetSystem { frames },
+            "frame_budget_system",
+            &[],
+        );
+    }
 
     let assets_dir = app_root.join("assets");
-    let mut game = Application::new(assets_dir, PongGame::new(), game_data)?;
+    // A fixed seed replays the exact same match for the same inputs.
+    let seed = MatchSeed(args.seed);
+    let record_path = RecordPath(args.record.clone());
+    let (frame_strategy, frame_rate) = settings.frame_limit();
+    let mut game = match replay {
+        // A replay is played right away, without the menus.
+        Some(replay) => Application::build(assets_dir, ReplayViewer::new(replay))?
+            .with_frame_limit(frame_strategy, frame_rate)
+            .with_resource(settings)
+            .with_resource(rules)
+            .with_resource(levels)
+            .with_resource(ai)
+            .with_resource(controls)
+            .with_resource(binding_path)
+            .with_resource(seed)
+            .with_resource(record_path)
+            .build(game_data)?,
+        None => {
+            // The match modes skip the title screen, it shows up after the match.
+            let menu = match args.mode {
+                Mode::Pvp | Mode::VsAi | Mode::AiVsAi => MainMenu::starting_match(),
+                _ => MainMenu::default(),
+            };
+            let mut builder = Application::build(assets_dir, menu)?
+                .with_frame_limit(frame_strategy, frame_rate)
+                .with_resource(settings)
+                .with_resource(rules)
+                .with_resource(levels)
+                .with_resource(ai)
+                .with_resource(controls)
+                .with_resource(binding_path)
+                .with_resource(seed)
+                .with_resource(record_path);
+            // Network matches start from the menu like local ones.
+            if let Some(net) = net {
+                builder = builder.with_resource(net);
+            }
+            builder.build(game_data)?
+        }
+    };
 
     game.run();
 
     Ok(())
 }
+
+/// Plays the ai-vs-ai match, the replay, the batch, the environment or the
+/// tournament of `args` without a window. The saved settings are left out, so
+/// a headless run plays the same everywhere.
+fn run_headless(
+    args: &Args,
+    rules: GameRules,
+    ai: &AiConfig,
+    config_dir: &Path,
+) -> amethyst::Result<()> {
+    match &args.mode {
+        Mode::Replay(path) => {
+            let replay = load_replay(path)?;
+            // A replay cut short by `--frames` is not expected to match.
+            if check_replay(&replay, args.frames) || args.frames.is_some() {
+                Ok(())
+            } else {
+                Err(amethyst::Error::from_string(
+                    "the replay does not end with the recorded score",
+                ))
+            }
+        }
+        Mode::Batch => {
+            // Variants may change the number of players, every paddle is
+            // given to the computer.
+            let mut ai = args.mode.computers(ai, &Side::ALL);
+            ai.apply_args(args.overrides.iter().cloned())
+                .map_err(amethyst::Error::from_string)?;
+            let variants = batch::variants(&rules, &args.vary)?;
+            let matches = args.matches.unwrap_or(DEFAULT_MATCHES);
+            let report = batch::run(
+                &variants,
+                &ai,
+                matches,
+                args.seed.unwrap_or_else(rand::random),
+                args.frames.unwrap_or(MAX_MATCH_TICKS),
+                // The report may go to the standard output, progress goes to the error one.
+                |variant| eprintln!("playing {} matches of {}", matches, variant.name),
+            );
+            let format = args.format.unwrap_or(Format::Json);
+            match &args.output {
+                Some(path) => report.write(format, &mut File::create(path)?)?,
+                None => report.write(format, &mut io::stdout().lock())?,
+            }
+            Ok(())
+        }
+        Mode::Env { side } => {
+            let mut ai = args.mode.computers(ai, rules.sides());
+            ai.apply_args(args.overrides.iter().cloned())
+                .map_err(amethyst::Error::from_string)?;
+            let level = LevelRotation::new(load_levels(config_dir, &rules)?).next_level();
+            let mut env = Environment::new(rules, level, *side, &ai)
+                .with_ticks_per_step(args.ticks_per_step.unwrap_or(DEFAULT_TICKS_PER_STEP))
+                .with_max_ticks(args.frames);
+            let seed = args.seed.unwrap_or_else(rand::random);
+            environment::serve(&mut env, seed, io::stdin().lock(), io::stdout().lock())?;
+            Ok(())
+        }
+        Mode::Tournament(bots) => {
+            if rules.players != 2 {
+                return Err(amethyst::Error::from_string(
+                    "a tournament is played by two bots, set `players: 2` in the rules",
+                ));
+            }
+            // Only the bot settings of the controls apply, the bots come from the command line.
+            let controls = ControlConfig::load(config_dir.join("controls.ron"))?;
+            controls.validate().map_err(amethyst::Error::from_string)?;
+            let level = LevelRotation::new(load_levels(config_dir, &rules)?).next_level();
+            let tournament = Tournament {
+                bots: bots.clone(),
+                rules,
+                level,
+                policy: controls.bot_policy(),
+                matches: args.matches.unwrap_or(tournament::DEFAULT_MATCHES),
+                max_ticks: args.frames.unwrap_or(MAX_MATCH_TICKS),
+            };
+            let standings = tournament.run(args.seed.unwrap_or_else(rand::random), |result| {
+                println!("{}", result.describe(&tournament.bots))
+            });
+            println!();
+            tournament::write_standings(&standings, &mut io::stdout().lock())?;
+            Ok(())
+        }
+        _ => {
+            let mut ai = args.mode.computers(ai, rules.sides());
+            ai.apply_args(args.overrides.iter().cloned())
+                .map_err(amethyst::Error::from_string)?;
+            let level = LevelRotation::new(load_levels(config_dir, &rules)?).next_level();
+            let seed = args.seed.unwrap_or_else(rand::random);
+            let game = AiMatch::new(rules, level, &ai, seed);
+            // Computer players may keep a rally going for good, the match is
+            // stopped after as many ticks as a match of a batch.
+            let max_ticks = args.frames.unwrap_or(MAX_MATCH_TICKS);
+            play_ai_vs_ai(game, seed, max_ticks, args.record.clone())?;
+            Ok(())
+        }
+    }
+}
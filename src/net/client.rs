@@ -0,0 +1,292 @@
+use std::{
+    collections::VecDeque,
+    io,
+    net::{ToSocketAddrs, UdpSocket},
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::net::is_retryable;
+use crate::net::protocol::{decode, encode, Message, Snapshot, INPUT_REDUNDANCY, MAX_PACKET_SIZE};
+use crate::rules::GameRules;
+use crate::sim::powerup::is_reversed;
+use crate::sim::{Ball, BallState, Paddle, Side, SimEvent, Simulation, TICK, TICK_RATE};
+
+/// Time between two `Join` packets while the host does not answer.
+const JOIN_RETRY: Duration = Duration::from_millis(250);
+/// Inputs kept for the prediction when the host stops answering, a second of play.
+const MAX_PENDING_INPUTS: usize = TICK_RATE as usize;
+
+/// Client plays one paddle of a match simulated by a `Host`. It shows the
+/// last snapshot received, except for its own paddle: that one is predicted
+/// from the inputs the host has not played yet, so it answers right away.
+pub struct Client {
+    socket: UdpSocket,
+    side: Side,
+    sim: Simulation,
+    /// Inputs sent and not played by the host yet, oldest first.
+    pending: VecDeque<(u32, f32)>,
+    sequence: u32,
+    /// Tick of the last snapshot applied, older snapshots are dropped.
+    last_tick: u32,
+    events: Vec<SimEvent>,
+}
+
+impl Client {
+    /// Joins the host at `address`, waiting at most `timeout` for its answer.
+    pub fn connect<A: ToSocketAddrs>(address: A, timeout: Duration) -> io::Result<Client> {
+        let host = address
+            .to_socket_addrs()?
+            .next()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "no host address"))?;
+        let local = if host.is_ipv4() {
+            "0.0.0.0:0"
+        } else {
+            "[::]:0"
+        };
+        let socket = UdpSocket::bind(local)?;
+        socket.connect(host)?;
+        socket.set_read_timeout(Some(JOIN_RETRY))?;
+
+        let started = Instant::now();
+        let mut buffer = [0; MAX_PACKET_SIZE];
+        let (side, rules) = loop {
+            if started.elapsed() > timeout {
+                return Err(io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    format!("no answer from {}", host),
+                ));
+            }
+            socket.send(&encode(&Message::Join))?;
+            match socket.recv(&mut buffer) {
+                Ok(length) => {
+                    if let Ok(Message::Welcome { side, rules }) = decode(&buffer[..length]) {
+                        break (side, rules);
+                    }
+                }
+                Err(e) if is_retryable(&e) => {
+                    // A refused packet comes back at once, wait before the next try.
+                    if e.kind() == io::ErrorKind::ConnectionRefused {
+                        thread::sleep(JOIN_RETRY);
+                    }
+                }
+                Err(e) => return Err(e),
+            }
+        };
+
+        socket.set_read_timeout(None)?;
+        socket.set_nonblocking(true)?;
+        Ok(Client::new(socket, side, rules))
+    }
+
+    fn new(socket: UdpSocket, side: Side, rules: GameRules) -> Client {
+        Client {
+            socket,
+            side,
+            sim: Simulation::new(rules),
+            pending: VecDeque::new(),
+            sequence: 0,
+            last_tick: 0,
+            events: Vec::new(),
+        }
+    }
+
+    /// Side played by this client.
+    #[cfg(test)]
+    pub fn side(&self) -> Side {
+        self.side
+    }
+
+    /// The match as the client sees it.
+    pub fn simulation(&self) -> &Simulation {
+        &self.sim
+    }
+
+    /// Returns what happened on the host in the snapshots applied since the
+    /// last call.
+    pub fn take_events(&mut self) -> Vec<SimEvent> {
+        std::mem::take(&mut self.events)
+    }
+
+    /// Reads every packet received since the last call.
+    pub fn poll(&mut self) -> io::Result<()> {
+        let mut buffer = [0; MAX_PACKET_SIZE];
+        loop {
+            match self.socket.recv(&mut buffer) {
+                Ok(length) => {
+                    if let Ok(Message::Snapshot(snapshot)) = decode(&buffer[..length]) {
+                        self.apply(snapshot);
+                    }
+                }
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => return Ok(()),
+                Err(e) if is_retryable(&e) => continue,
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// Sends the input of the next tick and moves the own paddle right away.
+    pub fn tick(&mut self, axis: f32) -> io::Result<()> {
+        self.sequence += 1;
+        self.pending.push_back((self.sequence, axis));
+        if self.pending.len() > MAX_PENDING_INPUTS {
+            self.pending.pop_front();
+        }
+
+        // The newest inputs go first, the older ones cover lost packets.
+        let axes = self
+            .pending
+            .iter()
+            .rev()
+            .take(INPUT_REDUNDANCY)
+            .map(|(_, axis)| *axis)
+            .collect();
+        let message = Message::Input {
+            round: 0,
+            sequence: self.sequence,
+            axes,
+        };
+        match self.socket.send(&encode(&message)) {
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock || is_retryable(&e) => {}
+            result => {
+                result?;
+            }
+        }
+
+        let axis = self.steering(axis);
+        let Simulation { rules, paddles, .. } = &mut self.sim;
+        if let Some(paddle) = own_paddle(paddles, self.side) {
+            paddle.update(axis, TICK, rules);
+        }
+        Ok(())
+    }
+
+    /// Replaces the match with a snapshot of the host, then plays again the
+    /// inputs the host had not received yet on the own paddle.
+    fn apply(&mut self, snapshot: Snapshot) {
+        // UDP does not keep the order, an older snapshot has nothing new.
+        if snapshot.tick <= self.last_tick {
+            return;
+        }
+        // Events of the ticks already seen come again, for lost snapshots.
+        let seen = std::mem::replace(&mut self.last_tick, snapshot.tick);
+        let events = snapshot.events.iter().filter(|(tick, _)| *tick > seen);
+        self.events.extend(events.map(|(_, event)| *event));
+        while let Some(&(number, _)) = self.pending.front() {
+            if number > snapshot.last_input {
+                break;
+            }
+            self.pending.pop_front();
+        }
+
+        let previous = self.sim.clone();
+        for state in snapshot.paddles.iter() {
+            if let Some(paddle) = self.sim.paddles.iter_mut().find(|p| p.side == state.side) {
+                paddle.last_position = paddle.position;
+                paddle.position = state.position;
+                paddle.velocity = state.velocity;
+                paddle.set_length(state.length);
+            }
+        }
+        // Split balls come and go.
+        let rules = &self.sim.rules;
+        self.sim
+            .balls
+            .resize_with(snapshot.balls.len(), || Ball::new(rules));
+        for (ball, state) in self.sim.balls.iter_mut().zip(snapshot.balls.iter()) {
+            ball.last_position = ball.position;
+            ball.position = state.position;
+            ball.velocity = state.velocity;
+            ball.state = state.state;
+            ball.waiting_time = state.waiting_time;
+        }
+        self.sim.score = snapshot.score;
+        self.sim.powerups = snapshot.powerups;
+        self.sim.effects = snapshot.effects;
+
+        for ball in self.sim.balls.iter_mut() {
+            // A ball put back in the middle does not slide there.
+            if ball.state == BallState::Waiting {
+                ball.last_position = ball.position;
+            }
+        }
+
+        let axes = self
+            .pending
+            .iter()
+            .map(|(_, axis)| self.steering(*axis))
+            .collect::<Vec<_>>();
+        let Simulation { rules, paddles, .. } = &mut self.sim;
+        if let Some(paddle) = own_paddle(paddles, self.side) {
+            let predicted = previous
+                .paddle(paddle.side)
+                .map_or(paddle.position, |p| p.position);
+            for axis in axes {
+                paddle.update(axis, TICK, rules);
+            }
+            paddle.last_position = predicted;
+        }
+    }
+
+    /// Input moving the own paddle for `axis`, reversed controls included.
+    fn steering(&self, axis: f32) -> f32 {
+        if is_reversed(&self.sim.effects, self.side) {
+            -axis
+        } else {
+            axis
+        }
+    }
+}
+
+impl Drop for Client {
+    fn drop(&mut self) {
+        let _ = self.socket.send(&encode(&Message::Leave));
+    }
+}
+
+fn own_paddle(paddles: &mut [Paddle], side: Side) -> Option<&mut Paddle> {
+    paddles.iter_mut().find(|paddle| paddle.side == side)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sim::ScoreBoard;
+
+    fn snapshot(tick: u32, events: Vec<(u32, SimEvent)>) -> Snapshot {
+        Snapshot {
+            tick,
+            last_input: 0,
+            paddles: Vec::new(),
+            balls: Vec::new(),
+            score: ScoreBoard::default(),
+            powerups: Vec::new(),
+            effects: Vec::new(),
+            events,
+        }
+    }
+
+    #[test]
+    fn events_of_the_host_are_told_once() {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let mut client = Client::new(socket, Side::Right, GameRules::default());
+        let serve = SimEvent::Serve { ball: 0 };
+        let hit = SimEvent::PaddleHit {
+            ball: 0,
+            side: Side::Left,
+        };
+        let goal = SimEvent::Goal {
+            ball: 1,
+            scorer: Side::Right,
+        };
+
+        client.apply(snapshot(1, vec![(1, serve)]));
+        assert_eq!(client.take_events(), vec![serve]);
+        // The snapshot of tick 2 is lost, the next ones repeat its events.
+        client.apply(snapshot(3, vec![(1, serve), (2, hit), (3, goal)]));
+        assert_eq!(client.take_events(), vec![hit, goal]);
+        client.apply(snapshot(2, vec![(1, serve), (2, hit)]));
+        client.apply(snapshot(4, vec![(2, hit), (3, goal)]));
+        assert_eq!(client.take_events(), Vec::new());
+    }
+}
@@ -0,0 +1,245 @@
+use std::{
+    collections::{BTreeMap, VecDeque},
+    io,
+    net::{SocketAddr, ToSocketAddrs, UdpSocket},
+};
+
+use crate::net::check_players;
+use crate::net::protocol::{
+    decode, encode, BallSnapshot, Message, PaddleState, Snapshot, EVENT_REDUNDANCY,
+    MAX_PACKET_SIZE, MAX_SNAPSHOT_EVENTS,
+};
+use crate::rules::GameRules;
+use crate::sim::{Side, SimEvent, Simulation};
+
+/// Client inputs kept ahead of the simulation. A client running faster than
+/// the host would otherwise fall further and further behind.
+const MAX_INPUT_BACKLOG: usize = 12;
+
+/// Host runs the authoritative simulation. It plays the inputs of the client
+/// in order, one per tick, and sends a `Snapshot` of the match after every tick.
+pub struct Host {
+    socket: UdpSocket,
+    rules: GameRules,
+    client: Option<SocketAddr>,
+    /// Client inputs received and not played yet, by input number.
+    inputs: BTreeMap<u32, f32>,
+    /// Input number the next tick plays.
+    next_input: u32,
+    /// Input of the last tick. It is played again while the next one is late.
+    remote_axis: f32,
+    tick: u32,
+    /// Events of the last ticks, sent again in every snapshot.
+    events: VecDeque<(u32, SimEvent)>,
+}
+
+impl Host {
+    /// Side played by the client, the host plays the other one.
+    pub const REMOTE_SIDE: Side = Side::Right;
+
+    pub fn bind<A: ToSocketAddrs>(address: A, rules: GameRules) -> io::Result<Host> {
+        check_players(&rules)?;
+        let socket = UdpSocket::bind(address)?;
+        socket.set_nonblocking(true)?;
+        Ok(Host {
+            socket,
+            rules,
+            client: None,
+            inputs: BTreeMap::new(),
+            next_input: 1,
+            remote_axis: 0.0,
+            tick: 0,
+            events: VecDeque::new(),
+        })
+    }
+
+    #[cfg(test)]
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.socket.local_addr()
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.client.is_some()
+    }
+
+    /// Reads every packet received since the last call.
+    pub fn poll(&mut self) -> io::Result<()> {
+        let mut buffer = [0; MAX_PACKET_SIZE];
+        loop {
+            match self.socket.recv_from(&mut buffer) {
+                Ok((length, from)) => {
+                    // Packets from other programs or versions are dropped.
+                    if let Ok(message) = decode(&buffer[..length]) {
+                        self.handle(message, from)?;
+                    }
+                }
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => return Ok(()),
+                // A client that went away makes the next read fail on some platforms.
+                Err(e) if e.kind() == io::ErrorKind::ConnectionReset => continue,
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    fn handle(&mut self, message: Message, from: SocketAddr) -> io::Result<()> {
+        match message {
+            // A single client plays, the others are ignored until it leaves.
+            Message::Join if self.client.is_none() || self.client == Some(from) => {
+                self.client = Some(from);
+                self.send(&Message::Welcome {
+                    side: Host::REMOTE_SIDE,
+                    rules: self.rules.clone(),
+                })?;
+            }
+            Message::Input { sequence, axes, .. } if self.client == Some(from) => {
+                self.receive_inputs(sequence, &axes)
+            }
+            Message::Leave if self.client == Some(from) => {
+                self.client = None;
+                self.inputs.clear();
+                self.remote_axis = 0.0;
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Stores the inputs of an input packet. Inputs already played, and the
+    /// copies sent again for redundancy, are dropped.
+    fn receive_inputs(&mut self, sequence: u32, axes: &[f32]) {
+        for (age, axis) in axes.iter().enumerate() {
+            let number = match sequence.checked_sub(age as u32) {
+                Some(number) if number >= self.next_input => number,
+                _ => break,
+            };
+            self.inputs.insert(number, *axis);
+        }
+        while self.inputs.len() > MAX_INPUT_BACKLOG {
+            let oldest = *self.inputs.keys().next().unwrap();
+            self.inputs.remove(&oldest);
+            self.next_input = oldest + 1;
+        }
+    }
+
+    /// Returns the input of the client for the next tick. A lost or late input
+    /// is replaced by the last one.
+    pub fn remote_input(&mut self) -> f32 {
+        // Every packet carries the previous inputs as well, so an input
+        // missing while a newer one arrived is lost for good.
+        if let Some((&number, &axis)) = self.inputs.iter().next() {
+            self.inputs.remove(&number);
+            self.next_input = number + 1;
+            self.remote_axis = axis;
+        }
+        self.remote_axis
+    }
+
+    /// Number of the newest client input received.
+    #[cfg(test)]
+    pub fn received_input(&self) -> u32 {
+        self.inputs
+            .keys()
+            .next_back()
+            .copied()
+            .unwrap_or_else(|| self.last_input())
+    }
+
+    /// Number of the last client input played.
+    pub fn last_input(&self) -> u32 {
+        self.next_input - 1
+    }
+
+    /// Sends the state of the match to the client after a tick, with the
+    /// `events` of that tick.
+    pub fn send_snapshot(&mut self, sim: &Simulation, events: &[SimEvent]) -> io::Result<()> {
+        self.tick += 1;
+        let tick = self.tick;
+        self.events
+            .extend(events.iter().map(|event| (tick, *event)));
+        while self
+            .events
+            .front()
+            .is_some_and(|(told, _)| told + EVENT_REDUNDANCY <= tick)
+            || self.events.len() > MAX_SNAPSHOT_EVENTS
+        {
+            self.events.pop_front();
+        }
+        let snapshot = Snapshot {
+            tick: self.tick,
+            last_input: self.last_input(),
+            paddles: sim.paddles.iter().map(PaddleState::new).collect(),
+            balls: sim.balls.iter().map(BallSnapshot::new).collect(),
+            score: sim.score.clone(),
+            powerups: sim.powerups.clone(),
+            effects: sim.effects.clone(),
+            events: self.events.iter().copied().collect(),
+        };
+        self.send(&Message::Snapshot(snapshot))
+    }
+
+    fn send(&self, message: &Message) -> io::Result<()> {
+        if let Some(client) = self.client {
+            match self.socket.send_to(&encode(message), client) {
+                // A full buffer is the same as a lost packet.
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => {}
+                result => {
+                    result?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Drop for Host {
+    fn drop(&mut self) {
+        let _ = self.send(&Message::Leave);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn inputs_are_played_in_order_despite_reordering() {
+        let mut host = Host::bind("127.0.0.1:0", GameRules::default()).unwrap();
+        // Input packets 3, 1 and 2 arrive in the wrong order.
+        host.receive_inputs(3, &[0.3, 0.2, 0.1]);
+        host.receive_inputs(1, &[0.1]);
+        host.receive_inputs(2, &[0.2, 0.1]);
+
+        assert_eq!(host.remote_input(), 0.1);
+        assert_eq!(host.remote_input(), 0.2);
+        assert_eq!(host.remote_input(), 0.3);
+        assert_eq!(host.last_input(), 3);
+    }
+
+    #[test]
+    fn lost_packets_are_covered_by_the_next_ones() {
+        let mut host = Host::bind("127.0.0.1:0", GameRules::default()).unwrap();
+        host.receive_inputs(1, &[1.0]);
+        assert_eq!(host.remote_input(), 1.0);
+
+        // Packets 2 to 4 are lost, packet 5 repeats them.
+        host.receive_inputs(5, &[0.5, 0.4, 0.3, 0.2, 1.0]);
+        let played = (0..4).map(|_| host.remote_input()).collect::<Vec<_>>();
+        assert_eq!(played, vec![0.2, 0.3, 0.4, 0.5]);
+
+        // Nothing new arrived, the last input is played again.
+        assert_eq!(host.remote_input(), 0.5);
+        assert_eq!(host.last_input(), 5);
+    }
+
+    #[test]
+    fn late_copies_of_played_inputs_are_ignored() {
+        let mut host = Host::bind("127.0.0.1:0", GameRules::default()).unwrap();
+        host.receive_inputs(2, &[0.2, 0.1]);
+        host.remote_input();
+        host.remote_input();
+
+        host.receive_inputs(1, &[0.1]);
+        assert_eq!(host.remote_input(), 0.2);
+        assert_eq!(host.last_input(), 2);
+    }
+}
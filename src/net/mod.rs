@@ -0,0 +1,124 @@
+//! Two players on two machines of a local network.
+//!
+//! The `Host` runs the match and the `Client` plays the other paddle. They talk
+//! over UDP with the packets of the `protocol` module: the client sends its
+//! paddle input every tick, the host answers with a snapshot of the match.
+//! Inputs are sent several times and snapshots carry their tick, so lost and
+//! reordered packets only delay the game.
+//!
+//! Two `Peer`s play without a host instead: both simulate the match and only
+//! send their inputs, the `rollback` module corrects what was predicted once
+//! the inputs of the other peer arrive.
+
+pub use self::client::Client;
+pub use self::host::Host;
+pub use self::peer::Peer;
+
+mod client;
+mod host;
+mod peer;
+pub mod protocol;
+pub mod rollback;
+
+use std::io;
+
+use crate::rules::GameRules;
+
+/// NetSession is the network side of a match played over the network.
+pub enum NetSession {
+    Host(Host),
+    Client(Client),
+    Peer(Peer),
+}
+
+/// Network matches are played by two players, one on each machine.
+fn check_players(rules: &GameRules) -> io::Result<()> {
+    if rules.players == 2 {
+        Ok(())
+    } else {
+        Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "network matches are played by two players",
+        ))
+    }
+}
+
+/// Errors telling that a packet was lost, not that the socket is broken.
+fn is_retryable(e: &io::Error) -> bool {
+    matches!(
+        e.kind(),
+        io::ErrorKind::TimedOut
+            | io::ErrorKind::WouldBlock
+            | io::ErrorKind::ConnectionRefused
+            | io::ErrorKind::ConnectionReset
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        thread,
+        time::{Duration, Instant},
+    };
+
+    use super::*;
+    use crate::rules::GameRules;
+    use crate::sim::{Inputs, Side, Simulation, TICK};
+
+    /// Calls `done` until it returns true, failing after a second.
+    fn wait_for<F: FnMut() -> bool>(mut done: F) {
+        let started = Instant::now();
+        while !done() {
+            assert!(started.elapsed() < Duration::from_secs(1), "timed out");
+            thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    #[test]
+    fn client_plays_against_host_over_loopback() {
+        let rules = GameRules::default();
+        let mut host = Host::bind("127.0.0.1:0", rules.clone()).unwrap();
+        let address = host.local_addr().unwrap();
+
+        let joining =
+            thread::spawn(move || Client::connect(address, Duration::from_secs(5)).unwrap());
+        wait_for(|| {
+            host.poll().unwrap();
+            host.is_connected()
+        });
+        let mut client = joining.join().unwrap();
+        assert_eq!(client.side(), Host::REMOTE_SIDE);
+        assert_eq!(client.simulation().rules, rules);
+
+        // The client pushes its paddle up for half a second.
+        let mut sim = Simulation::new(rules);
+        for tick in 1..=60 {
+            client.tick(1.0).unwrap();
+            // The own paddle moves before the host even knows about it.
+            let predicted = client.simulation().paddle(Side::Right).unwrap().position;
+            assert!(predicted[1] > sim.paddle(Side::Right).unwrap().position[1]);
+
+            wait_for(|| {
+                host.poll().unwrap();
+                host.received_input() >= tick
+            });
+            let mut inputs = Inputs::default();
+            inputs.set(Host::REMOTE_SIDE, host.remote_input());
+            let events = sim.step(TICK, &inputs);
+            host.send_snapshot(&sim, &events).unwrap();
+        }
+        assert_eq!(host.last_input(), 60);
+
+        // Once every input is played, the client sees exactly the host match.
+        wait_for(|| {
+            client.poll().unwrap();
+            client.simulation().balls[0].position == sim.balls[0].position
+        });
+        let seen = client.simulation();
+        assert_eq!(seen.score, sim.score);
+        for (seen, paddle) in seen.paddles.iter().zip(sim.paddles.iter()) {
+            assert_eq!(seen.position, paddle.position);
+        }
+        assert!(sim.paddle(Side::Right).unwrap().position[1] > sim.rules.halve_height());
+    }
+}
@@ -0,0 +1,316 @@
+use std::{
+    collections::BTreeMap,
+    io,
+    net::{ToSocketAddrs, UdpSocket},
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::net::protocol::{decode, encode, Message, MAX_PACKET_SIZE};
+use crate::net::rollback::Rollback;
+use crate::net::{check_players, is_retryable};
+use crate::rules::GameRules;
+use crate::sim::{Side, SimEvent, Simulation};
+
+/// Time between two `Hello` packets while the other peer does not answer.
+const HELLO_RETRY: Duration = Duration::from_millis(250);
+
+/// Peer plays one paddle of a match simulated on both machines, with
+/// rollback: nobody waits for the other to show their own paddle move.
+pub struct Peer {
+    socket: UdpSocket,
+    session: Rollback,
+    /// Matches started so far, the inputs of another match are not mixed
+    /// with the ones of this one.
+    round: u32,
+    /// Inputs of the next match, by tick, sent by a peer that started it first.
+    early: BTreeMap<u32, f32>,
+    events: Vec<SimEvent>,
+}
+
+impl Peer {
+    /// Listens on `port` and greets the peer at `address` until it answers,
+    /// for at most `timeout`. The peers must play different sides with the
+    /// same rules, nothing else is accepted before their hello says so.
+    pub fn connect<A: ToSocketAddrs>(
+        port: u16,
+        address: A,
+        side: Side,
+        rules: GameRules,
+        timeout: Duration,
+    ) -> io::Result<Peer> {
+        check_players(&rules)?;
+        let remote = address
+            .to_socket_addrs()?
+            .next()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "no peer address"))?;
+        let socket = if remote.is_ipv4() {
+            UdpSocket::bind(("0.0.0.0", port))?
+        } else {
+            UdpSocket::bind(("::", port))?
+        };
+        socket.connect(remote)?;
+        socket.set_read_timeout(Some(HELLO_RETRY))?;
+
+        let hello = encode(&Message::Hello {
+            side,
+            rules: rules.clone(),
+        });
+        let started = Instant::now();
+        let mut buffer = [0; MAX_PACKET_SIZE];
+        loop {
+            if started.elapsed() > timeout {
+                return Err(io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    format!("no answer from {}", remote),
+                ));
+            }
+            socket.send(&hello)?;
+            match socket.recv(&mut buffer) {
+                // Inputs of a peer that already plays are ignored until it
+                // answers our hello with its own.
+                Ok(length) => {
+                    if let Ok(Message::Hello {
+                        side: remote_side,
+                        rules: remote_rules,
+                    }) = decode(&buffer[..length])
+                    {
+                        if remote_side == side {
+                            return Err(invalid("both peers play the same side"));
+                        }
+                        if remote_rules != rules {
+                            return Err(invalid("the peers play with different rules"));
+                        }
+                        break;
+                    }
+                }
+                Err(e) if is_retryable(&e) => {
+                    // A refused packet comes back at once, wait before the next try.
+                    if e.kind() == io::ErrorKind::ConnectionRefused {
+                        thread::sleep(HELLO_RETRY);
+                    }
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        // The other peer may still wait for our hello.
+        socket.send(&hello)?;
+
+        socket.set_read_timeout(None)?;
+        socket.set_nonblocking(true)?;
+        Ok(Peer {
+            socket,
+            session: Rollback::new(side, rules),
+            round: 0,
+            early: BTreeMap::new(),
+            events: Vec::new(),
+        })
+    }
+
+    /// Starts a new match from the first tick, with the inputs the other peer
+    /// already sent for it.
+    pub fn start_match(&mut self) {
+        let rules = self.simulation().rules.clone();
+        self.session = Rollback::new(self.side(), rules);
+        self.round += 1;
+        for (tick, axis) in std::mem::take(&mut self.early) {
+            self.session.receive(tick, &[axis]);
+        }
+        self.events.clear();
+    }
+
+    pub fn side(&self) -> Side {
+        self.session.local_side()
+    }
+
+    /// The match as this peer sees it, predictions included.
+    pub fn simulation(&self) -> &Simulation {
+        self.session.simulation()
+    }
+
+    /// Returns what happened in the ticks played since the last call.
+    pub fn take_events(&mut self) -> Vec<SimEvent> {
+        std::mem::take(&mut self.events)
+    }
+
+    /// Reads every packet received since the last call, correcting the
+    /// predictions they prove wrong.
+    pub fn poll(&mut self) -> io::Result<()> {
+        let mut buffer = [0; MAX_PACKET_SIZE];
+        loop {
+            match self.socket.recv(&mut buffer) {
+                Ok(length) => match decode(&buffer[..length]) {
+                    Ok(Message::Input {
+                        round,
+                        sequence,
+                        axes,
+                    }) => {
+                        if round == self.round {
+                            let events = self.session.receive(sequence, &axes);
+                            self.events.extend(events);
+                        } else if round == self.round + 1 {
+                            // Kept until this peer starts the match too.
+                            for (age, axis) in axes.iter().enumerate() {
+                                if let Some(tick) = sequence.checked_sub(age as u32) {
+                                    self.early.entry(tick).or_insert(*axis);
+                                }
+                            }
+                        }
+                    }
+                    // The other peer missed our hello and is still waiting.
+                    Ok(Message::Hello { .. }) => self.send(&Message::Hello {
+                        side: self.side(),
+                        rules: self.simulation().rules.clone(),
+                    })?,
+                    _ => {}
+                },
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => return Ok(()),
+                Err(e) if is_retryable(&e) => continue,
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// Plays the next tick with the local input `axis` and sends it. When the
+    /// other peer is too far behind, the tick waits and only the inputs are
+    /// sent again.
+    pub fn tick(&mut self, axis: f32) -> io::Result<()> {
+        if self.session.can_advance() {
+            let events = self.session.advance(axis);
+            self.events.extend(events);
+        }
+        let (sequence, axes) = self.session.local_inputs();
+        self.send(&Message::Input {
+            round: self.round,
+            sequence,
+            axes,
+        })
+    }
+
+    fn send(&self, message: &Message) -> io::Result<()> {
+        match self.socket.send(&encode(message)) {
+            // A full buffer or a peer not listening yet is a lost packet.
+            Err(e) if is_retryable(&e) => Ok(()),
+            result => result.map(|_| ()),
+        }
+    }
+}
+
+impl Drop for Peer {
+    fn drop(&mut self) {
+        let _ = self.send(&Message::Leave);
+    }
+}
+
+fn invalid(reason: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, reason)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sim::ScoreBoard;
+
+    fn free_port() -> u16 {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        socket.local_addr().unwrap().port()
+    }
+
+    fn connect_pair() -> (Peer, Peer) {
+        let (left_port, right_port) = (free_port(), free_port());
+        let rules = GameRules::default();
+        let timeout = Duration::from_secs(5);
+        let right = {
+            let rules = rules.clone();
+            thread::spawn(move || {
+                Peer::connect(
+                    right_port,
+                    ("127.0.0.1", left_port),
+                    Side::Right,
+                    rules,
+                    timeout,
+                )
+            })
+        };
+        let left = Peer::connect(
+            left_port,
+            ("127.0.0.1", right_port),
+            Side::Left,
+            rules,
+            timeout,
+        );
+        (left.unwrap(), right.join().unwrap().unwrap())
+    }
+
+    fn played(peer: &Peer) -> u32 {
+        peer.session.tick()
+    }
+
+    /// Plays both peers up to `ticks`, and returns once they agree.
+    fn play_to(left: &mut Peer, right: &mut Peer, ticks: u32) {
+        let started = Instant::now();
+        while started.elapsed() < Duration::from_secs(10) {
+            for peer in [&mut *left, &mut *right].iter_mut() {
+                peer.poll().unwrap();
+                let tick = played(peer);
+                if tick < ticks {
+                    peer.tick(((tick / 20) % 3) as f32 - 1.0).unwrap();
+                }
+            }
+            if played(left) == ticks && left.simulation() == right.simulation() {
+                return;
+            }
+            thread::sleep(Duration::from_millis(1));
+        }
+        panic!("the peers never agreed");
+    }
+
+    #[test]
+    fn inputs_before_the_hello_are_ignored() {
+        let port = free_port();
+        let intruder = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let address = intruder.local_addr().unwrap();
+        let peer = thread::spawn(move || {
+            Peer::connect(
+                port,
+                address,
+                Side::Left,
+                GameRules::default(),
+                Duration::from_millis(600),
+            )
+        });
+        let input = encode(&Message::Input {
+            round: 1,
+            sequence: 2,
+            axes: vec![1.0],
+        });
+        while !peer.is_finished() {
+            let _ = intruder.send_to(&input, ("127.0.0.1", port));
+            thread::sleep(Duration::from_millis(20));
+        }
+        let error = peer.join().unwrap().err().unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::TimedOut);
+    }
+
+    #[test]
+    fn rematch_starts_both_peers_over() {
+        let (mut left, mut right) = connect_pair();
+        left.start_match();
+        right.start_match();
+        play_to(&mut left, &mut right, 300);
+
+        // The left player starts the next match first and plays as far as it
+        // can, while the right one still looks at the last match.
+        left.start_match();
+        for _ in 0..50 {
+            left.tick(1.0).unwrap();
+            right.poll().unwrap();
+            thread::sleep(Duration::from_millis(1));
+        }
+        assert_eq!(played(&right), 300);
+
+        right.start_match();
+        play_to(&mut left, &mut right, 120);
+        assert_eq!(left.simulation().score, ScoreBoard::default());
+    }
+}
@@ -0,0 +1,793 @@
+//! The wire protocol between a host and a client, or between two peers.
+//!
+//! Every packet starts with the `PONG` magic, the protocol version and the kind
+//! of message, followed by the message fields in little endian. A packet of
+//! another version is refused instead of being misread.
+
+use std::{error::Error, fmt};
+
+use crate::rules::PowerUpRules;
+use crate::rules::{GameRules, MAX_BALLS};
+use crate::sim::{
+    Ball, BallState, Effect, Paddle, PowerUp, PowerUpKind, ScoreBoard, Side, SimEvent,
+};
+
+/// Version of the protocol, bumped on every change of the packets.
+pub const PROTOCOL_VERSION: u16 = 6;
+/// Largest packet sent, small enough to never be fragmented.
+pub const MAX_PACKET_SIZE: usize = 1200;
+/// Number of inputs repeated in every input packet, so a lost packet is
+/// covered by the next ones.
+pub const INPUT_REDUNDANCY: usize = 8;
+/// Number of ticks whose events are repeated in every snapshot.
+pub const EVENT_REDUNDANCY: u32 = 8;
+/// Most events sent in a snapshot, the oldest ones are dropped past that.
+pub const MAX_SNAPSHOT_EVENTS: usize = 64;
+
+const MAGIC: [u8; 4] = *b"PONG";
+
+const JOIN: u8 = 0;
+const WELCOME: u8 = 1;
+const INPUT: u8 = 2;
+const SNAPSHOT: u8 = 3;
+const LEAVE: u8 = 4;
+const HELLO: u8 = 5;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Message {
+    /// Sent by a client until the host answers.
+    Join,
+    /// Sent by the host to accept a client, with the side it plays and the rules of the match.
+    Welcome { side: Side, rules: GameRules },
+    /// Sent by the client, or by a peer, every tick. `axes[0]` is the input
+    /// number `sequence`, `axes[1]` the one before it, and so on. Between
+    /// peers the number is the tick the input is played on, and `round` the
+    /// match it belongs to, counted from 1. Clients always send 0.
+    Input {
+        round: u32,
+        sequence: u32,
+        axes: Vec<f32>,
+    },
+    /// Sent by the host every tick.
+    Snapshot(Snapshot),
+    /// Sent by either side when it stops playing.
+    Leave,
+    /// Sent by a peer until the other one answers, with the side it plays and
+    /// its rules. Both peers must have the same rules and different sides.
+    Hello { side: Side, rules: GameRules },
+}
+
+/// State of the match on the host after a tick.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Snapshot {
+    pub tick: u32,
+    /// Last client input applied by the host, the client predicts from there.
+    pub last_input: u32,
+    pub paddles: Vec<PaddleState>,
+    pub balls: Vec<BallSnapshot>,
+    pub score: ScoreBoard,
+    pub powerups: Vec<PowerUp>,
+    pub effects: Vec<Effect>,
+    /// What happened during the last `EVENT_REDUNDANCY` ticks, with the tick
+    /// of each event, so the client gets the events of lost snapshots too.
+    pub events: Vec<(u32, SimEvent)>,
+}
+
+/// What changes of a paddle during a match, the rest comes from the rules.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PaddleState {
+    pub side: Side,
+    pub position: [f32; 2],
+    pub velocity: f32,
+    pub length: f32,
+}
+
+impl PaddleState {
+    pub fn new(paddle: &Paddle) -> PaddleState {
+        PaddleState {
+            side: paddle.side,
+            position: paddle.position,
+            velocity: paddle.velocity,
+            length: paddle.length(),
+        }
+    }
+}
+
+/// What changes of a ball during a match, the rest comes from the rules.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BallSnapshot {
+    pub position: [f32; 2],
+    pub velocity: [f32; 2],
+    pub state: BallState,
+    pub waiting_time: f32,
+}
+
+impl BallSnapshot {
+    pub fn new(ball: &Ball) -> BallSnapshot {
+        BallSnapshot {
+            position: ball.position,
+            velocity: ball.velocity,
+            state: ball.state,
+            waiting_time: ball.waiting_time,
+        }
+    }
+}
+
+/// Errors that can happen while decoding a packet.
+#[derive(Debug, PartialEq)]
+pub enum ProtocolError {
+    /// The packet does not start with the magic, it is not meant for us.
+    NotPong,
+    /// The packet was sent with another version of the protocol.
+    Version(u16),
+    UnknownMessage(u8),
+    /// The packet ends in the middle of a message.
+    Truncated,
+    /// A field has a value no message can have.
+    Invalid(&'static str),
+}
+
+impl fmt::Display for ProtocolError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProtocolError::NotPong => write!(f, "not a pong packet"),
+            ProtocolError::Version(version) => write!(
+                f,
+                "protocol version {} is not supported, expected {}",
+                version, PROTOCOL_VERSION
+            ),
+            ProtocolError::UnknownMessage(kind) => write!(f, "unknown message kind {}", kind),
+            ProtocolError::Truncated => write!(f, "truncated packet"),
+            ProtocolError::Invalid(field) => write!(f, "invalid `{}`", field),
+        }
+    }
+}
+
+impl Error for ProtocolError {}
+
+pub fn encode(message: &Message) -> Vec<u8> {
+    let mut writer = Writer(Vec::with_capacity(64));
+    writer.0.extend_from_slice(&MAGIC);
+    writer.u16(PROTOCOL_VERSION);
+    match message {
+        Message::Join => writer.u8(JOIN),
+        Message::Welcome { side, rules } => {
+            writer.u8(WELCOME);
+            writer.side(*side);
+            write_rules(&mut writer, rules);
+        }
+        Message::Input {
+            round,
+            sequence,
+            axes,
+        } => {
+            writer.u8(INPUT);
+            writer.u32(*round);
+            writer.u32(*sequence);
+            writer.u8(axes.len().min(INPUT_REDUNDANCY) as u8);
+            for axis in axes.iter().take(INPUT_REDUNDANCY) {
+                writer.f32(*axis);
+            }
+        }
+        Message::Snapshot(snapshot) => {
+            writer.u8(SNAPSHOT);
+            write_snapshot(&mut writer, snapshot);
+        }
+        Message::Leave => writer.u8(LEAVE),
+        Message::Hello { side, rules } => {
+            writer.u8(HELLO);
+            writer.side(*side);
+            write_rules(&mut writer, rules);
+        }
+    }
+    writer.0
+}
+
+pub fn decode(packet: &[u8]) -> Result<Message, ProtocolError> {
+    let mut reader = Reader {
+        packet,
+        position: 0,
+    };
+    if packet.len() < MAGIC.len() || packet[..MAGIC.len()] != MAGIC {
+        return Err(ProtocolError::NotPong);
+    }
+    reader.position = MAGIC.len();
+    let version = reader.u16()?;
+    if version != PROTOCOL_VERSION {
+        return Err(ProtocolError::Version(version));
+    }
+
+    let message = match reader.u8()? {
+        JOIN => Message::Join,
+        WELCOME => Message::Welcome {
+            side: reader.side()?,
+            rules: read_rules(&mut reader)?,
+        },
+        INPUT => {
+            let round = reader.u32()?;
+            let sequence = reader.u32()?;
+            let count = reader.u8()? as usize;
+            if count > INPUT_REDUNDANCY {
+                return Err(ProtocolError::Invalid("axes"));
+            }
+            let axes = (0..count)
+                .map(|_| reader.f32())
+                .collect::<Result<Vec<_>, _>>()?;
+            Message::Input {
+                round,
+                sequence,
+                axes,
+            }
+        }
+        SNAPSHOT => Message::Snapshot(read_snapshot(&mut reader)?),
+        LEAVE => Message::Leave,
+        HELLO => Message::Hello {
+            side: reader.side()?,
+            rules: read_rules(&mut reader)?,
+        },
+        kind => return Err(ProtocolError::UnknownMessage(kind)),
+    };
+    Ok(message)
+}
+
+fn write_rules(writer: &mut Writer, rules: &GameRules) {
+    for value in [
+        rules.arena_width,
+        rules.arena_height,
+        rules.paddle_width,
+        rules.paddle_height,
+        rules.paddle_max_speed,
+        rules.paddle_acceleration,
+        rules.paddle_deceleration,
+        rules.ball_radius,
+        rules.ball_velocity_x,
+        rules.ball_velocity_y,
+        rules.ball_acceleration,
+        rules.max_ball_velocity_x,
+        rules.max_ball_velocity_y,
+        rules.max_bounce_angle,
+        rules.paddle_english,
+        rules.serve_wait,
+        rules.serve_stagger,
+    ]
+    .iter()
+    {
+        writer.f32(*value);
+    }
+    writer.i32(rules.points_to_win);
+    writer.u8(rules.win_by_two as u8);
+    writer.i32(rules.best_of_sets);
+    writer.i32(rules.players);
+    writer.i32(rules.ball_count);
+    writer.u8(rules.ball_collisions as u8);
+
+    let powerups = &rules.powerups;
+    writer.u8(powerups.enabled.len() as u8);
+    for kind in powerups.enabled.iter() {
+        writer.powerup_kind(*kind);
+    }
+    for value in [
+        powerups.spawn_interval,
+        powerups.radius,
+        powerups.duration,
+        powerups.long_paddle_scale,
+        powerups.short_paddle_scale,
+        powerups.fast_ball_scale,
+    ]
+    .iter()
+    {
+        writer.f32(*value);
+    }
+    writer.i32(powerups.max_pickups);
+}
+
+fn read_rules(reader: &mut Reader<'_>) -> Result<GameRules, ProtocolError> {
+    let rules = GameRules {
+        arena_width: reader.f32()?,
+        arena_height: reader.f32()?,
+        paddle_width: reader.f32()?,
+        paddle_height: reader.f32()?,
+        paddle_max_speed: reader.f32()?,
+        paddle_acceleration: reader.f32()?,
+        paddle_deceleration: reader.f32()?,
+        ball_radius: reader.f32()?,
+        ball_velocity_x: reader.f32()?,
+        ball_velocity_y: reader.f32()?,
+        ball_acceleration: reader.f32()?,
+        max_ball_velocity_x: reader.f32()?,
+        max_ball_velocity_y: reader.f32()?,
+        max_bounce_angle: reader.f32()?,
+        paddle_english: reader.f32()?,
+        serve_wait: reader.f32()?,
+        serve_stagger: reader.f32()?,
+        points_to_win: reader.i32()?,
+        win_by_two: reader.u8()? != 0,
+        best_of_sets: reader.i32()?,
+        players: reader.i32()?,
+        ball_count: reader.i32()?,
+        ball_collisions: reader.u8()? != 0,
+        powerups: PowerUpRules {
+            enabled: (0..reader.u8()?)
+                .map(|_| reader.powerup_kind())
+                .collect::<Result<Vec<_>, _>>()?,
+            spawn_interval: reader.f32()?,
+            radius: reader.f32()?,
+            duration: reader.f32()?,
+            long_paddle_scale: reader.f32()?,
+            short_paddle_scale: reader.f32()?,
+            fast_ball_scale: reader.f32()?,
+            max_pickups: reader.i32()?,
+        },
+    };
+    rules
+        .validate()
+        .map_err(|_| ProtocolError::Invalid("rules"))?;
+    Ok(rules)
+}
+
+fn write_snapshot(writer: &mut Writer, snapshot: &Snapshot) {
+    writer.u32(snapshot.tick);
+    writer.u32(snapshot.last_input);
+
+    writer.u8(snapshot.paddles.len() as u8);
+    for paddle in snapshot.paddles.iter() {
+        writer.side(paddle.side);
+        writer.f32(paddle.position[0]);
+        writer.f32(paddle.position[1]);
+        writer.f32(paddle.velocity);
+        writer.f32(paddle.length);
+    }
+
+    writer.u8(snapshot.balls.len() as u8);
+    for ball in snapshot.balls.iter() {
+        writer.f32(ball.position[0]);
+        writer.f32(ball.position[1]);
+        writer.f32(ball.velocity[0]);
+        writer.f32(ball.velocity[1]);
+        writer.u8(match ball.state {
+            BallState::Waiting => 0,
+            BallState::Moving => 1,
+        });
+        writer.f32(ball.waiting_time);
+    }
+
+    let score = &snapshot.score;
+    for value in score.points.iter().chain(score.sets.iter()) {
+        writer.i32(*value);
+    }
+    match score.winner {
+        None => writer.u8(0),
+        Some(side) => writer.u8(1 + side.index() as u8),
+    }
+
+    writer.u8(snapshot.powerups.len() as u8);
+    for powerup in snapshot.powerups.iter() {
+        writer.powerup_kind(powerup.kind);
+        writer.f32(powerup.position[0]);
+        writer.f32(powerup.position[1]);
+    }
+    writer.u8(snapshot.effects.len() as u8);
+    for effect in snapshot.effects.iter() {
+        writer.powerup_kind(effect.kind);
+        writer.side(effect.side);
+        writer.f32(effect.remaining);
+    }
+
+    let events = &snapshot.events[snapshot.events.len().saturating_sub(MAX_SNAPSHOT_EVENTS)..];
+    writer.u8(events.len() as u8);
+    for (tick, event) in events.iter() {
+        writer.u32(*tick);
+        write_event(writer, event);
+    }
+}
+
+fn write_event(writer: &mut Writer, event: &SimEvent) {
+    match *event {
+        SimEvent::WallBounce { ball } => {
+            writer.u8(0);
+            writer.u8(ball as u8);
+        }
+        SimEvent::PaddleHit { ball, side } => {
+            writer.u8(1);
+            writer.u8(ball as u8);
+            writer.side(side);
+        }
+        SimEvent::BallHit { ball, other } => {
+            writer.u8(2);
+            writer.u8(ball as u8);
+            writer.u8(other as u8);
+        }
+        SimEvent::Goal { ball, scorer } => {
+            writer.u8(3);
+            writer.u8(ball as u8);
+            writer.side(scorer);
+        }
+        SimEvent::Serve { ball } => {
+            writer.u8(4);
+            writer.u8(ball as u8);
+        }
+        SimEvent::MatchOver { winner } => {
+            writer.u8(5);
+            writer.side(winner);
+        }
+        SimEvent::PowerUpSpawned { kind } => {
+            writer.u8(6);
+            writer.powerup_kind(kind);
+        }
+        SimEvent::PowerUpCollected { kind, side } => {
+            writer.u8(7);
+            writer.powerup_kind(kind);
+            writer.side(side);
+        }
+        SimEvent::PowerUpExpired { kind, side } => {
+            writer.u8(8);
+            writer.powerup_kind(kind);
+            writer.side(side);
+        }
+        SimEvent::ObstacleHit { ball } => {
+            writer.u8(9);
+            writer.u8(ball as u8);
+        }
+        SimEvent::BumperHit { ball } => {
+            writer.u8(10);
+            writer.u8(ball as u8);
+        }
+        SimEvent::PortalUsed { ball } => {
+            writer.u8(11);
+            writer.u8(ball as u8);
+        }
+    }
+}
+
+fn read_event(reader: &mut Reader<'_>) -> Result<SimEvent, ProtocolError> {
+    Ok(match reader.u8()? {
+        0 => SimEvent::WallBounce {
+            ball: reader.ball()?,
+        },
+        1 => SimEvent::PaddleHit {
+            ball: reader.ball()?,
+            side: reader.side()?,
+        },
+        2 => SimEvent::BallHit {
+            ball: reader.ball()?,
+            other: reader.ball()?,
+        },
+        3 => SimEvent::Goal {
+            ball: reader.ball()?,
+            scorer: reader.side()?,
+        },
+        4 => SimEvent::Serve {
+            ball: reader.ball()?,
+        },
+        5 => SimEvent::MatchOver {
+            winner: reader.side()?,
+        },
+        6 => SimEvent::PowerUpSpawned {
+            kind: reader.powerup_kind()?,
+        },
+        7 => SimEvent::PowerUpCollected {
+            kind: reader.powerup_kind()?,
+            side: reader.side()?,
+        },
+        8 => SimEvent::PowerUpExpired {
+            kind: reader.powerup_kind()?,
+            side: reader.side()?,
+        },
+        9 => SimEvent::ObstacleHit {
+            ball: reader.ball()?,
+        },
+        10 => SimEvent::BumperHit {
+            ball: reader.ball()?,
+        },
+        11 => SimEvent::PortalUsed {
+            ball: reader.ball()?,
+        },
+        _ => return Err(ProtocolError::Invalid("event")),
+    })
+}
+
+fn read_snapshot(reader: &mut Reader<'_>) -> Result<Snapshot, ProtocolError> {
+    let tick = reader.u32()?;
+    let last_input = reader.u32()?;
+
+    let paddles = (0..reader.u8()?)
+        .map(|_| -> Result<PaddleState, ProtocolError> {
+            Ok(PaddleState {
+                side: reader.side()?,
+                position: [reader.f32()?, reader.f32()?],
+                velocity: reader.f32()?,
+                length: reader.f32()?,
+            })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let balls = (0..reader.u8()?)
+        .map(|_| -> Result<BallSnapshot, ProtocolError> {
+            Ok(BallSnapshot {
+                position: [reader.f32()?, reader.f32()?],
+                velocity: [reader.f32()?, reader.f32()?],
+                state: match reader.u8()? {
+                    0 => BallState::Waiting,
+                    1 => BallState::Moving,
+                    _ => return Err(ProtocolError::Invalid("ball state")),
+                },
+                waiting_time: reader.f32()?,
+            })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut score = ScoreBoard::default();
+    for value in score.points.iter_mut().chain(score.sets.iter_mut()) {
+        *value = reader.i32()?;
+    }
+    score.winner = match reader.u8()? {
+        0 => None,
+        side => Some(
+            *Side::ALL
+                .get(side as usize - 1)
+                .ok_or(ProtocolError::Invalid("winner"))?,
+        ),
+    };
+
+    let powerups = (0..reader.u8()?)
+        .map(|_| -> Result<PowerUp, ProtocolError> {
+            Ok(PowerUp {
+                kind: reader.powerup_kind()?,
+                position: [reader.f32()?, reader.f32()?],
+            })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let effects = (0..reader.u8()?)
+        .map(|_| -> Result<Effect, ProtocolError> {
+            Ok(Effect {
+                kind: reader.powerup_kind()?,
+                side: reader.side()?,
+                remaining: reader.f32()?,
+            })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let events = (0..reader.u8()?)
+        .map(|_| -> Result<(u32, SimEvent), ProtocolError> {
+            Ok((reader.u32()?, read_event(reader)?))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(Snapshot {
+        tick,
+        last_input,
+        paddles,
+        balls,
+        score,
+        powerups,
+        effects,
+        events,
+    })
+}
+
+struct Writer(Vec<u8>);
+
+impl Writer {
+    fn u8(&mut self, value: u8) {
+        self.0.push(value);
+    }
+
+    fn u16(&mut self, value: u16) {
+        self.0.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn u32(&mut self, value: u32) {
+        self.0.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn i32(&mut self, value: i32) {
+        self.0.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn f32(&mut self, value: f32) {
+        self.0.extend_from_slice(&value.to_bits().to_le_bytes());
+    }
+
+    fn side(&mut self, side: Side) {
+        self.u8(side.index() as u8);
+    }
+
+    fn powerup_kind(&mut self, kind: PowerUpKind) {
+        let index = PowerUpKind::ALL.iter().position(|k| *k == kind).unwrap();
+        self.u8(index as u8);
+    }
+}
+
+struct Reader<'a> {
+    packet: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, length: usize) -> Result<&'a [u8], ProtocolError> {
+        let end = self.position + length;
+        let bytes = self
+            .packet
+            .get(self.position..end)
+            .ok_or(ProtocolError::Truncated)?;
+        self.position = end;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8, ProtocolError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, ProtocolError> {
+        let mut bytes = [0; 2];
+        bytes.copy_from_slice(self.take(2)?);
+        Ok(u16::from_le_bytes(bytes))
+    }
+
+    fn u32(&mut self) -> Result<u32, ProtocolError> {
+        let mut bytes = [0; 4];
+        bytes.copy_from_slice(self.take(4)?);
+        Ok(u32::from_le_bytes(bytes))
+    }
+
+    fn i32(&mut self) -> Result<i32, ProtocolError> {
+        Ok(self.u32()? as i32)
+    }
+
+    fn f32(&mut self) -> Result<f32, ProtocolError> {
+        let value = f32::from_bits(self.u32()?);
+        if value.is_finite() {
+            Ok(value)
+        } else {
+            Err(ProtocolError::Invalid("number"))
+        }
+    }
+
+    /// Index of a ball, below `MAX_BALLS`.
+    fn ball(&mut self) -> Result<usize, ProtocolError> {
+        let ball = self.u8()? as usize;
+        if ball < MAX_BALLS as usize {
+            Ok(ball)
+        } else {
+            Err(ProtocolError::Invalid("ball"))
+        }
+    }
+
+    fn side(&mut self) -> Result<Side, ProtocolError> {
+        Side::ALL
+            .get(self.u8()? as usize)
+            .copied()
+            .ok_or(ProtocolError::Invalid("side"))
+    }
+
+    fn powerup_kind(&mut self) -> Result<PowerUpKind, ProtocolError> {
+        PowerUpKind::ALL
+            .get(self.u8()? as usize)
+            .copied()
+            .ok_or(ProtocolError::Invalid("power-up"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot() -> Snapshot {
+        Snapshot {
+            tick: 1234,
+            last_input: 1200,
+            paddles: vec![
+                PaddleState {
+                    side: Side::Left,
+                    position: [2.0, 40.5],
+                    velocity: -72.0,
+                    length: 24.0,
+                },
+                PaddleState {
+                    side: Side::Right,
+                    position: [98.0, 60.25],
+                    velocity: 0.0,
+                    length: 16.0,
+                },
+            ],
+            balls: vec![BallSnapshot {
+                position: [51.5, 22.125],
+                velocity: [-36.0, 18.0],
+                state: BallState::Moving,
+                waiting_time: 0.0,
+            }],
+            score: ScoreBoard {
+                points: [3, 7, 0, 2],
+                sets: [1, 0, 0, 0],
+                winner: Some(Side::Bottom),
+            },
+            powerups: vec![PowerUp {
+                kind: PowerUpKind::Shield,
+                position: [48.0, 70.0],
+            }],
+            effects: vec![Effect {
+                kind: PowerUpKind::LongPaddle,
+                side: Side::Left,
+                remaining: 3.5,
+            }],
+            events: vec![
+                (
+                    1227,
+                    SimEvent::PaddleHit {
+                        ball: 0,
+                        side: Side::Right,
+                    },
+                ),
+                (1234, SimEvent::WallBounce { ball: 0 }),
+                (
+                    1234,
+                    SimEvent::Goal {
+                        ball: 0,
+                        scorer: Side::Bottom,
+                    },
+                ),
+                (
+                    1234,
+                    SimEvent::MatchOver {
+                        winner: Side::Bottom,
+                    },
+                ),
+                (
+                    1234,
+                    SimEvent::PowerUpCollected {
+                        kind: PowerUpKind::Shield,
+                        side: Side::Left,
+                    },
+                ),
+            ],
+        }
+    }
+
+    #[test]
+    fn messages_survive_a_round_trip() {
+        let mut powerup_rules = GameRules::default();
+        powerup_rules.powerups.enabled = PowerUpKind::ALL.to_vec();
+        powerup_rules.players = 4;
+        let messages = vec![
+            Message::Join,
+            Message::Welcome {
+                side: Side::Right,
+                rules: GameRules::default(),
+            },
+            Message::Input {
+                round: 3,
+                sequence: 42,
+                axes: vec![1.0, 0.0, -1.0, -0.5],
+            },
+            Message::Snapshot(snapshot()),
+            Message::Leave,
+            Message::Hello {
+                side: Side::Left,
+                rules: powerup_rules,
+            },
+        ];
+        for message in messages {
+            let packet = encode(&message);
+            assert!(packet.len() <= MAX_PACKET_SIZE);
+            assert_eq!(decode(&packet), Ok(message));
+        }
+    }
+
+    #[test]
+    fn other_versions_are_refused() {
+        let mut packet = encode(&Message::Join);
+        packet[4..6].copy_from_slice(&(PROTOCOL_VERSION + 1).to_le_bytes());
+        assert_eq!(
+            decode(&packet),
+            Err(ProtocolError::Version(PROTOCOL_VERSION + 1))
+        );
+        assert_eq!(decode(b"HTTP/1.1"), Err(ProtocolError::NotPong));
+    }
+
+    #[test]
+    fn truncated_packets_are_refused() {
+        let packet = encode(&Message::Snapshot(snapshot()));
+        for length in 0..packet.len() {
+            assert!(decode(&packet[..length]).is_err());
+        }
+    }
+}
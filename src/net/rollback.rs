@@ -0,0 +1,508 @@
+//! Rollback of a match played on both machines.
+//!
+//! Each peer simulates the whole match. The input of the other player arrives
+//! late, so the tick is played with a prediction of it: the last input known.
+//! The state before every predicted tick is kept, and when the real input
+//! differs from the prediction, the match goes back to that state and plays
+//! again up to the present with the right inputs.
+
+use std::collections::{BTreeMap, VecDeque};
+
+use crate::net::protocol::INPUT_REDUNDANCY;
+use crate::rules::GameRules;
+use crate::sim::{Inputs, Side, SimEvent, Simulation, TICK};
+
+/// Ticks between reading a local input and playing it. The other peer gets
+/// that much time to receive it, which hides small latencies completely.
+pub const INPUT_DELAY: u32 = 2;
+/// Ticks that can be played ahead of the last input known from the other
+/// peer. The match waits past that, instead of predicting a whole second.
+pub const MAX_PREDICTION: u32 = 16;
+/// Local inputs kept after they are played, the other peer may still miss them.
+const LOCAL_HISTORY: u32 = 64;
+
+/// State of the match before a tick played with a predicted remote input.
+#[derive(Clone, Debug)]
+struct SavedTick {
+    tick: u32,
+    sim: Simulation,
+    predicted: f32,
+}
+
+/// Rollback is the match of one peer, whatever carries the inputs between
+/// the two.
+#[derive(Clone, Debug)]
+pub struct Rollback {
+    local_side: Side,
+    sim: Simulation,
+    /// Ticks played so far, also the number of the next tick.
+    tick: u32,
+    local_inputs: BTreeMap<u32, f32>,
+    remote_inputs: BTreeMap<u32, f32>,
+    /// Every remote input before this tick is known.
+    confirmed: u32,
+    /// Oldest first, only for the ticks that are not confirmed yet.
+    saved: VecDeque<SavedTick>,
+    /// Goals told for the ticks that are not confirmed yet, by tick, so a
+    /// rollback does not tell them twice.
+    goals: BTreeMap<u32, Vec<SimEvent>>,
+    rollbacks: u32,
+    /// The end of the match was told, once the ticks up to it were confirmed.
+    over: bool,
+}
+
+impl Rollback {
+    pub fn new(local_side: Side, rules: GameRules) -> Rollback {
+        let mut rollback = Rollback {
+            local_side,
+            sim: Simulation::new(rules),
+            tick: 0,
+            local_inputs: BTreeMap::new(),
+            remote_inputs: BTreeMap::new(),
+            confirmed: INPUT_DELAY,
+            saved: VecDeque::new(),
+            goals: BTreeMap::new(),
+            rollbacks: 0,
+            over: false,
+        };
+        // Nobody can press anything before the input delay.
+        for tick in 0..INPUT_DELAY {
+            rollback.local_inputs.insert(tick, 0.0);
+            rollback.remote_inputs.insert(tick, 0.0);
+        }
+        rollback
+    }
+
+    pub fn local_side(&self) -> Side {
+        self.local_side
+    }
+
+    /// The match as this peer sees it, predictions included.
+    pub fn simulation(&self) -> &Simulation {
+        &self.sim
+    }
+
+    /// Ticks played so far.
+    #[cfg(test)]
+    pub fn tick(&self) -> u32 {
+        self.tick
+    }
+
+    /// Every remote input before this tick is known, the match is the same on
+    /// both peers up to there.
+    #[cfg(test)]
+    pub fn confirmed(&self) -> u32 {
+        self.confirmed
+    }
+
+    /// Number of times the match went back to correct a prediction.
+    #[cfg(test)]
+    pub fn rollbacks(&self) -> u32 {
+        self.rollbacks
+    }
+
+    /// False when the next tick would be predicted too far ahead of the other
+    /// peer, the match then waits for its inputs.
+    pub fn can_advance(&self) -> bool {
+        self.tick < self.confirmed + MAX_PREDICTION
+    }
+
+    /// Plays the next tick. `axis` is the local input read now, it is played
+    /// `INPUT_DELAY` ticks later.
+    pub fn advance(&mut self, axis: f32) -> Vec<SimEvent> {
+        self.local_inputs.insert(self.tick + INPUT_DELAY, axis);
+        let mut events = self.play_tick();
+        // A predicted end of the match can still be rolled back.
+        events.retain(|event| !matches!(event, SimEvent::MatchOver { .. }));
+        events.extend(self.confirmed_match_over());
+
+        let kept = self.tick.saturating_sub(LOCAL_HISTORY);
+        while let Some(&oldest) = self.local_inputs.keys().next() {
+            if oldest >= kept {
+                break;
+            }
+            self.local_inputs.remove(&oldest);
+        }
+        events
+    }
+
+    /// Returns the last local input and its tick, followed by the ones
+    /// before it, newest first, as sent to the other peer.
+    pub fn local_inputs(&self) -> (u32, Vec<f32>) {
+        let last = (self.tick + INPUT_DELAY).saturating_sub(1);
+        let axes = self
+            .local_inputs
+            .range(..=last)
+            .rev()
+            .take(INPUT_REDUNDANCY)
+            .map(|(_, axis)| *axis)
+            .collect();
+        (last, axes)
+    }
+
+    /// Adds inputs of the other peer: `axes[0]` is its input of `tick`,
+    /// `axes[1]` the one of the tick before, and so on. When they prove a
+    /// prediction wrong, the match is played again from there and what
+    /// changed in the score is returned. The end of the match is returned
+    /// once every tick up to it is confirmed.
+    pub fn receive(&mut self, tick: u32, axes: &[f32]) -> Vec<SimEvent> {
+        for (age, axis) in axes.iter().enumerate() {
+            let input_tick = match tick.checked_sub(age as u32) {
+                Some(input_tick) if input_tick >= self.confirmed => input_tick,
+                _ => break,
+            };
+            self.remote_inputs.entry(input_tick).or_insert(*axis);
+        }
+        while self.remote_inputs.contains_key(&self.confirmed) {
+            self.confirmed += 1;
+        }
+        let mut events = self.correct_predictions();
+
+        // Confirmed ticks are never played again. Their inputs are kept until
+        // they are played, the other peer may be ahead, and the last one
+        // played is still needed to predict the next ones.
+        while self
+            .saved
+            .front()
+            .is_some_and(|saved| saved.tick < self.confirmed)
+        {
+            self.saved.pop_front();
+        }
+        self.goals = self.goals.split_off(&self.confirmed);
+        let kept = self.confirmed.min(self.tick).saturating_sub(1);
+        while let Some(&oldest) = self.remote_inputs.keys().next() {
+            if oldest >= kept {
+                break;
+            }
+            self.remote_inputs.remove(&oldest);
+        }
+        events.extend(self.confirmed_match_over());
+        events
+    }
+
+    /// Returns the end of the match the first time it is reached by the
+    /// confirmed ticks, which no input of the other peer can change anymore.
+    fn confirmed_match_over(&mut self) -> Option<SimEvent> {
+        // The oldest saved state is the match before the first predicted tick.
+        let confirmed = self.saved.front().map_or(&self.sim, |saved| &saved.sim);
+        let winner = confirmed.score.winner.filter(|_| !self.over)?;
+        self.over = true;
+        Some(SimEvent::MatchOver { winner })
+    }
+
+    /// Goes back to the first tick played with a wrong prediction and plays
+    /// every tick again from there.
+    fn correct_predictions(&mut self) -> Vec<SimEvent> {
+        let remote_inputs = &self.remote_inputs;
+        let wrong = self.saved.iter().position(|saved| {
+            remote_inputs
+                .get(&saved.tick)
+                .is_some_and(|&axis| axis != saved.predicted)
+        });
+        let index = match wrong {
+            Some(index) => index,
+            None => return Vec::new(),
+        };
+
+        let mut discarded = self.saved.drain(index..);
+        let restored = discarded.next().expect("wrong prediction is saved");
+        drop(discarded);
+
+        let present = self.tick;
+        self.sim = restored.sim;
+        self.tick = restored.tick;
+        let told = self.goals.split_off(&restored.tick);
+        while self.tick < present {
+            self.play_tick();
+        }
+        self.rollbacks += 1;
+
+        // Bounces and hits of the past ticks are already heard, only the
+        // goals not told yet are worth telling. The end of the match waits
+        // for the confirmed ticks.
+        let mut goals = self
+            .goals
+            .range(restored.tick..)
+            .flat_map(|(_, goals)| goals.iter().copied())
+            .collect::<Vec<_>>();
+        for goal in told.values().flatten() {
+            if let Some(index) = goals.iter().position(|new| new == goal) {
+                goals.remove(index);
+            }
+        }
+        goals
+    }
+
+    fn play_tick(&mut self) -> Vec<SimEvent> {
+        let tick = self.tick;
+        let local = self.local_inputs.get(&tick).copied().unwrap_or(0.0);
+        let remote = match self.remote_inputs.get(&tick) {
+            Some(&remote) => remote,
+            None => {
+                // The other player keeps doing what they did last.
+                let predicted = self
+                    .remote_inputs
+                    .range(..tick)
+                    .next_back()
+                    .map_or(0.0, |(_, axis)| *axis);
+                self.saved.push_back(SavedTick {
+                    tick,
+                    sim: self.sim.clone(),
+                    predicted,
+                });
+                predicted
+            }
+        };
+
+        let mut inputs = Inputs::default();
+        inputs.set(self.local_side, local);
+        inputs.set(self.local_side.opponent(), remote);
+        self.tick += 1;
+        let events = self.sim.step(TICK, &inputs);
+        let goals = events
+            .iter()
+            .filter(|event| matches!(event, SimEvent::Goal { .. }))
+            .copied()
+            .collect::<Vec<_>>();
+        if !goals.is_empty() {
+            self.goals.insert(tick, goals);
+        }
+        events
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sim::SimRng;
+    use rand::Rng;
+
+    /// Input of a scripted player, changing every few tenths of a second.
+    fn script(side: Side, tick: u32) -> f32 {
+        match side {
+            Side::Left => ((tick / 37) % 3) as f32 - 1.0,
+            Side::Right => ((tick / 53) as f32).sin(),
+            Side::Top | Side::Bottom => 0.0,
+        }
+    }
+
+    /// A one way link with latency, jitter and packet loss, in frames.
+    struct Link {
+        latency: u32,
+        jitter: u32,
+        loss: f64,
+        rng: SimRng,
+        in_flight: Vec<(u32, u32, Vec<f32>)>,
+    }
+
+    impl Link {
+        fn new(latency: u32, jitter: u32, loss: f64, seed: u64) -> Link {
+            Link {
+                latency,
+                jitter,
+                loss,
+                rng: SimRng::new(seed),
+                in_flight: Vec::new(),
+            }
+        }
+
+        fn send(&mut self, now: u32, (tick, axes): (u32, Vec<f32>)) {
+            if self.rng.gen_bool(self.loss) {
+                return;
+            }
+            let arrival = now + self.latency + self.rng.gen_range(0, self.jitter + 1);
+            self.in_flight.push((arrival, tick, axes));
+        }
+
+        /// Hands the packets arrived by `now` to `peer`, in any order, and
+        /// returns what they changed.
+        fn deliver(&mut self, now: u32, peer: &mut Rollback) -> Vec<SimEvent> {
+            let (arrived, in_flight): (Vec<_>, Vec<_>) = self
+                .in_flight
+                .drain(..)
+                .partition(|(arrival, _, _)| *arrival <= now);
+            self.in_flight = in_flight;
+            arrived
+                .into_iter()
+                .flat_map(|(_, tick, axes)| peer.receive(tick, &axes))
+                .collect()
+        }
+    }
+
+    /// Plays `ticks` ticks on two peers over the links, one tick a frame when
+    /// the peers can advance, and returns both once everything is confirmed,
+    /// with the goals each one told.
+    fn play(
+        ticks: u32,
+        mut to_right: Link,
+        mut to_left: Link,
+    ) -> (Rollback, Rollback, [Vec<SimEvent>; 2]) {
+        let rules = GameRules::default();
+        let mut left = Rollback::new(Side::Left, rules.clone());
+        let mut right = Rollback::new(Side::Right, rules);
+
+        let mut told = [Vec::new(), Vec::new()];
+        for frame in 0..ticks * 10 {
+            for (peer, told) in [&mut left, &mut right].iter_mut().zip(told.iter_mut()) {
+                if peer.tick() < ticks && peer.can_advance() {
+                    let axis = script(peer.local_side(), peer.tick());
+                    told.extend(peer.advance(axis));
+                }
+            }
+            // Inputs are sent every frame, even while waiting, so the lost
+            // ones are covered.
+            to_right.send(frame, left.local_inputs());
+            to_left.send(frame, right.local_inputs());
+            told[1].extend(to_right.deliver(frame, &mut right));
+            told[0].extend(to_left.deliver(frame, &mut left));
+
+            if [&left, &right]
+                .iter()
+                .all(|peer| peer.tick() == ticks && peer.confirmed() >= ticks)
+            {
+                let goals = told.map(|told| {
+                    told.into_iter()
+                        .filter(|event| matches!(event, SimEvent::Goal { .. }))
+                        .collect()
+                });
+                return (left, right, goals);
+            }
+        }
+        panic!("the peers never caught up");
+    }
+
+    /// The match both peers should end up with.
+    fn expected(ticks: u32) -> Simulation {
+        let mut sim = Simulation::new(GameRules::default());
+        for tick in 0..ticks {
+            let mut inputs = Inputs::default();
+            if let Some(read) = tick.checked_sub(INPUT_DELAY) {
+                inputs.left = script(Side::Left, read);
+                inputs.right = script(Side::Right, read);
+            }
+            sim.step(TICK, &inputs);
+        }
+        sim
+    }
+
+    #[test]
+    fn peers_agree_without_latency() {
+        let (left, right, _) = play(600, Link::new(0, 0, 0.0, 1), Link::new(0, 0, 0.0, 2));
+        assert_eq!(left.simulation(), right.simulation());
+        assert_eq!(*left.simulation(), expected(600));
+        assert_eq!(left.rollbacks() + right.rollbacks(), 0);
+    }
+
+    #[test]
+    fn peers_converge_despite_latency_and_jitter() {
+        // Around 50 ms each way with 25 ms of jitter, which reorders packets.
+        let (left, right, _) = play(1200, Link::new(6, 3, 0.0, 3), Link::new(5, 4, 0.0, 4));
+        assert!(left.rollbacks() > 0 && right.rollbacks() > 0);
+        assert_eq!(left.simulation(), right.simulation());
+        assert_eq!(*left.simulation(), expected(1200));
+    }
+
+    #[test]
+    fn peers_converge_despite_lost_packets() {
+        let (left, right, _) = play(1200, Link::new(4, 8, 0.2, 5), Link::new(9, 2, 0.3, 6));
+        assert_eq!(left.simulation(), right.simulation());
+        assert_eq!(*left.simulation(), expected(1200));
+    }
+
+    #[test]
+    fn rollbacks_tell_every_goal_once() {
+        let ticks = 7200;
+        let (left, right, goals) = play(ticks, Link::new(6, 3, 0.1, 7), Link::new(5, 4, 0.1, 8));
+        assert!(left.rollbacks() > 0 && right.rollbacks() > 0);
+        let score = expected(ticks).score;
+        let scored = score.points.iter().sum::<i32>() as usize;
+        assert!(scored > 0 && score.sets == [0; 4]);
+        for goals in goals.iter() {
+            assert_eq!(goals.len(), scored);
+            for side in [Side::Left, Side::Right].iter() {
+                let points = goals
+                    .iter()
+                    .filter(|goal| matches!(goal, SimEvent::Goal { scorer, .. } if scorer == side))
+                    .count();
+                assert_eq!(points as i32, score.points(*side));
+            }
+        }
+    }
+
+    #[test]
+    fn prediction_waits_for_a_silent_peer() {
+        let mut peer = Rollback::new(Side::Left, GameRules::default());
+        while peer.can_advance() {
+            peer.advance(1.0);
+        }
+        assert_eq!(peer.tick(), INPUT_DELAY + MAX_PREDICTION);
+
+        peer.receive(INPUT_DELAY, &[0.0]);
+        assert!(peer.can_advance());
+        assert_eq!(peer.confirmed(), INPUT_DELAY + 1);
+    }
+
+    #[test]
+    fn wrong_prediction_is_played_again() {
+        let rules = GameRules::default();
+        let mut peer = Rollback::new(Side::Left, rules.clone());
+        for _ in 0..10 {
+            peer.advance(0.0);
+        }
+        let predicted = peer.simulation().paddle(Side::Right).unwrap().position;
+
+        // The other player pushed up from the first tick they could.
+        let axes = vec![1.0; (10 - INPUT_DELAY) as usize];
+        peer.receive(9, &axes);
+        assert_eq!(peer.rollbacks(), 1);
+        assert_eq!(peer.confirmed(), 10);
+        let corrected = peer.simulation().paddle(Side::Right).unwrap().position;
+        assert!(corrected[1] > predicted[1]);
+    }
+
+    #[test]
+    fn match_over_waits_for_the_confirmed_ticks() {
+        let rules = GameRules {
+            points_to_win: 1,
+            win_by_two: false,
+            best_of_sets: 1,
+            ..GameRules::default()
+        };
+        let mut peer = Rollback::new(Side::Left, rules);
+        let mut predicted_over = None;
+        let mut told = Vec::new();
+        for _ in 0..20_000 {
+            if peer.can_advance() {
+                told.extend(peer.advance(0.0));
+            }
+            if predicted_over.is_none() && peer.simulation().score.winner.is_some() {
+                predicted_over = Some(peer.tick());
+            }
+            // The other peer stays still, a few ticks late.
+            if let Some(tick) = peer.tick().checked_sub(6) {
+                told.extend(peer.receive(tick, &[0.0]));
+            }
+            if told
+                .iter()
+                .any(|event| matches!(event, SimEvent::MatchOver { .. }))
+            {
+                break;
+            }
+        }
+
+        let over = predicted_over.expect("a point was scored");
+        assert!(peer.confirmed() >= over);
+        let told_over = told
+            .iter()
+            .filter(|event| matches!(event, SimEvent::MatchOver { .. }))
+            .count();
+        assert_eq!(told_over, 1);
+        told.clear();
+        for _ in 0..10 {
+            told.extend(peer.advance(0.0));
+            let tick = peer.tick() - 1;
+            told.extend(peer.receive(tick, &[0.0]));
+        }
+        assert!(told.is_empty());
+    }
+}
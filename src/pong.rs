@@ -1,247 +1,576 @@
 use amethyst::{
     assets::{AssetStorage, Handle, Loader},
-    core::transform::Transform,
-    ecs::prelude::{Component, DenseVecStorage, Entity},
+    core::{
+        math::Vector3,
+        shrev::{EventChannel, ReaderId},
+        transform::Transform,
+        Hidden,
+    },
+    ecs::prelude::{Component, DenseVecStorage, Entity, EntityBuilder},
+    input::{is_close_requested, is_key_down, VirtualKeyCode},
     prelude::*,
     renderer::{palette::Srgba, resources::Tint},
     renderer::{Camera, ImageFormat, SpriteRender, SpriteSheet, SpriteSheetFormat, Texture},
     ui::{Anchor, TtfFormat, UiText, UiTransform},
 };
 
-use crate::audio::initialise_audio;
+use crate::ai::{AiConfig, AiController};
+use crate::bot::Bot;
+use crate::controls::{ControlConfig, InputSource};
+use crate::level::{Level, LevelRotation};
+use crate::net::{Host, NetSession};
+use crate::replay::{Playback, RecordPath, Recording, ReplayCheck};
+use crate::rules::{GameRules, MAX_BALLS};
+use crate::sim::{
+    Ball, Inputs, Paddle, PowerUpKind, Replay, Side, SimEvent, SimRng, Simulation, SHIELD_WIDTH,
+};
+use crate::states::{
+    create_label, delete_entities, is_action, load_font, GameOver, PauseMenu, RunState, BACK, PAUSE,
+};
 
 pub struct PongGame {
     sprite_sheet_handle: Option<Handle<SpriteSheet>>,
+    /// Every entity of the match, deleted when the match ends.
+    entities: Vec<Entity>,
+    event_reader: Option<ReaderId<GameEvent>>,
+    /// Replay to play instead of a live match, moved into a `Playback` on start.
+    replay: Option<Replay>,
+    replaying: bool,
 }
 
 const Z_FRONT: f32 = 1.0;
 const Z_BACK: f32 = 0.0;
 
-pub const ARENA_WIDTH: f32 = 100.0;
-pub const ARENA_HEIGHT: f32 = 100.0;
-pub const HALVE_WIDTH: f32 = ARENA_WIDTH * 0.5;
-pub const HALVE_HEIGHT: f32 = ARENA_HEIGHT * 0.5;
 const CAMERA_Z: f32 = Z_FRONT;
 
-const PADDLE_WIDTH: f32 = 4.0;
-const PADDLE_HEIGHT: f32 = 16.0;
-
-const PADDLE_PIVOT_X: f32 = PADDLE_WIDTH * 0.5;
-const PADDLE_LEFT_X: f32 = PADDLE_PIVOT_X;
-const PADDLE_RIGHT_X: f32 = ARENA_WIDTH - PADDLE_PIVOT_X;
-const PADDLE_INITIAL_Y: f32 = HALVE_HEIGHT;
 const PADDLE_Z: f32 = Z_BACK;
 const PADDLE_SPRITE_NUM: usize = 0;
+pub const PADDLE_SPRITE_WIDTH: f32 = 4.0;
+pub const PADDLE_SPRITE_HEIGHT: f32 = 16.0;
 
-const BALL_RADIUS: f32 = 2.0;
-const BALL_VELOCITY_X: f32 = 30.0;
-const BALL_VELOCITY_Y: f32 = 15.0;
-const BALL_ACCELERATION: f32 = 0.2;
-const MAX_BALL_VELOCITY_X: f32 = BALL_VELOCITY_X * 2.0;
-const MAX_BALL_VELOCITY_Y: f32 = BALL_VELOCITY_Y * 2.0;
-pub const BALL_Z: f32 = Z_BACK;
+const BALL_Z: f32 = Z_BACK;
 const BALL_SPRITE_NUM: usize = 1;
+const BALL_SPRITE_SIZE: f32 = 4.0;
+/// Colour of every ball in multiball matches, in order.
+const BALL_COLORS: [[f32; 3]; 6] = [
+    [1.0, 1.0, 1.0],
+    [1.0, 0.85, 0.2],
+    [0.3, 0.8, 1.0],
+    [0.45, 1.0, 0.45],
+    [1.0, 0.45, 0.9],
+    [1.0, 0.6, 0.25],
+];
+
+const LEVEL_Z: f32 = Z_BACK;
+const OBSTACLE_SPRITE_NUM: usize = 2;
+const PORTAL_SPRITE_NUM: usize = 3;
+const BUMPER_SPRITE_NUM: usize = 4;
+/// Size of the obstacle, portal and bumper sprites.
+const LEVEL_SPRITE_SIZE: f32 = 4.0;
+/// Colour of both ends of every portal, in order.
+const PORTAL_COLORS: [[f32; 3]; 4] = [
+    [0.3, 0.8, 1.0],
+    [1.0, 0.6, 0.25],
+    [0.45, 1.0, 0.45],
+    [1.0, 0.45, 0.9],
+];
 
 const GAME_SPRITE_SHEET_TEXTURE: &str = "texture/pong_spritesheet.png";
 const GAME_SPRITE_SHEET_RON: &str = "texture/pong_spritesheet.ron";
 
-fn initialise_camera(world: &mut World) {
+fn initialise_camera(world: &mut World, rules: &GameRules) -> Entity {
     // Setup camera in a way that our screen covers whole arena and (0, 0) is in the bottom left.
     let mut transform = Transform::default();
-    transform.set_translation_xyz(HALVE_WIDTH, HALVE_HEIGHT, CAMERA_Z);
+    transform.set_translation_xyz(rules.halve_width(), rules.halve_height(), CAMERA_Z);
 
     world
         .create_entity()
-        .with(Camera::standard_2d(ARENA_WIDTH, ARENA_HEIGHT))
+        .with(Camera::standard_2d(rules.arena_width, rules.arena_height))
         .with(transform)
-        .build();
+        .build()
 }
 
-#[derive(PartialEq, Eq)]
-pub enum Side {
-    Left,
-    Right,
+/// PaddleSprite links a rendered paddle to the paddle of the same side in
+/// the `Simulation`.
+pub struct PaddleSprite {
+    pub side: Side,
 }
 
-pub struct Paddle {
-    pub side: Side,
-    pub width: f32,
-    pub height: f32,
+impl Component for PaddleSprite {
+    type Storage = DenseVecStorage<Self>;
 }
 
-impl Paddle {
-    fn new(side: Side) -> Paddle {
-        Paddle {
-            side,
-            width: PADDLE_WIDTH,
-            height: PADDLE_HEIGHT,
-        }
-    }
+/// BallSprite links a rendered ball to the ball at `index` in the `Simulation`.
+pub struct BallSprite {
+    pub index: usize,
 }
 
-impl Component for Paddle {
+impl Component for BallSprite {
     type Storage = DenseVecStorage<Self>;
 }
 
-#[derive(PartialEq, Eq)]
-pub enum BallState {
-    Waiting,
-    Moving,
+/// PowerUpSprite shows the power-up at `index` in the `Simulation`, it is
+/// hidden while there is none.
+pub struct PowerUpSprite {
+    pub index: usize,
 }
 
-pub struct Ball {
-    pub velocity: [f32; 2],
-    pub acceleration: f32,
-    pub radius: f32,
-    pub state: BallState,
-    pub waiting_time: f32,
+impl Component for PowerUpSprite {
+    type Storage = DenseVecStorage<Self>;
 }
 
-impl Ball {
-    pub fn wait(&mut self) {
-        self.state = BallState::Waiting;
-        self.waiting_time = 2.0;
-        self.velocity = [BALL_VELOCITY_X, BALL_VELOCITY_Y]
-    }
-    pub fn accelerate(&mut self) {
-        let velocity_x =
-            (self.velocity[0] + (self.velocity[0] * self.acceleration)).min(MAX_BALL_VELOCITY_X);
-        let velocity_y =
-            (self.velocity[1] + (self.velocity[1] * self.acceleration)).min(MAX_BALL_VELOCITY_Y);
-        self.velocity = [velocity_x, velocity_y]
-    }
+/// ShieldSprite shows the shield in front of the goal of `side` while it is up.
+pub struct ShieldSprite {
+    pub side: Side,
 }
 
-impl Component for Ball {
+impl Component for ShieldSprite {
     type Storage = DenseVecStorage<Self>;
 }
 
-/// ScoreBoard contains the actual score data
-#[derive(Default)]
-pub struct ScoreBoard {
-    pub score_left: i32,
-    pub score_right: i32,
+/// ObstacleSprite shows the obstacle at `index` in the level of the
+/// `Simulation`, it follows the obstacle when it moves.
+pub struct ObstacleSprite {
+    pub index: usize,
 }
 
-/// ScoreText contains the ui text components that display the score
-pub struct ScoreText {
-    pub p1_score: Entity,
-    pub p2_score: Entity,
+impl Component for ObstacleSprite {
+    type Storage = DenseVecStorage<Self>;
+}
+
+/// MatchSeed seeds the `SimRng`s of every match. Without a seed, each match
+/// gets a random one.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct MatchSeed(pub Option<u64>);
+
+/// GameEvent is what happened during a match, sent by the `SimulationSystem`
+/// on an `EventChannel` after every tick. The sounds, the score, the effects
+/// and the statistics of the match follow it with readers of their own.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GameEvent {
+    /// A ball bounced on a wall of the arena that is not a goal.
+    WallBounce { ball: usize },
+    /// A ball was returned by the paddle of `side`, it left at `speed` units
+    /// per second.
+    PaddleHit { ball: usize, side: Side, speed: f32 },
+    /// Two balls bounced on each other.
+    BallHit { ball: usize },
+    /// A ball bounced on an obstacle or a bumper of the level, or went
+    /// through a portal.
+    LevelHit { ball: usize },
+    /// A ball left the arena and `scorer` got the point.
+    Goal { ball: usize, scorer: Side },
+    /// A ball waiting in the middle of the arena was served.
+    ServeStart { ball: usize },
+    /// `winner` won the match.
+    MatchOver { winner: Side },
 }
 
-/// Initialises one paddle on the left, and one paddle on the right.
-fn initialise_paddles(world: &mut World, sprite_sheet_handle: Handle<SpriteSheet>) {
-    let mut left_transform = Transform::default();
-    let mut right_transform = Transform::default();
+impl GameEvent {
+    /// The event of `event`, played during the tick that led to `sim`. The
+    /// power-ups are shown from the simulation itself, they have none.
+    pub fn from_sim(event: &SimEvent, sim: &Simulation) -> Option<GameEvent> {
+        Some(match *event {
+            SimEvent::WallBounce { ball } => GameEvent::WallBounce { ball },
+            SimEvent::PaddleHit { ball, side } => {
+                let speed = sim.balls.get(ball).map_or(0.0, |ball| {
+                    let [x, y] = ball.velocity;
+                    (x * x + y * y).sqrt()
+                });
+                GameEvent::PaddleHit { ball, side, speed }
+            }
+            SimEvent::BallHit { ball, .. } => GameEvent::BallHit { ball },
+            SimEvent::ObstacleHit { ball }
+            | SimEvent::BumperHit { ball }
+            | SimEvent::PortalUsed { ball } => GameEvent::LevelHit { ball },
+            SimEvent::Goal { ball, scorer } => GameEvent::Goal { ball, scorer },
+            SimEvent::Serve { ball } => GameEvent::ServeStart { ball },
+            SimEvent::MatchOver { winner } => GameEvent::MatchOver { winner },
+            SimEvent::PowerUpSpawned { .. }
+            | SimEvent::PowerUpCollected { .. }
+            | SimEvent::PowerUpExpired { .. } => return None,
+        })
+    }
+}
 
-    // Correctly position the paddles.
-    left_transform.set_translation_xyz(PADDLE_LEFT_X, PADDLE_INITIAL_Y, PADDLE_Z);
-    right_transform.set_translation_xyz(PADDLE_RIGHT_X, PADDLE_INITIAL_Y, PADDLE_Z);
+/// MatchStats follows the rallies of the match for the game over screen, it
+/// is kept up to date by the `StatsSystem`.
+#[derive(Clone, Debug, Default)]
+pub struct MatchStats {
+    /// Paddle hits of the rally of every ball, since its last serve.
+    pub rallies: Vec<u32>,
+    pub longest_rally: u32,
+    /// Speed of the fastest ball returned by a paddle, in units per second.
+    pub fastest_hit: f32,
+    pub paddle_hits: u32,
+}
+
+impl MatchStats {
+    /// Starts a new rally for `ball`.
+    pub fn serve(&mut self, ball: usize) {
+        *self.rally(ball) = 0;
+    }
 
+    /// Counts a paddle hit of `ball`, returned at `speed`.
+    pub fn hit(&mut self, ball: usize, speed: f32) {
+        let rally = self.rally(ball);
+        *rally += 1;
+        let rally = *rally;
+        self.longest_rally = self.longest_rally.max(rally);
+        self.fastest_hit = self.fastest_hit.max(speed);
+        self.paddle_hits += 1;
+    }
+
+    fn rally(&mut self, ball: usize) -> &mut u32 {
+        if self.rallies.len() <= ball {
+            self.rallies.resize(ball + 1, 0);
+        }
+        &mut self.rallies[ball]
+    }
+}
+
+/// ScoreText contains the ui text components that display the score of
+/// every player
+pub struct ScoreText {
+    pub points: Vec<(Side, Entity)>,
+    pub sets: Vec<(Side, Entity)>,
+}
+
+/// Initialises a sprite for every paddle of the simulation. Paddles chosen in
+/// the `AiConfig` are played by the computer, and the bots of `controls` are
+/// started for the others.
+fn initialise_paddles(
+    world: &mut World,
+    sprite_sheet_handle: Handle<SpriteSheet>,
+    paddles: &[Paddle],
+    ai: &AiConfig,
+    controls: Option<&ControlConfig>,
+) -> Vec<Entity> {
     // Assign the sprites for the paddles
     let sprite_render = SpriteRender {
         sprite_sheet: sprite_sheet_handle,
         sprite_number: PADDLE_SPRITE_NUM,
     };
 
-    // Create a left plank entity.
-    world
-        .create_entity()
-        .with(sprite_render.clone())
-        .with(Paddle::new(Side::Left))
-        .with(left_transform)
-        .build();
-
-    // Create right plank entity.
-    world
-        .create_entity()
-        .with(sprite_render)
-        .with(Paddle::new(Side::Right))
-        .with(right_transform)
-        .build();
+    paddles
+        .iter()
+        .map(|paddle| {
+            let mut transform = Transform::default();
+            transform.set_translation_xyz(paddle.position[0], paddle.position[1], PADDLE_Z);
+            // Stretch the sprite to the paddle size set in the rules.
+            transform.set_scale(Vector3::new(
+                paddle.width / PADDLE_SPRITE_WIDTH,
+                paddle.height / PADDLE_SPRITE_HEIGHT,
+                1.0,
+            ));
+
+            let mut entity = world
+                .create_entity()
+                .with(sprite_render.clone())
+                .with(PaddleSprite { side: paddle.side })
+                .with(transform);
+            if let Some(difficulty) = ai.get(paddle.side) {
+                let initial = paddle.position[paddle.side.axis()];
+                entity = entity.with(AiController::new(difficulty, initial));
+            } else if let Some((InputSource::Bot(spec), controls)) =
+                controls.map(|controls| (controls.get(paddle.side), controls))
+            {
+                // A bot that does not start leaves its paddle still.
+                match Bot::start(spec, controls.bot_policy()) {
+                    Ok(bot) => entity = entity.with(bot),
+                    Err(e) => eprintln!(
+                        "could not start the bot of the {:?} paddle `{}`: {}",
+                        paddle.side, spec, e
+                    ),
+                }
+            }
+            entity.build()
+        })
+        .collect()
 }
 
-/// Initialises one ball in the middle-ish of the arena.
-fn initialise_ball(world: &mut World, sprite_sheet_handle: Handle<SpriteSheet>) {
-    // Create the translation.
-    let mut local_transform = Transform::default();
-    local_transform.set_translation_xyz(HALVE_WIDTH, HALVE_HEIGHT, BALL_Z);
-
+/// Initialises a sprite for every ball of the simulation, and hidden ones for
+/// the balls a split can add.
+fn initialise_balls(
+    world: &mut World,
+    sprite_sheet_handle: Handle<SpriteSheet>,
+    balls: &[Ball],
+    rules: &GameRules,
+) -> Vec<Entity> {
     // Assign the sprite for the ball
     let sprite_render = SpriteRender {
         sprite_sheet: sprite_sheet_handle,
         sprite_number: BALL_SPRITE_NUM,
     };
 
-    // White shows the sprite as normal.
-    // You can change the color at any point to modify the sprite's tint.
-    let tint = Tint(Srgba::new(1.0, 1.0, 1.0, 1.0));
-    let mut cycling = CyclingColor::new(Srgba::new(1.0, 0.0, 0.0, 1.0), 0.5);
-    cycling.start();
+    let count = if rules.powerups.enabled.contains(&PowerUpKind::SplitBall) {
+        balls.len().max(MAX_BALLS as usize)
+    } else {
+        balls.len()
+    };
+    (0..count)
+        .map(|index| {
+            let ball = balls
+                .get(index)
+                .cloned()
+                .unwrap_or_else(|| Ball::new(rules));
+            let mut transform = Transform::default();
+            transform.set_translation_xyz(ball.position[0], ball.position[1], BALL_Z);
+            let scale = ball.radius * 2.0 / BALL_SPRITE_SIZE;
+            transform.set_scale(Vector3::new(scale, scale, 1.0));
+
+            // White shows the sprite as normal, the other balls get a colour
+            // of their own so they can be told apart.
+            let [red, green, blue] = BALL_COLORS[index % BALL_COLORS.len()];
+            let color = Srgba::new(red, green, blue, 1.0);
+            let tint = Tint(color);
+            let mut cycling = CyclingColor::new(color, Srgba::new(1.0, 0.0, 0.0, 1.0), 0.5);
+            cycling.start();
+
+            let mut entity = world
+                .create_entity()
+                .with(sprite_render.clone())
+                .with(BallSprite { index })
+                .with(transform)
+                .with(tint)
+                .with(cycling);
+            if index >= balls.len() {
+                entity = entity.with(Hidden);
+            }
+            entity.build()
+        })
+        .collect()
+}
+
+/// Initialises hidden sprites for the power-ups and the shields, shown by the
+/// `PowerUpSystem` when they are in the match.
+fn initialise_powerups(
+    world: &mut World,
+    sprite_sheet_handle: Handle<SpriteSheet>,
+    rules: &GameRules,
+) -> Vec<Entity> {
+    let mut entities = Vec::new();
+    if rules.powerups.enabled.is_empty() {
+        return entities;
+    }
+
+    let ball_render = SpriteRender {
+        sprite_sheet: sprite_sheet_handle.clone(),
+        sprite_number: BALL_SPRITE_NUM,
+    };
+    for index in 0..rules.powerups.max_pickups as usize {
+        let mut transform = Transform::default();
+        transform.set_translation_xyz(rules.halve_width(), rules.halve_height(), BALL_Z);
+        let scale = rules.powerups.radius * 2.0 / BALL_SPRITE_SIZE;
+        transform.set_scale(Vector3::new(scale, scale, 1.0));
+        entities.push(
+            world
+                .create_entity()
+                .with(ball_render.clone())
+                .with(PowerUpSprite { index })
+                .with(transform)
+                .with(Tint(Srgba::new(1.0, 1.0, 1.0, 1.0)))
+                .with(Hidden)
+                .build(),
+        );
+    }
+
+    // A shield is a paddle sprite stretched over the whole goal.
+    let paddle_render = SpriteRender {
+        sprite_sheet: sprite_sheet_handle,
+        sprite_number: PADDLE_SPRITE_NUM,
+    };
+    for &side in rules.sides() {
+        let (width, height) = (rules.arena_width, rules.arena_height);
+        let half = SHIELD_WIDTH * 0.5;
+        let (position, size) = match side {
+            Side::Left => ([half, rules.halve_height()], [SHIELD_WIDTH, height]),
+            Side::Right => ([width - half, rules.halve_height()], [SHIELD_WIDTH, height]),
+            Side::Top => ([rules.halve_width(), height - half], [width, SHIELD_WIDTH]),
+            Side::Bottom => ([rules.halve_width(), half], [width, SHIELD_WIDTH]),
+        };
+        let mut transform = Transform::default();
+        transform.set_translation_xyz(position[0], position[1], PADDLE_Z);
+        transform.set_scale(Vector3::new(
+            size[0] / PADDLE_SPRITE_WIDTH,
+            size[1] / PADDLE_SPRITE_HEIGHT,
+            1.0,
+        ));
+        entities.push(
+            world
+                .create_entity()
+                .with(paddle_render.clone())
+                .with(ShieldSprite { side })
+                .with(transform)
+                .with(Tint(Srgba::new(0.3, 0.8, 1.0, 0.6)))
+                .with(Hidden)
+                .build(),
+        );
+    }
+    entities
+}
+
+/// Initialises the sprites of the obstacles, portals and bumpers of `level`.
+fn initialise_level(
+    world: &mut World,
+    sprite_sheet_handle: Handle<SpriteSheet>,
+    level: &Level,
+) -> Vec<Entity> {
+    let mut entities = Vec::new();
+    for (index, obstacle) in level.obstacles.iter().enumerate() {
+        entities.push(
+            level_sprite(
+                world,
+                sprite_sheet_handle.clone(),
+                OBSTACLE_SPRITE_NUM,
+                obstacle.position,
+                obstacle.size,
+            )
+            .with(ObstacleSprite { index })
+            .build(),
+        );
+    }
+    // Both ends of a portal have the same colour.
+    for (portal, color) in level.portals.iter().zip(PORTAL_COLORS.iter().cycle()) {
+        let size = portal.radius * 2.0;
+        for end in portal.ends.iter() {
+            entities.push(
+                level_sprite(
+                    world,
+                    sprite_sheet_handle.clone(),
+                    PORTAL_SPRITE_NUM,
+                    *end,
+                    [size, size],
+                )
+                .with(Tint(Srgba::new(color[0], color[1], color[2], 1.0)))
+                .build(),
+            );
+        }
+    }
+    for bumper in level.bumpers.iter() {
+        let size = bumper.radius * 2.0;
+        entities.push(
+            level_sprite(
+                world,
+                sprite_sheet_handle.clone(),
+                BUMPER_SPRITE_NUM,
+                bumper.position,
+                [size, size],
+            )
+            .build(),
+        );
+    }
+    entities
+}
 
+/// Starts an entity showing a level sprite stretched to `size`.
+fn level_sprite(
+    world: &mut World,
+    sprite_sheet: Handle<SpriteSheet>,
+    sprite_number: usize,
+    position: [f32; 2],
+    size: [f32; 2],
+) -> EntityBuilder<'_> {
+    let mut transform = Transform::default();
+    transform.set_translation_xyz(position[0], position[1], LEVEL_Z);
+    transform.set_scale(Vector3::new(
+        size[0] / LEVEL_SPRITE_SIZE,
+        size[1] / LEVEL_SPRITE_SIZE,
+        1.0,
+    ));
     world
         .create_entity()
-        .with(sprite_render)
-        .with(Ball {
-            radius: BALL_RADIUS,
-            velocity: [BALL_VELOCITY_X, BALL_VELOCITY_Y],
-            acceleration: BALL_ACCELERATION,
-            state: BallState::Waiting,
-            waiting_time: 2.0,
+        .with(SpriteRender {
+            sprite_sheet,
+            sprite_number,
         })
-        .with(local_transform)
-        .with(tint)
-        .with(cycling)
-        .build();
+        .with(transform)
+}
+
+/// Where the score of `side` is shown: side by side at the top of the screen
+/// with two players, next to the goal of each player with four.
+fn score_position(side: Side, players: i32) -> (Anchor, f32, f32) {
+    match (side, players) {
+        (Side::Left, 2) => (Anchor::TopMiddle, -50., -50.),
+        (Side::Right, 2) => (Anchor::TopMiddle, 50., -50.),
+        (Side::Left, _) => (Anchor::MiddleLeft, 80., 0.),
+        (Side::Right, _) => (Anchor::MiddleRight, -80., 0.),
+        (Side::Top, _) => (Anchor::TopMiddle, 0., -50.),
+        (Side::Bottom, _) => (Anchor::BottomMiddle, 0., 100.),
+    }
 }
 
 /// Initialises a ui scoreboard
-fn initialise_scoreboard(world: &mut World) {
+fn initialise_scoreboard(world: &mut World, rules: &GameRules) -> Vec<Entity> {
     let font = world.read_resource::<Loader>().load(
         "font/square.ttf",
         TtfFormat,
         (),
         &world.read_resource(),
     );
-    let p1_transform = UiTransform::new(
-        "P1".to_string(),
-        Anchor::TopMiddle,
-        Anchor::TopMiddle,
-        -50.,
-        -50.,
-        1.,
-        200.,
-        50.,
-    );
-    let p2_transform = UiTransform::new(
-        "P2".to_string(),
-        Anchor::TopMiddle,
-        Anchor::TopMiddle,
-        50.,
-        -50.,
-        1.,
-        200.,
-        50.,
-    );
 
-    let p1_score = world
-        .create_entity()
-        .with(p1_transform)
-        .with(UiText::new(
-            font.clone(),
-            "0".to_string(),
-            [1., 1., 1., 1.],
+    // The won sets are shown under the points, only when more than one set is played.
+    let sets_text = if rules.best_of_sets > 1 { "0" } else { "" };
+    let mut points = Vec::new();
+    let mut sets = Vec::new();
+    for &side in rules.sides() {
+        let (anchor, x, y) = score_position(side, rules.players);
+        let points_transform = UiTransform::new(
+            format!("{:?}", side),
+            anchor,
+            Anchor::TopMiddle,
+            x,
+            y,
+            1.,
+            200.,
             50.,
-        ))
-        .build();
-
-    let p2_score = world
-        .create_entity()
-        .with(p2_transform)
-        .with(UiText::new(font, "0".to_string(), [1., 1., 1., 1.], 50.))
-        .build();
+        );
+        let sets_transform = UiTransform::new(
+            format!("{:?}_sets", side),
+            anchor,
+            Anchor::TopMiddle,
+            x,
+            y - 50.,
+            1.,
+            200.,
+            25.,
+        );
+
+        points.push((
+            side,
+            world
+                .create_entity()
+                .with(points_transform)
+                .with(UiText::new(
+                    font.clone(),
+                    "0".to_string(),
+                    [1., 1., 1., 1.],
+                    50.,
+                ))
+                .build(),
+        ));
+        sets.push((
+            side,
+            world
+                .create_entity()
+                .with(sets_transform)
+                .with(UiText::new(
+                    font.clone(),
+                    sets_text.to_string(),
+                    [0.6, 0.6, 0.6, 1.],
+                    25.,
+                ))
+                .build(),
+        ));
+    }
 
-    world.insert(ScoreText { p1_score, p2_score });
+    let entities = points
+        .iter()
+        .chain(sets.iter())
+        .map(|(_, entity)| *entity)
+        .collect();
+    world.insert(ScoreText { points, sets });
+    entities
 }
 
 fn load_sprite_sheet(world: &mut World) -> Handle<SpriteSheet> {
@@ -282,6 +611,8 @@ pub enum CyclingState {
 pub struct CyclingColor {
     pub state: CyclingState,
     pub cycle_time: f32,
+    /// Colour shown while not cycling.
+    pub base: Srgba,
     pub color: Srgba,
     pub from: Srgba,
     pub to: Srgba,
@@ -293,11 +624,12 @@ impl Component for CyclingColor {
 }
 
 impl CyclingColor {
-    fn new(to: Srgba, cycle_time: f32) -> CyclingColor {
+    fn new(base: Srgba, to: Srgba, cycle_time: f32) -> CyclingColor {
         CyclingColor {
             state: CyclingState::Stopped,
+            base,
             color: to,
-            from: Srgba::new(1.0, 1.0, 1.0, 1.0),
+            from: base,
             to,
             cycle_time: cycle_time / 2.0,
             current_cycle: 0.0,
@@ -306,11 +638,12 @@ impl CyclingColor {
 
     pub fn stop(&mut self) {
         self.state = CyclingState::Stopped;
+        self.from = self.base;
     }
 
     pub fn start(&mut self) {
         self.state = CyclingState::Cycling;
-        self.from = Srgba::new(1.0, 1.0, 1.0, 1.0);
+        self.from = self.base;
         self.to = self.color;
         self.current_cycle = 0.0;
     }
@@ -320,6 +653,19 @@ impl PongGame {
     pub(crate) fn new() -> PongGame {
         PongGame {
             sprite_sheet_handle: None,
+            entities: Vec::new(),
+            event_reader: None,
+            replay: None,
+            replaying: false,
+        }
+    }
+
+    /// Plays a recorded match instead of a live one. Space pauses it, F
+    /// fast-forwards it and Escape stops it.
+    pub(crate) fn replay(replay: Replay) -> PongGame {
+        PongGame {
+            replay: Some(replay),
+            ..PongGame::new()
         }
     }
 }
@@ -327,14 +673,201 @@ impl PongGame {
 impl SimpleState for PongGame {
     fn on_start(&mut self, data: StateData<'_, GameData<'_, '_>>) {
         let world = data.world;
+        let playback = self.replay.take().map(Playback::new);
+        self.replaying = playback.is_some();
+        // A replay brings its own rules and level, every paddle follows the
+        // recorded inputs.
+        let (rules, ai, seed) = match &playback {
+            Some(playback) => (
+                playback.replay.rules.clone(),
+                AiConfig::default(),
+                playback.replay.seed,
+            ),
+            None => (
+                (*world.read_resource::<GameRules>()).clone(),
+                (*world.read_resource::<AiConfig>()).clone(),
+                world
+                    .read_resource::<MatchSeed>()
+                    .0
+                    .unwrap_or_else(rand::random),
+            ),
+        };
+        // Over the network, the computer never plays the paddle of the other
+        // player, the client leaves every decision to the host and peers play
+        // their own paddle.
+        let (ai, joined) = match world.try_fetch::<NetSession>().as_deref() {
+            Some(NetSession::Host(_)) => (ai.without(Host::REMOTE_SIDE), false),
+            Some(NetSession::Client(_)) | Some(NetSession::Peer(_)) => (AiConfig::default(), true),
+            None => (ai, false),
+        };
+        // A rematch between peers starts over from the first tick, the session
+        // still holds the match that just ended.
+        if let Some(NetSession::Peer(peer)) = world.try_fetch_mut::<NetSession>().as_deref_mut() {
+            peer.start_match();
+        }
+        // Matches go through the levels in turn. Network matches are played in
+        // the empty arena, the other player may not have the same levels.
+        let level = match &playback {
+            Some(playback) => playback.replay.level.clone(),
+            None if world.has_value::<NetSession>() => Level::default(),
+            None => world.write_resource::<LevelRotation>().next_level(),
+        };
+        // Every match starts from zero.
+        let sim = Simulation::seeded(rules.clone(), seed).with_level(level.clone());
+
+        // Bots only play the paddles of this machine, a replay has none.
+        let controls = match (&playback, joined) {
+            (None, false) => world
+                .try_fetch::<ControlConfig>()
+                .map(|controls| (*controls).clone()),
+            _ => None,
+        };
 
         // Load the spritesheet necessary to render the graphics.
         self.sprite_sheet_handle.replace(load_sprite_sheet(world));
 
-        initialise_paddles(world, self.sprite_sheet_handle.clone().unwrap());
-        initialise_ball(world, self.sprite_sheet_handle.clone().unwrap());
-        initialise_camera(world);
-        initialise_scoreboard(world);
-        initialise_audio(world);
+        let sprite_sheet_handle = self.sprite_sheet_handle.clone().unwrap();
+        self.entities.extend(initialise_paddles(
+            world,
+            sprite_sheet_handle.clone(),
+            &sim.paddles,
+            &ai,
+            controls.as_ref(),
+        ));
+        self.entities.extend(initialise_balls(
+            world,
+            sprite_sheet_handle.clone(),
+            &sim.balls,
+            &rules,
+        ));
+        self.entities
+            .extend(initialise_level(world, sprite_sheet_handle.clone(), &level));
+        self.entities
+            .extend(initialise_powerups(world, sprite_sheet_handle, &rules));
+        self.entities.push(initialise_camera(world, &rules));
+        self.entities.extend(initialise_scoreboard(world, &rules));
+
+        world.insert(sim);
+        world.insert(SimRng::computer(seed));
+        world.insert(Inputs::default());
+        world.insert(MatchStats::default());
+        match playback {
+            Some(playback) => {
+                let font = load_font(world);
+                self.entities.push(create_label(
+                    world,
+                    font,
+                    "replay",
+                    "REPLAY - Space: pause, F: fast-forward, Escape: stop",
+                    -200.,
+                    15.,
+                ));
+                world.insert(playback);
+            }
+            None => {
+                let record_path = world.read_resource::<RecordPath>().0.clone();
+                // The client does not play the ticks, the host records them.
+                // Peers play them again on rollbacks, they do not record.
+                if let (Some(path), false) = (record_path, joined) {
+                    world.insert(Recording {
+                        path,
+                        replay: Replay::new(seed, rules, level),
+                    });
+                }
+            }
+        }
+        self.event_reader.replace(
+            world
+                .write_resource::<EventChannel<GameEvent>>()
+                .register_reader(),
+        );
+        *world.write_resource::<RunState>() = RunState::Running;
+    }
+
+    fn on_stop(&mut self, data: StateData<'_, GameData<'_, '_>>) {
+        let world = data.world;
+        *world.write_resource::<RunState>() = RunState::Paused;
+        delete_entities(world, &mut self.entities);
+
+        let sim = world.remove::<Simulation>();
+        if let (Some(sim), Some(playback)) = (&sim, world.remove::<Playback>()) {
+            world.insert(ReplayCheck {
+                expected: playback.replay.score.clone(),
+                actual: sim.score.clone(),
+                sides: playback.replay.rules.sides(),
+                finished: playback.is_finished(),
+            });
+        }
+        if let (Some(sim), Some(recording)) = (&sim, world.remove::<Recording>()) {
+            let path = recording.path.clone();
+            if let Err(e) = recording.save(&sim.score) {
+                eprintln!("could not save replay to {}: {}", path.display(), e);
+            }
+        }
+    }
+
+    fn on_pause(&mut self, data: StateData<'_, GameData<'_, '_>>) {
+        *data.world.write_resource::<RunState>() = RunState::Paused;
+    }
+
+    fn on_resume(&mut self, data: StateData<'_, GameData<'_, '_>>) {
+        *data.world.write_resource::<RunState>() = RunState::Running;
+    }
+
+    fn handle_event(
+        &mut self,
+        data: StateData<'_, GameData<'_, '_>>,
+        event: StateEvent,
+    ) -> SimpleTrans {
+        if let StateEvent::Window(event) = &event {
+            if is_close_requested(event) {
+                return Trans::Quit;
+            }
+            if self.replaying {
+                let mut playback = data.world.write_resource::<Playback>();
+                if is_key_down(event, VirtualKeyCode::Space) {
+                    playback.paused = !playback.paused;
+                }
+                if is_key_down(event, VirtualKeyCode::F) {
+                    playback.fast_forward = !playback.fast_forward;
+                }
+            }
+        }
+        if self.replaying {
+            if is_action(&event, BACK) {
+                return Trans::Pop;
+            }
+        } else if is_action(&event, BACK) || is_action(&event, PAUSE) {
+            return Trans::Push(Box::new(PauseMenu::default()));
+        }
+        Trans::None
+    }
+
+    fn update(&mut self, data: &mut StateData<'_, GameData<'_, '_>>) -> SimpleTrans {
+        let channel = data.world.read_resource::<EventChannel<GameEvent>>();
+        let reader = self
+            .event_reader
+            .as_mut()
+            .expect("`PongGame::on_start` registers the event reader");
+        let winner = channel
+            .read(reader)
+            .filter_map(|event| match *event {
+                GameEvent::MatchOver { winner } => Some(winner),
+                _ => None,
+            })
+            .next();
+
+        // A replay goes on until its last tick, then goes back to the viewer.
+        if self.replaying {
+            return if data.world.read_resource::<Playback>().is_finished() {
+                Trans::Pop
+            } else {
+                Trans::None
+            };
+        }
+        match winner {
+            Some(winner) => Trans::Switch(Box::new(GameOver::new(winner))),
+            None => Trans::None,
+        }
     }
 }
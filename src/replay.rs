@@ -0,0 +1,132 @@
+use std::{
+    error::Error,
+    fmt,
+    path::{Path, PathBuf},
+};
+
+use amethyst::config::{Config, ConfigError};
+
+use crate::sim::{Inputs, Replay, ReplayCursor, ScoreBoard, Side, REPLAY_VERSION};
+
+/// Speed of a replay while it is fast-forwarded.
+pub const FAST_FORWARD_SPEED: f32 = 4.0;
+
+/// Loads a replay written with `--record`.
+pub fn load_replay<P: AsRef<Path>>(path: P) -> Result<Replay, ReplayError> {
+    let replay = Replay::load(path).map_err(ReplayError::Load)?;
+    if replay.version != REPLAY_VERSION {
+        return Err(ReplayError::Version(replay.version));
+    }
+    Ok(replay)
+}
+
+/// RecordPath is where the matches are recorded, set with `--record`.
+#[derive(Clone, Debug, Default)]
+pub struct RecordPath(pub Option<PathBuf>);
+
+/// Recording is the replay of the match being played. The `SimulationSystem`
+/// records every tick in it and the `PongGame` writes it once the match ends.
+pub struct Recording {
+    pub path: PathBuf,
+    pub replay: Replay,
+}
+
+impl Recording {
+    /// Writes the replay, ending with the final score of the match.
+    pub fn save(mut self, score: &ScoreBoard) -> Result<(), ConfigError> {
+        self.replay.score = score.clone();
+        self.replay.write(&self.path)
+    }
+}
+
+/// Playback feeds the `SimulationSystem` with the inputs of a replay instead
+/// of the keyboard and the computer players.
+pub struct Playback {
+    pub replay: Replay,
+    cursor: ReplayCursor,
+    pub paused: bool,
+    pub fast_forward: bool,
+    finished: bool,
+}
+
+impl Playback {
+    pub fn new(replay: Replay) -> Playback {
+        Playback {
+            replay,
+            cursor: ReplayCursor::default(),
+            paused: false,
+            fast_forward: false,
+            finished: false,
+        }
+    }
+
+    /// Returns the inputs of the next tick, or `None` once every tick was played.
+    pub fn next_inputs(&mut self) -> Option<Inputs> {
+        let inputs = self.cursor.next(&self.replay);
+        self.finished = inputs.is_none();
+        inputs
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    /// How fast the replay goes compared to the recorded match.
+    pub fn speed(&self) -> f32 {
+        if self.paused {
+            0.0
+        } else if self.fast_forward {
+            FAST_FORWARD_SPEED
+        } else {
+            1.0
+        }
+    }
+}
+
+/// ReplayCheck compares the score at the end of a replay with the recorded one.
+#[derive(Clone, Debug)]
+pub struct ReplayCheck {
+    pub expected: ScoreBoard,
+    pub actual: ScoreBoard,
+    /// Sides played in the replay, the scores of the others are not shown.
+    pub sides: &'static [Side],
+    /// Whether every tick was played, a replay stopped early does not match.
+    pub finished: bool,
+}
+
+impl ReplayCheck {
+    pub fn matches(&self) -> bool {
+        self.finished && self.expected == self.actual
+    }
+}
+
+/// Errors that can happen while loading a replay.
+#[derive(Debug)]
+pub enum ReplayError {
+    /// The file could not be read or parsed.
+    Load(ConfigError),
+    /// The replay was recorded with another version of the format.
+    Version(u32),
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReplayError::Load(e) => write!(f, "could not load replay: {}", e),
+            ReplayError::Version(version) => write!(
+                f,
+                "replay format version {} is not supported, expected {}",
+                version, REPLAY_VERSION
+            ),
+        }
+    }
+}
+
+impl Error for ReplayError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ReplayError::Load(e) => Some(e),
+            ReplayError::Version(_) => None,
+        }
+    }
+}
@@ -0,0 +1,486 @@
+use std::{error::Error, fmt, path::Path, str::FromStr};
+
+use amethyst::config::{Config, ConfigError};
+use serde::{Deserialize, Serialize};
+
+use crate::sim::{PowerUpKind, Side};
+
+/// Larger scores would overlap on the scoreboard.
+const MAX_POINTS_TO_WIN: i32 = 99;
+/// More balls would not leave room to play in the arena, split balls included.
+pub(crate) const MAX_BALLS: i32 = 8;
+/// More power-ups would cover the middle of the arena.
+const MAX_PICKUPS: i32 = 4;
+
+/// GameRules contains every tuning value of a match. It is loaded from
+/// `config/rules.ron` at startup and inserted in the world as a resource.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct GameRules {
+    pub arena_width: f32,
+    pub arena_height: f32,
+    pub paddle_width: f32,
+    pub paddle_height: f32,
+    /// Top speed of a paddle, in units per second.
+    pub paddle_max_speed: f32,
+    /// Speed gained per second while a paddle is pushed.
+    pub paddle_acceleration: f32,
+    /// Speed lost per second once a paddle is released.
+    pub paddle_deceleration: f32,
+    pub ball_radius: f32,
+    pub ball_velocity_x: f32,
+    pub ball_velocity_y: f32,
+    pub ball_acceleration: f32,
+    pub max_ball_velocity_x: f32,
+    pub max_ball_velocity_y: f32,
+    /// Angle, in degrees, of a ball returned by the very edge of a paddle. A
+    /// ball hitting the center of a paddle is returned straight.
+    pub max_bounce_angle: f32,
+    /// Fraction of the paddle vertical speed given to the ball when it is
+    /// returned, zero turns it off.
+    pub paddle_english: f32,
+    /// Seconds the ball waits in the middle of the arena before being served.
+    pub serve_wait: f32,
+    /// Points needed to win a set.
+    pub points_to_win: i32,
+    /// Whether a set needs a lead of two points to be won (deuce).
+    pub win_by_two: bool,
+    /// Number of sets of the match, the first player to win most of them wins.
+    pub best_of_sets: i32,
+    /// Number of players, 2 or 4. With four players the top and bottom walls
+    /// are goals too, each one guarded by a paddle.
+    pub players: i32,
+    /// Number of balls played at the same time, each one scores on its own.
+    pub ball_count: i32,
+    /// Whether the balls bounce on each other, they pass through otherwise.
+    pub ball_collisions: bool,
+    /// Seconds between the serves of two balls, so they never wait in the
+    /// middle of the arena together.
+    pub serve_stagger: f32,
+    pub powerups: PowerUpRules,
+}
+
+/// Power-up settings of a match.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct PowerUpRules {
+    /// Power-ups that can appear, an empty list turns them off. A power-up
+    /// listed twice appears twice as often.
+    pub enabled: Vec<PowerUpKind>,
+    /// Average seconds between two power-ups.
+    pub spawn_interval: f32,
+    /// Power-ups waiting in the arena at the same time.
+    pub max_pickups: i32,
+    pub radius: f32,
+    /// Seconds an effect lasts once picked up.
+    pub duration: f32,
+    /// Height of a long paddle, as a fraction of `paddle_height`.
+    pub long_paddle_scale: f32,
+    /// Height of a short paddle, as a fraction of `paddle_height`.
+    pub short_paddle_scale: f32,
+    /// Speed of the balls while a fast ball is active, as a fraction of their velocity.
+    pub fast_ball_scale: f32,
+}
+
+impl Default for PowerUpRules {
+    fn default() -> Self {
+        PowerUpRules {
+            enabled: Vec::new(),
+            spawn_interval: 10.0,
+            max_pickups: 1,
+            radius: 3.0,
+            duration: 8.0,
+            long_paddle_scale: 1.5,
+            short_paddle_scale: 0.6,
+            fast_ball_scale: 1.5,
+        }
+    }
+}
+
+impl Default for GameRules {
+    fn default() -> Self {
+        GameRules {
+            arena_width: 100.0,
+            arena_height: 100.0,
+            paddle_width: 4.0,
+            paddle_height: 16.0,
+            paddle_max_speed: 72.0,
+            paddle_acceleration: 600.0,
+            paddle_deceleration: 900.0,
+            ball_radius: 2.0,
+            ball_velocity_x: 30.0,
+            ball_velocity_y: 15.0,
+            ball_acceleration: 0.2,
+            max_ball_velocity_x: 60.0,
+            max_ball_velocity_y: 30.0,
+            max_bounce_angle: 45.0,
+            paddle_english: 0.0,
+            serve_wait: 2.0,
+            points_to_win: 11,
+            win_by_two: true,
+            best_of_sets: 1,
+            players: 2,
+            ball_count: 1,
+            ball_collisions: false,
+            serve_stagger: 1.0,
+            powerups: PowerUpRules::default(),
+        }
+    }
+}
+
+impl GameRules {
+    /// Loads the rules from a RON file and validates them.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<GameRules, RulesError> {
+        let rules = GameRules::load(path).map_err(RulesError::Load)?;
+        rules.validate()?;
+        Ok(rules)
+    }
+
+    pub fn halve_width(&self) -> f32 {
+        self.arena_width * 0.5
+    }
+
+    pub fn halve_height(&self) -> f32 {
+        self.arena_height * 0.5
+    }
+
+    /// Sides played in the match, left and right first.
+    pub fn sides(&self) -> &'static [Side] {
+        if self.players == 4 {
+            &Side::ALL
+        } else {
+            &Side::ALL[..2]
+        }
+    }
+
+    /// Number of sets a player has to win to win the match.
+    pub fn sets_to_win(&self) -> i32 {
+        self.best_of_sets / 2 + 1
+    }
+
+    /// Sets the rule called `field` to `value`, for the rule variants of a
+    /// batch. Only the numbers and switches of the top level can be set, and
+    /// the rules are not validated again.
+    pub fn set(&mut self, field: &str, value: &str) -> Result<(), RulesError> {
+        fn parse<T: FromStr>(field: &str, value: &str) -> Result<T, RulesError> {
+            value
+                .parse()
+                .map_err(|_| RulesError::invalid(field, format!("can not be `{}`", value)))
+        }
+
+        match field {
+            "arena_width" => self.arena_width = parse(field, value)?,
+            "arena_height" => self.arena_height = parse(field, value)?,
+            "paddle_width" => self.paddle_width = parse(field, value)?,
+            "paddle_height" => self.paddle_height = parse(field, value)?,
+            "paddle_max_speed" => self.paddle_max_speed = parse(field, value)?,
+            "paddle_acceleration" => self.paddle_acceleration = parse(field, value)?,
+            "paddle_deceleration" => self.paddle_deceleration = parse(field, value)?,
+            "ball_radius" => self.ball_radius = parse(field, value)?,
+            "ball_velocity_x" => self.ball_velocity_x = parse(field, value)?,
+            "ball_velocity_y" => self.ball_velocity_y = parse(field, value)?,
+            "ball_acceleration" => self.ball_acceleration = parse(field, value)?,
+            "max_ball_velocity_x" => self.max_ball_velocity_x = parse(field, value)?,
+            "max_ball_velocity_y" => self.max_ball_velocity_y = parse(field, value)?,
+            "max_bounce_angle" => self.max_bounce_angle = parse(field, value)?,
+            "paddle_english" => self.paddle_english = parse(field, value)?,
+            "serve_wait" => self.serve_wait = parse(field, value)?,
+            "points_to_win" => self.points_to_win = parse(field, value)?,
+            "win_by_two" => self.win_by_two = parse(field, value)?,
+            "best_of_sets" => self.best_of_sets = parse(field, value)?,
+            "players" => self.players = parse(field, value)?,
+            "ball_count" => self.ball_count = parse(field, value)?,
+            "ball_collisions" => self.ball_collisions = parse(field, value)?,
+            "serve_stagger" => self.serve_stagger = parse(field, value)?,
+            _ => return Err(RulesError::invalid(field, "is not a rule that can be set")),
+        }
+        Ok(())
+    }
+
+    /// Checks that every value is usable, so a bad rules file fails at startup
+    /// instead of producing a broken match.
+    pub fn validate(&self) -> Result<(), RulesError> {
+        let values = [
+            ("arena_width", self.arena_width),
+            ("arena_height", self.arena_height),
+            ("paddle_width", self.paddle_width),
+            ("paddle_height", self.paddle_height),
+            ("paddle_max_speed", self.paddle_max_speed),
+            ("paddle_acceleration", self.paddle_acceleration),
+            ("paddle_deceleration", self.paddle_deceleration),
+            ("ball_radius", self.ball_radius),
+            ("ball_velocity_x", self.ball_velocity_x),
+            ("ball_velocity_y", self.ball_velocity_y),
+            ("ball_acceleration", self.ball_acceleration),
+            ("max_ball_velocity_x", self.max_ball_velocity_x),
+            ("max_ball_velocity_y", self.max_ball_velocity_y),
+            ("max_bounce_angle", self.max_bounce_angle),
+            ("paddle_english", self.paddle_english),
+            ("serve_wait", self.serve_wait),
+            ("serve_stagger", self.serve_stagger),
+            ("powerups.spawn_interval", self.powerups.spawn_interval),
+            ("powerups.radius", self.powerups.radius),
+            ("powerups.duration", self.powerups.duration),
+            ("powerups.long_paddle_scale", self.powerups.long_paddle_scale),
+            ("powerups.short_paddle_scale", self.powerups.short_paddle_scale),
+            ("powerups.fast_ball_scale", self.powerups.fast_ball_scale),
+        ];
+        for (field, value) in values.iter() {
+            if !value.is_finite() {
+                return Err(RulesError::invalid(field, "must be a finite number"));
+            }
+            if *value < 0.0 {
+                return Err(RulesError::invalid(field, "must not be negative"));
+            }
+        }
+
+        let non_zero = [
+            ("arena_width", self.arena_width),
+            ("arena_height", self.arena_height),
+            ("paddle_width", self.paddle_width),
+            ("paddle_height", self.paddle_height),
+            ("paddle_max_speed", self.paddle_max_speed),
+            ("paddle_acceleration", self.paddle_acceleration),
+            ("paddle_deceleration", self.paddle_deceleration),
+            ("ball_radius", self.ball_radius),
+            ("ball_velocity_x", self.ball_velocity_x),
+            ("powerups.spawn_interval", self.powerups.spawn_interval),
+            ("powerups.radius", self.powerups.radius),
+            ("powerups.duration", self.powerups.duration),
+            ("powerups.long_paddle_scale", self.powerups.long_paddle_scale),
+            ("powerups.short_paddle_scale", self.powerups.short_paddle_scale),
+            ("powerups.fast_ball_scale", self.powerups.fast_ball_scale),
+        ];
+        for (field, value) in non_zero.iter() {
+            if *value == 0.0 {
+                return Err(RulesError::invalid(field, "must be greater than zero"));
+            }
+        }
+
+        if self.paddle_height >= self.arena_height {
+            return Err(RulesError::invalid(
+                "paddle_height",
+                format!("must be smaller than arena_height ({})", self.arena_height),
+            ));
+        }
+        if (self.paddle_width + self.ball_radius) * 2.0 >= self.arena_width {
+            return Err(RulesError::invalid(
+                "paddle_width",
+                format!(
+                    "two paddles and the ball must fit in arena_width ({})",
+                    self.arena_width
+                ),
+            ));
+        }
+        if self.ball_radius * 2.0 >= self.arena_height {
+            return Err(RulesError::invalid(
+                "ball_radius",
+                format!("the ball must fit in arena_height ({})", self.arena_height),
+            ));
+        }
+        if self.max_ball_velocity_x < self.ball_velocity_x {
+            return Err(RulesError::invalid(
+                "max_ball_velocity_x",
+                format!(
+                    "must be at least ball_velocity_x ({})",
+                    self.ball_velocity_x
+                ),
+            ));
+        }
+        if self.max_ball_velocity_y < self.ball_velocity_y {
+            return Err(RulesError::invalid(
+                "max_ball_velocity_y",
+                format!(
+                    "must be at least ball_velocity_y ({})",
+                    self.ball_velocity_y
+                ),
+            ));
+        }
+
+        if self.max_bounce_angle >= 90.0 {
+            return Err(RulesError::invalid(
+                "max_bounce_angle",
+                "must be smaller than 90 degrees",
+            ));
+        }
+
+        if self.points_to_win < 1 || self.points_to_win > MAX_POINTS_TO_WIN {
+            return Err(RulesError::invalid(
+                "points_to_win",
+                format!("must be between 1 and {}", MAX_POINTS_TO_WIN),
+            ));
+        }
+        if self.best_of_sets < 1 || self.best_of_sets % 2 == 0 {
+            return Err(RulesError::invalid(
+                "best_of_sets",
+                "must be an odd number of at least 1",
+            ));
+        }
+        if self.players != 2 && self.players != 4 {
+            return Err(RulesError::invalid("players", "must be 2 or 4"));
+        }
+        if self.players == 4 {
+            if self.paddle_height >= self.arena_width {
+                return Err(RulesError::invalid(
+                    "paddle_height",
+                    format!(
+                        "must be smaller than arena_width ({}) with four players",
+                        self.arena_width
+                    ),
+                ));
+            }
+            if (self.paddle_width + self.ball_radius) * 2.0 >= self.arena_height {
+                return Err(RulesError::invalid(
+                    "paddle_width",
+                    format!(
+                        "two paddles and the ball must fit in arena_height ({}) with four players",
+                        self.arena_height
+                    ),
+                ));
+            }
+        }
+        if self.ball_count < 1 || self.ball_count > MAX_BALLS {
+            return Err(RulesError::invalid(
+                "ball_count",
+                format!("must be between 1 and {}", MAX_BALLS),
+            ));
+        }
+
+        let powerups = &self.powerups;
+        if powerups.max_pickups < 0 || powerups.max_pickups > MAX_PICKUPS {
+            return Err(RulesError::invalid(
+                "powerups.max_pickups",
+                format!("must be between 0 and {}", MAX_PICKUPS),
+            ));
+        }
+        if powerups.radius * 2.0 >= self.arena_height {
+            return Err(RulesError::invalid(
+                "powerups.radius",
+                format!("power-ups must fit in arena_height ({})", self.arena_height),
+            ));
+        }
+        if self.paddle_height * powerups.long_paddle_scale >= self.arena_height {
+            return Err(RulesError::invalid(
+                "powerups.long_paddle_scale",
+                format!(
+                    "a long paddle must be smaller than arena_height ({})",
+                    self.arena_height
+                ),
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+/// Errors that can happen while loading the game rules.
+#[derive(Debug)]
+pub enum RulesError {
+    /// The file could not be read or parsed.
+    Load(ConfigError),
+    /// The file was parsed but a value is out of range.
+    Invalid { field: String, reason: String },
+}
+
+impl RulesError {
+    fn invalid<R: Into<String>>(field: &str, reason: R) -> RulesError {
+        RulesError::Invalid {
+            field: field.to_string(),
+            reason: reason.into(),
+        }
+    }
+}
+
+impl fmt::Display for RulesError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RulesError::Load(e) => write!(f, "could not load game rules: {}", e),
+            RulesError::Invalid { field, reason } => {
+                write!(f, "invalid game rules: `{}` {}", field, reason)
+            }
+        }
+    }
+}
+
+impl Error for RulesError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            RulesError::Load(e) => Some(e),
+            RulesError::Invalid { .. } => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns the field `rules` are refused for.
+    fn refused_field(rules: GameRules) -> String {
+        match rules.validate() {
+            Err(RulesError::Invalid { field, .. }) => field,
+            other => panic!("expected invalid rules, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn shipped_rules_are_the_defaults() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("config/rules.ron");
+        let rules = GameRules::from_file(path).unwrap();
+        assert_eq!(rules, GameRules::default());
+    }
+
+    #[test]
+    fn values_out_of_range_are_refused() {
+        let defaults = GameRules::default();
+        assert!(defaults.validate().is_ok());
+
+        let cases = vec![
+            (
+                GameRules {
+                    arena_width: 0.0,
+                    ..defaults.clone()
+                },
+                "arena_width",
+            ),
+            (
+                GameRules {
+                    paddle_max_speed: -1.0,
+                    ..defaults.clone()
+                },
+                "paddle_max_speed",
+            ),
+            (
+                GameRules {
+                    ball_radius: f32::NAN,
+                    ..defaults.clone()
+                },
+                "ball_radius",
+            ),
+            (
+                GameRules {
+                    paddle_height: defaults.arena_height,
+                    ..defaults.clone()
+                },
+                "paddle_height",
+            ),
+            (
+                GameRules {
+                    max_ball_velocity_x: defaults.ball_velocity_x - 1.0,
+                    ..defaults.clone()
+                },
+                "max_ball_velocity_x",
+            ),
+            (
+                GameRules {
+                    max_bounce_angle: 90.0,
+                    ..defaults.clone()
+                },
+                "max_bounce_angle",
+            ),
+        ];
+        for (rules, field) in cases {
+            assert_eq!(refused_field(rules), field);
+        }
+    }
+}
@@ -0,0 +1,277 @@
+//! Preferences changed from the settings screen and kept between runs, in the
+//! config directory of the platform.
+
+use std::path::PathBuf;
+
+use amethyst::{
+    audio::AudioSink,
+    config::{Config, ConfigError},
+    core::frame_limiter::{FrameLimiter, FrameRateLimitStrategy},
+    prelude::*,
+    window::Window,
+    winit::dpi::LogicalSize,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::ai::{AiConfig, Difficulty};
+use crate::audio::Sounds;
+use crate::rules::GameRules;
+
+/// Window sizes offered by the settings screen.
+pub const WINDOW_SIZES: [(u32, u32); 4] = [(500, 500), (750, 750), (1000, 1000), (1200, 1200)];
+/// Frame rate with vsync on. The renderer does not let the game pick the
+/// present mode, so the frame limiter keeps it in step with a 60 Hz screen.
+pub const VSYNC_FRAME_RATE: u32 = 60;
+
+/// Most points to win a set the settings screen goes up to.
+const MAX_POINTS_TO_WIN: i32 = 21;
+/// Computer opponents the settings screen goes through, in order.
+const COMPUTERS: [Option<Difficulty>; 4] = [
+    None,
+    Some(Difficulty::Easy),
+    Some(Difficulty::Normal),
+    Some(Difficulty::Hard),
+];
+
+/// A line of the settings screen.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Setting {
+    MusicVolume,
+    SfxVolume,
+    Fullscreen,
+    WindowSize,
+    Vsync,
+    Computer,
+    PointsToWin,
+}
+
+impl Setting {
+    pub const ALL: [Setting; 7] = [
+        Setting::MusicVolume,
+        Setting::SfxVolume,
+        Setting::Fullscreen,
+        Setting::WindowSize,
+        Setting::Vsync,
+        Setting::Computer,
+        Setting::PointsToWin,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Setting::MusicVolume => "Music volume",
+            Setting::SfxVolume => "Sound effects volume",
+            Setting::Fullscreen => "Fullscreen",
+            Setting::WindowSize => "Window size",
+            Setting::Vsync => "Vsync",
+            Setting::Computer => "Computer opponent",
+            Setting::PointsToWin => "Points to win",
+        }
+    }
+}
+
+/// Settings are the preferences of the player. They are loaded before the
+/// game starts and applied over the config files, the command line still has
+/// the last word.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Settings {
+    /// Volume of the music and of the sound effects, from 0.0 to 1.0.
+    pub music_volume: f32,
+    pub sfx_volume: f32,
+    pub fullscreen: bool,
+    /// Size of the window, when it is not fullscreen.
+    pub window_size: (u32, u32),
+    pub vsync: bool,
+    /// Difficulty of the computer playing the right paddle, `None` for a
+    /// second human player.
+    pub computer: Option<Difficulty>,
+    pub points_to_win: i32,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            music_volume: 0.25,
+            sfx_volume: 1.0,
+            fullscreen: false,
+            window_size: WINDOW_SIZES[0],
+            vsync: true,
+            computer: None,
+            points_to_win: 11,
+        }
+    }
+}
+
+impl Settings {
+    /// File the settings are saved to, `None` on platforms without a config
+    /// directory.
+    pub fn path() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join("rust-pong").join("settings.ron"))
+    }
+
+    /// Loads the saved settings. Without any, the settings are the ones of
+    /// the config files. A broken settings file is reported and replaced by
+    /// them as well, it should not keep the game from starting.
+    pub fn load_or(rules: &GameRules, ai: &AiConfig, window_size: Option<(u32, u32)>) -> Settings {
+        let current = Settings {
+            computer: ai.right,
+            points_to_win: rules.points_to_win,
+            window_size: window_size.unwrap_or(WINDOW_SIZES[0]),
+            ..Settings::default()
+        };
+        let path = match Settings::path() {
+            Some(path) if path.exists() => path,
+            _ => return current,
+        };
+        match Settings::load(&path) {
+            Ok(settings) if settings.is_valid() => settings,
+            Ok(_) => {
+                eprintln!("ignoring invalid settings in {}", path.display());
+                current
+            }
+            Err(e) => {
+                eprintln!("could not load settings from {}: {}", path.display(), e);
+                current
+            }
+        }
+    }
+
+    /// Writes the settings to the config directory, creating it if needed.
+    pub fn save(&self) -> Result<(), ConfigError> {
+        let path = match Settings::path() {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        self.write(&path)
+    }
+
+    /// Text of `setting` on the settings screen.
+    pub fn value(&self, setting: Setting) -> String {
+        let on_off = |on: bool| if on { "On" } else { "Off" }.to_string();
+        match setting {
+            Setting::MusicVolume => format!("{:.0}%", self.music_volume * 100.0),
+            Setting::SfxVolume => format!("{:.0}%", self.sfx_volume * 100.0),
+            Setting::Fullscreen => on_off(self.fullscreen),
+            Setting::WindowSize => format!("{}x{}", self.window_size.0, self.window_size.1),
+            Setting::Vsync => on_off(self.vsync),
+            Setting::Computer => match self.computer {
+                Some(difficulty) => format!("{:?}", difficulty),
+                None => "Off".to_string(),
+            },
+            Setting::PointsToWin => self.points_to_win.to_string(),
+        }
+    }
+
+    /// Moves `setting` one notch up, or down when `step` is negative. Lists
+    /// of choices wrap around, numbers stop at their limits.
+    pub fn change(&mut self, setting: Setting, step: i32) {
+        // Volumes go by tenths, rounded so they do not drift.
+        let volume = |volume: f32| ((volume * 10.0).round() + step as f32).clamp(0.0, 10.0) / 10.0;
+        let cycle =
+            |index: usize, count: usize| (index as i32 + step).rem_euclid(count as i32) as usize;
+        match setting {
+            Setting::MusicVolume => self.music_volume = volume(self.music_volume),
+            Setting::SfxVolume => self.sfx_volume = volume(self.sfx_volume),
+            Setting::Fullscreen => self.fullscreen = !self.fullscreen,
+            Setting::WindowSize => {
+                let index = WINDOW_SIZES
+                    .iter()
+                    .position(|size| *size == self.window_size)
+                    .unwrap_or(0);
+                self.window_size = WINDOW_SIZES[cycle(index, WINDOW_SIZES.len())];
+            }
+            Setting::Vsync => self.vsync = !self.vsync,
+            Setting::Computer => {
+                let index = COMPUTERS
+                    .iter()
+                    .position(|computer| *computer == self.computer)
+                    .unwrap_or(0);
+                self.computer = COMPUTERS[cycle(index, COMPUTERS.len())];
+            }
+            Setting::PointsToWin => {
+                self.points_to_win = (self.points_to_win + step).clamp(1, MAX_POINTS_TO_WIN)
+            }
+        }
+    }
+
+    fn is_valid(&self) -> bool {
+        let volumes = [self.music_volume, self.sfx_volume];
+        volumes.iter().all(|volume| (0.0..=1.0).contains(volume))
+            && self.window_size.0 > 0
+            && self.window_size.1 > 0
+            && self.points_to_win >= 1
+    }
+
+    /// Applies the settings that are read when a match starts.
+    pub fn apply_to_match(&self, rules: &mut GameRules, ai: &mut AiConfig) {
+        rules.points_to_win = self.points_to_win;
+        ai.right = self.computer;
+    }
+
+    /// Frame rate limit matching the vsync setting, the rate is ignored
+    /// without vsync.
+    pub fn frame_limit(&self) -> (FrameRateLimitStrategy, u32) {
+        let strategy = if self.vsync {
+            FrameRateLimitStrategy::default()
+        } else {
+            FrameRateLimitStrategy::Unlimited
+        };
+        (strategy, VSYNC_FRAME_RATE)
+    }
+}
+
+/// Applies the volumes, the window and the frame rate of the `Settings` of
+/// the world to the running game.
+pub fn apply(world: &mut World) {
+    let settings = world.read_resource::<Settings>().clone();
+    world
+        .write_resource::<AudioSink>()
+        .set_volume(settings.music_volume);
+    if let Some(mut sounds) = world.try_fetch_mut::<Sounds>() {
+        sounds.volume = settings.sfx_volume;
+    }
+    let (strategy, rate) = settings.frame_limit();
+    world
+        .write_resource::<FrameLimiter>()
+        .set_rate(strategy, rate);
+
+    let window = world.read_resource::<Window>();
+    if settings.fullscreen {
+        window.set_fullscreen(Some(window.get_current_monitor()));
+    } else {
+        window.set_fullscreen(None);
+        let (width, height) = settings.window_size;
+        window.set_inner_size(LogicalSize::new(f64::from(width), f64::from(height)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn volumes_go_by_tenths_within_limits() {
+        let mut settings = Settings::default();
+        settings.change(Setting::MusicVolume, 1);
+        assert_eq!(settings.value(Setting::MusicVolume), "40%");
+        for _ in 0..20 {
+            settings.change(Setting::MusicVolume, -1);
+        }
+        assert_eq!(settings.music_volume, 0.0);
+    }
+
+    #[test]
+    fn choices_wrap_around() {
+        let mut settings = Settings::default();
+        settings.change(Setting::Computer, -1);
+        assert_eq!(settings.computer, Some(Difficulty::Hard));
+        settings.change(Setting::Computer, 1);
+        assert_eq!(settings.computer, None);
+
+        settings.change(Setting::WindowSize, -1);
+        assert_eq!(settings.window_size, WINDOW_SIZES[WINDOW_SIZES.len() - 1]);
+    }
+}
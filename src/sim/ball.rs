@@ -0,0 +1,69 @@
+use crate::rules::GameRules;
+use crate::sim::Side;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BallState {
+    Waiting,
+    Moving,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Ball {
+    pub position: [f32; 2],
+    pub velocity: [f32; 2],
+    pub radius: f32,
+    pub state: BallState,
+    pub waiting_time: f32,
+    /// Position of the ball before it moved during the last step.
+    pub last_position: [f32; 2],
+    /// Side of the last paddle that returned the ball, it gets the power-ups.
+    pub last_hit: Option<Side>,
+    /// Copy of a split ball, it leaves the match instead of being served again.
+    pub extra: bool,
+}
+
+impl Ball {
+    /// Creates a ball waiting to be served in the middle of the arena.
+    pub fn new(rules: &GameRules) -> Ball {
+        let center = [rules.halve_width(), rules.halve_height()];
+        Ball {
+            position: center,
+            velocity: [rules.ball_velocity_x, rules.ball_velocity_y],
+            radius: rules.ball_radius,
+            state: BallState::Waiting,
+            waiting_time: rules.serve_wait,
+            last_position: center,
+            last_hit: None,
+            extra: false,
+        }
+    }
+
+    pub fn wait(&mut self, rules: &GameRules) {
+        self.state = BallState::Waiting;
+        self.waiting_time = rules.serve_wait;
+        self.velocity = [rules.ball_velocity_x, rules.ball_velocity_y]
+    }
+
+    /// Speeds the ball up after a hit, each component up to the top velocity
+    /// of its axis whichever way the ball goes.
+    pub fn accelerate(&mut self, rules: &GameRules) {
+        let acceleration = rules.ball_acceleration;
+        let (max_x, max_y) = (rules.max_ball_velocity_x, rules.max_ball_velocity_y);
+        let velocity_x =
+            (self.velocity[0] + (self.velocity[0] * acceleration)).clamp(-max_x, max_x);
+        let velocity_y =
+            (self.velocity[1] + (self.velocity[1] * acceleration)).clamp(-max_y, max_y);
+        self.velocity = [velocity_x, velocity_y]
+    }
+
+    /// Puts the ball back in the middle of the arena, served away from the
+    /// direction it was going.
+    pub fn reset(&mut self, rules: &GameRules) {
+        let direction = -self.velocity[0].signum(); // Reverse X-Direction
+        self.position = [rules.halve_width(), rules.halve_height()];
+        self.last_position = self.position;
+        self.last_hit = None;
+        self.wait(rules);
+        self.velocity[0] *= direction;
+    }
+}
@@ -0,0 +1,417 @@
+use crate::level::{Bumper, Portal};
+use crate::rules::GameRules;
+use crate::sim::collision::{reflect, sweep_circle_rect, Rect};
+use crate::sim::powerup::SHIELD_WIDTH;
+use crate::sim::{Ball, BallState, Paddle, Side, SimEvent};
+
+/// A ball stuck between obstacles stops at its last impact instead of
+/// bouncing forever within a single step, the rest of its movement is dropped.
+const MAX_BOUNCES_PER_STEP: usize = 4;
+
+/// Everything a ball bounces on during a step, besides the walls.
+pub struct Surfaces<'a> {
+    pub paddles: &'a [Paddle],
+    /// Obstacles of the level, where they are this step.
+    pub obstacles: &'a [Rect],
+    /// Sides whose goal is closed by a shield.
+    pub shields: &'a [Side],
+}
+
+/// What a ball hit.
+#[derive(Clone, Copy)]
+enum Surface<'a> {
+    Wall,
+    Obstacle,
+    Paddle(&'a Paddle),
+}
+
+/// Moves the ball from `last_position` to `last_position + motion`, bouncing on
+/// the walls, the shields, the obstacles and the paddles on the way. The whole
+/// movement is swept, so a fast ball can not jump over a paddle, and whatever
+/// is left of it after an impact goes on in the new direction.
+pub fn move_ball(
+    index: usize,
+    ball: &mut Ball,
+    motion: [f32; 2],
+    surfaces: &Surfaces<'_>,
+    rules: &GameRules,
+    events: &mut Vec<SimEvent>,
+) {
+    // The top and bottom walls are rectangles right outside the arena, so
+    // they go through the same collision code as the paddles. They are goals
+    // when four players play.
+    let (width, height) = (rules.arena_width, rules.arena_height);
+    let mut walls = Vec::new();
+    if !rules.sides().contains(&Side::Bottom) {
+        walls.push(Rect {
+            left: -width,
+            bottom: -height,
+            right: width * 2.0,
+            top: 0.0,
+        });
+    }
+    if !rules.sides().contains(&Side::Top) {
+        walls.push(Rect {
+            left: -width,
+            bottom: height,
+            right: width * 2.0,
+            top: height * 2.0,
+        });
+    }
+    for side in surfaces.shields.iter() {
+        walls.push(match side {
+            Side::Left => Rect {
+                left: -width,
+                bottom: -height,
+                right: SHIELD_WIDTH,
+                top: height * 2.0,
+            },
+            Side::Right => Rect {
+                left: width - SHIELD_WIDTH,
+                bottom: -height,
+                right: width * 2.0,
+                top: height * 2.0,
+            },
+            Side::Top => Rect {
+                left: -width,
+                bottom: height - SHIELD_WIDTH,
+                right: width * 2.0,
+                top: height * 2.0,
+            },
+            Side::Bottom => Rect {
+                left: -width,
+                bottom: -height,
+                right: width * 2.0,
+                top: SHIELD_WIDTH,
+            },
+        });
+    }
+    let paddle_rects = surfaces
+        .paddles
+        .iter()
+        .map(|paddle| (paddle, paddle_rect(paddle)))
+        .collect::<Vec<_>>();
+    let obstacles = walls
+        .iter()
+        .map(|rect| (Surface::Wall, rect))
+        .chain(
+            surfaces
+                .obstacles
+                .iter()
+                .map(|rect| (Surface::Obstacle, rect)),
+        )
+        .chain(
+            paddle_rects
+                .iter()
+                .map(|(paddle, rect)| (Surface::Paddle(paddle), rect)),
+        )
+        .collect::<Vec<_>>();
+
+    let mut position = ball.last_position;
+    let mut motion = motion;
+    for _ in 0..MAX_BOUNCES_PER_STEP {
+        let hit = obstacles
+            .iter()
+            .filter_map(|(surface, rect)| {
+                sweep_circle_rect(position, motion, ball.radius, rect).map(|hit| (*surface, hit))
+            })
+            .min_by(|a, b| a.1.time.partial_cmp(&b.1.time).unwrap());
+        let (surface, hit) = match hit {
+            Some(hit) => hit,
+            None => {
+                position = [position[0] + motion[0], position[1] + motion[1]];
+                break;
+            }
+        };
+
+        position = [
+            position[0] + motion[0] * hit.time,
+            position[1] + motion[1] * hit.time,
+        ];
+        let speed = length(ball.velocity);
+        ball.velocity = reflect(ball.velocity, hit.normal);
+        ball.accelerate(rules);
+
+        match surface {
+            Surface::Paddle(paddle) => {
+                // A ball hitting the front of a paddle is aimed by the hit position.
+                let side = paddle.side;
+                if hit.normal[side.across()] * side.direction() > 0.0 {
+                    ball.velocity = aim(
+                        length(ball.velocity),
+                        position[side.axis()],
+                        ball.radius,
+                        paddle,
+                        rules,
+                    );
+                }
+                ball.last_hit = Some(paddle.side);
+                events.push(SimEvent::PaddleHit {
+                    ball: index,
+                    side: paddle.side,
+                });
+            }
+            Surface::Obstacle => events.push(SimEvent::ObstacleHit { ball: index }),
+            Surface::Wall => events.push(SimEvent::WallBounce { ball: index }),
+        }
+
+        // The rest of the movement follows the new velocity.
+        let time_left = (1.0 - hit.time) * length(motion) / speed;
+        motion = [ball.velocity[0] * time_left, ball.velocity[1] * time_left];
+    }
+
+    // A paddle can also move onto a ball that is not moving towards it.
+    // In that case the ball is sent back to the other player.
+    for (paddle, rect) in paddle_rects.iter() {
+        let across = paddle.side.across();
+        if rect.expand(ball.radius).contains(position)
+            && ball.velocity[across] * paddle.side.direction() < 0.0
+        {
+            ball.velocity[across] = -ball.velocity[across];
+            ball.accelerate(rules);
+            ball.last_hit = Some(paddle.side);
+            events.push(SimEvent::PaddleHit {
+                ball: index,
+                side: paddle.side,
+            });
+        }
+    }
+
+    // A moving obstacle can move onto a ball as well, it pushes the ball out
+    // of its way through the closest side.
+    for rect in surfaces.obstacles.iter() {
+        let grown = rect.expand(ball.radius);
+        if !grown.contains(position) {
+            continue;
+        }
+        let pushes = [
+            (grown.left - position[0], 0, -1.0),
+            (grown.right - position[0], 0, 1.0),
+            (grown.bottom - position[1], 1, -1.0),
+            (grown.top - position[1], 1, 1.0),
+        ];
+        let (distance, axis, direction) = pushes
+            .iter()
+            .copied()
+            .min_by(|a, b| a.0.abs().partial_cmp(&b.0.abs()).unwrap())
+            .unwrap();
+        position[axis] += distance;
+        if ball.velocity[axis] * direction < 0.0 {
+            ball.velocity[axis] = -ball.velocity[axis];
+        }
+        events.push(SimEvent::ObstacleHit { ball: index });
+    }
+
+    ball.position = position;
+}
+
+/// Sends a ball touching a bumper back, `boost` times faster.
+pub fn bump(
+    index: usize,
+    ball: &mut Ball,
+    bumpers: &[Bumper],
+    rules: &GameRules,
+    events: &mut Vec<SimEvent>,
+) {
+    for bumper in bumpers.iter() {
+        let offset = [
+            ball.position[0] - bumper.position[0],
+            ball.position[1] - bumper.position[1],
+        ];
+        let distance = length(offset);
+        let reach = ball.radius + bumper.radius;
+        if distance >= reach || distance == 0.0 {
+            continue;
+        }
+        let normal = [offset[0] / distance, offset[1] / distance];
+        // A ball already leaving the bumper is not sent back again.
+        if ball.velocity[0] * normal[0] + ball.velocity[1] * normal[1] >= 0.0 {
+            continue;
+        }
+        let velocity = reflect(ball.velocity, normal);
+        ball.velocity = limit_velocity(
+            [velocity[0] * bumper.boost, velocity[1] * bumper.boost],
+            rules,
+        );
+        ball.position = [
+            bumper.position[0] + normal[0] * reach,
+            bumper.position[1] + normal[1] * reach,
+        ];
+        events.push(SimEvent::BumperHit { ball: index });
+    }
+}
+
+/// Moves a ball that just went into the end of a portal to its other end,
+/// with the same velocity. The ball has to leave that end before it can go
+/// back through it.
+pub fn teleport(index: usize, ball: &mut Ball, portals: &[Portal], events: &mut Vec<SimEvent>) {
+    for portal in portals.iter() {
+        let inside = |point: [f32; 2], end: [f32; 2]| {
+            length([point[0] - end[0], point[1] - end[1]]) < portal.radius
+        };
+        for (entry, exit) in [(0, 1), (1, 0)].iter() {
+            let end = portal.ends[*entry];
+            if inside(ball.position, end) && !inside(ball.last_position, end) {
+                ball.position = portal.ends[*exit];
+                // The ball does not slide across the arena.
+                ball.last_position = ball.position;
+                events.push(SimEvent::PortalUsed { ball: index });
+                return;
+            }
+        }
+    }
+}
+
+/// Bounces the moving balls that touch off each other, like two equal masses.
+/// Both are pushed apart so they do not stay stuck together.
+pub fn collide_balls(balls: &mut [Ball], rules: &GameRules, events: &mut Vec<SimEvent>) {
+    for second in 1..balls.len() {
+        let (before, after) = balls.split_at_mut(second);
+        let other = &mut after[0];
+        for (first, ball) in before.iter_mut().enumerate() {
+            if ball.state != BallState::Moving || other.state != BallState::Moving {
+                continue;
+            }
+            let offset = [
+                other.position[0] - ball.position[0],
+                other.position[1] - ball.position[1],
+            ];
+            let distance = length(offset);
+            let reach = ball.radius + other.radius;
+            if distance >= reach || distance == 0.0 {
+                continue;
+            }
+            let normal = [offset[0] / distance, offset[1] / distance];
+            // Balls already moving apart are leaving each other.
+            let closing = (ball.velocity[0] - other.velocity[0]) * normal[0]
+                + (ball.velocity[1] - other.velocity[1]) * normal[1];
+            if closing <= 0.0 {
+                continue;
+            }
+
+            let push = (reach - distance) * 0.5;
+            for (target, sign) in [(&mut *ball, -1.0), (&mut *other, 1.0)].iter_mut() {
+                target.velocity[0] += *sign * closing * normal[0];
+                target.velocity[1] += *sign * closing * normal[1];
+                target.velocity = limit_velocity(target.velocity, rules);
+                target.position[0] += *sign * push * normal[0];
+                target.position[1] = (target.position[1] + *sign * push * normal[1])
+                    .max(target.radius)
+                    .min(rules.arena_height - target.radius);
+            }
+            events.push(SimEvent::BallHit {
+                ball: first,
+                other: second,
+            });
+        }
+    }
+}
+
+/// Keeps a ball hit by another one within the speed limits of the rules, and
+/// fast enough across the arena to never stall between the paddles.
+fn limit_velocity(velocity: [f32; 2], rules: &GameRules) -> [f32; 2] {
+    let min_x = rules.ball_velocity_x * 0.5;
+    let direction = if velocity[0] < 0.0 { -1.0 } else { 1.0 };
+    [
+        direction * velocity[0].abs().max(min_x).min(rules.max_ball_velocity_x),
+        velocity[1]
+            .max(-rules.max_ball_velocity_y)
+            .min(rules.max_ball_velocity_y),
+    ]
+}
+
+fn paddle_rect(paddle: &Paddle) -> Rect {
+    Rect::from_center(paddle.position, paddle.width, paddle.height)
+}
+
+/// Returns the velocity of a ball returned by a paddle. The further from the
+/// paddle center the ball hits, the steeper it goes back, up to
+/// `max_bounce_angle`. A moving paddle also gives the ball some english.
+/// Neither component goes past the top ball velocity of its axis.
+fn aim(speed: f32, impact: f32, radius: f32, paddle: &Paddle, rules: &GameRules) -> [f32; 2] {
+    let side = paddle.side;
+    let (axis, across) = (side.axis(), side.across());
+    let half_reach = paddle.length() * 0.5 + radius;
+    let offset = ((impact - paddle.position[axis]) / half_reach).clamp(-1.0, 1.0);
+    let angle = (offset * rules.max_bounce_angle).to_radians();
+
+    let max_velocity = [rules.max_ball_velocity_x, rules.max_ball_velocity_y];
+    let mut velocity = [0.0; 2];
+    velocity[across] = side.direction() * (speed * angle.cos()).min(max_velocity[across]);
+    velocity[axis] = (speed * angle.sin() + paddle.velocity * rules.paddle_english)
+        .clamp(-max_velocity[axis], max_velocity[axis]);
+    velocity
+}
+
+fn length(vector: [f32; 2]) -> f32 {
+    (vector[0] * vector[0] + vector[1] * vector[1]).sqrt()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: [f32; 2], b: [f32; 2]) -> bool {
+        (a[0] - b[0]).abs() < 1e-4 && (a[1] - b[1]).abs() < 1e-4
+    }
+
+    #[test]
+    fn center_hit_goes_straight_back() {
+        let rules = GameRules::default();
+        let paddle = Paddle::new(Side::Left, &rules);
+        let velocity = aim(40.0, paddle.position[1], rules.ball_radius, &paddle, &rules);
+        assert!(close(velocity, [40.0, 0.0]), "{:?}", velocity);
+    }
+
+    #[test]
+    fn edge_hit_goes_back_at_the_largest_angle() {
+        let rules = GameRules::default();
+        let paddle = Paddle::new(Side::Right, &rules);
+        let edge = paddle.position[1] + paddle.length() * 0.5 + rules.ball_radius;
+        let velocity = aim(40.0, edge, rules.ball_radius, &paddle, &rules);
+        let angle = rules.max_bounce_angle.to_radians();
+        let expected = [-40.0 * angle.cos(), 40.0 * angle.sin()];
+        assert!(close(velocity, expected), "{:?}", velocity);
+
+        // Past the edge is still the edge.
+        assert_eq!(
+            aim(40.0, edge + 5.0, rules.ball_radius, &paddle, &rules),
+            velocity
+        );
+    }
+
+    #[test]
+    fn returned_ball_keeps_under_the_top_velocity() {
+        let rules = GameRules::default();
+        for side in [Side::Left, Side::Right].iter() {
+            let paddle = Paddle::new(*side, &rules);
+            let edge = paddle.position[1] - paddle.length();
+            for impact in [paddle.position[1], edge].iter() {
+                let velocity = aim(200.0, *impact, rules.ball_radius, &paddle, &rules);
+                assert!(
+                    velocity[0].abs() <= rules.max_ball_velocity_x,
+                    "{:?}",
+                    velocity
+                );
+                assert!(
+                    velocity[1].abs() <= rules.max_ball_velocity_y,
+                    "{:?}",
+                    velocity
+                );
+                assert_eq!(velocity[0].signum(), side.direction());
+            }
+        }
+
+        // Going left and down, the ball still speeds up to the top velocity only.
+        let mut ball = Ball::new(&rules);
+        ball.velocity = [
+            rules.max_ball_velocity_x * -0.95,
+            rules.max_ball_velocity_y * -0.95,
+        ];
+        ball.accelerate(&rules);
+        assert_eq!(
+            ball.velocity,
+            [-rules.max_ball_velocity_x, -rules.max_ball_velocity_y]
+        );
+    }
+}
@@ -0,0 +1,255 @@
+//! Continuous collision detection between a moving ball and rectangles.
+//!
+//! The ball is swept along its whole movement of the step, so it can not jump
+//! over a paddle when it moves fast or when a step takes long.
+
+/// An axis aligned rectangle, in arena units.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Rect {
+    pub left: f32,
+    pub bottom: f32,
+    pub right: f32,
+    pub top: f32,
+}
+
+impl Rect {
+    pub fn from_center(center: [f32; 2], width: f32, height: f32) -> Rect {
+        Rect {
+            left: center[0] - width * 0.5,
+            bottom: center[1] - height * 0.5,
+            right: center[0] + width * 0.5,
+            top: center[1] + height * 0.5,
+        }
+    }
+
+    /// Returns the rectangle grown by `amount` on every side.
+    pub fn expand(&self, amount: f32) -> Rect {
+        Rect {
+            left: self.left - amount,
+            bottom: self.bottom - amount,
+            right: self.right + amount,
+            top: self.top + amount,
+        }
+    }
+
+    // A point is in a box when its coordinates are smaller or equal than the top
+    // right and larger or equal than the bottom left.
+    pub fn contains(&self, point: [f32; 2]) -> bool {
+        point[0] >= self.left
+            && point[0] <= self.right
+            && point[1] >= self.bottom
+            && point[1] <= self.top
+    }
+}
+
+/// Where a swept ball first touches a rectangle.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Hit {
+    /// Fraction of the movement done before the impact, between 0 and 1.
+    pub time: f32,
+    /// Unit vector pointing out of the rectangle at the impact point.
+    pub normal: [f32; 2],
+}
+
+/// Sweeps a circle of `radius` from `start` along `motion` and returns the
+/// first impact with `rect`. A circle that already overlaps the rectangle at
+/// `start` is not reported, it is on its way out.
+pub fn sweep_circle_rect(
+    start: [f32; 2],
+    motion: [f32; 2],
+    radius: f32,
+    rect: &Rect,
+) -> Option<Hit> {
+    // The circle touches the rectangle when its center enters the rectangle
+    // grown by the radius with rounded corners. We first cast the center against
+    // the grown rectangle with square corners.
+    let expanded = rect.expand(radius);
+    let mins = [expanded.left, expanded.bottom];
+    let maxs = [expanded.right, expanded.top];
+
+    let mut enter = f32::NEG_INFINITY;
+    let mut exit = f32::INFINITY;
+    let mut normal = [0.0, 0.0];
+    for axis in 0..2 {
+        if motion[axis] == 0.0 {
+            if start[axis] < mins[axis] || start[axis] > maxs[axis] {
+                return None;
+            }
+            continue;
+        }
+        let to_min = (mins[axis] - start[axis]) / motion[axis];
+        let to_max = (maxs[axis] - start[axis]) / motion[axis];
+        let (near, far) = if to_min < to_max {
+            (to_min, to_max)
+        } else {
+            (to_max, to_min)
+        };
+        if near > enter {
+            enter = near;
+            normal = [0.0, 0.0];
+            normal[axis] = -motion[axis].signum();
+        }
+        exit = exit.min(far);
+    }
+
+    if !(0.0..=1.0).contains(&enter) || enter > exit {
+        return None;
+    }
+
+    // On the faces of the grown rectangle the square corners are right. In the
+    // corner areas the real shape is a circle around the rectangle corner.
+    let point = [start[0] + motion[0] * enter, start[1] + motion[1] * enter];
+    let outside_x = point[0] < rect.left || point[0] > rect.right;
+    let outside_y = point[1] < rect.bottom || point[1] > rect.top;
+    if !(outside_x && outside_y) {
+        return Some(Hit {
+            time: enter,
+            normal,
+        });
+    }
+
+    let corner = [
+        if point[0] < rect.left {
+            rect.left
+        } else {
+            rect.right
+        },
+        if point[1] < rect.bottom {
+            rect.bottom
+        } else {
+            rect.top
+        },
+    ];
+    sweep_point_circle(start, motion, corner, radius)
+}
+
+/// Casts a point against a circle, used for the rounded corners.
+fn sweep_point_circle(
+    start: [f32; 2],
+    motion: [f32; 2],
+    center: [f32; 2],
+    radius: f32,
+) -> Option<Hit> {
+    let offset = [start[0] - center[0], start[1] - center[1]];
+    let a = motion[0] * motion[0] + motion[1] * motion[1];
+    let b = 2.0 * (motion[0] * offset[0] + motion[1] * offset[1]);
+    let c = offset[0] * offset[0] + offset[1] * offset[1] - radius * radius;
+    let discriminant = b * b - 4.0 * a * c;
+    if a == 0.0 || c < 0.0 || discriminant < 0.0 {
+        return None;
+    }
+
+    let time = (-b - discriminant.sqrt()) / (2.0 * a);
+    if !(0.0..=1.0).contains(&time) {
+        return None;
+    }
+    let normal = [
+        (offset[0] + motion[0] * time) / radius,
+        (offset[1] + motion[1] * time) / radius,
+    ];
+    Some(Hit { time, normal })
+}
+
+/// Mirrors `vector` on the surface with the given unit `normal`.
+pub fn reflect(vector: [f32; 2], normal: [f32; 2]) -> [f32; 2] {
+    let dot = vector[0] * normal[0] + vector[1] * normal[1];
+    [
+        vector[0] - 2.0 * dot * normal[0],
+        vector[1] - 2.0 * dot * normal[1],
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RADIUS: f32 = 2.0;
+
+    fn left_paddle() -> Rect {
+        Rect::from_center([2.0, 50.0], 4.0, 16.0)
+    }
+
+    #[test]
+    fn fast_ball_does_not_tunnel_through_paddle() {
+        // Both ends of the movement are far from the paddle, only a sweep sees it.
+        let start = [50.0, 50.0];
+        let motion = [-1000.0, 0.0];
+        assert!(!left_paddle()
+            .expand(RADIUS)
+            .contains([start[0] + motion[0], 50.0]));
+
+        let hit = sweep_circle_rect(start, motion, RADIUS, &left_paddle()).unwrap();
+        assert_eq!(hit.normal, [1.0, 0.0]);
+        let impact_x = start[0] + motion[0] * hit.time;
+        assert!((impact_x - 6.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn large_delta_diagonal_movement_hits_the_face() {
+        // A frame hitch of one second at 60 units per second on both axes.
+        let start = [40.0, 20.0];
+        let motion = [-60.0, 60.0];
+
+        let hit = sweep_circle_rect(start, motion, RADIUS, &left_paddle()).unwrap();
+        assert_eq!(hit.normal, [1.0, 0.0]);
+        let impact = [
+            start[0] + motion[0] * hit.time,
+            start[1] + motion[1] * hit.time,
+        ];
+        assert!((impact[0] - 6.0).abs() < 1e-4);
+        assert!(impact[1] >= 42.0 && impact[1] <= 58.0);
+    }
+
+    #[test]
+    fn ball_passing_above_the_paddle_misses() {
+        let hit = sweep_circle_rect([50.0, 70.0], [-1000.0, 0.0], RADIUS, &left_paddle());
+        assert_eq!(hit, None);
+    }
+
+    #[test]
+    fn ball_stopping_before_the_paddle_misses() {
+        let hit = sweep_circle_rect([50.0, 50.0], [-40.0, 0.0], RADIUS, &left_paddle());
+        assert_eq!(hit, None);
+    }
+
+    #[test]
+    fn ball_moving_away_from_the_paddle_misses() {
+        let hit = sweep_circle_rect([6.0, 50.0], [1000.0, 0.0], RADIUS, &left_paddle());
+        assert_eq!(hit, None);
+    }
+
+    #[test]
+    fn ball_overlapping_at_start_is_not_reported() {
+        let hit = sweep_circle_rect([5.0, 50.0], [-10.0, 0.0], RADIUS, &left_paddle());
+        assert_eq!(hit, None);
+    }
+
+    #[test]
+    fn corner_hit_uses_the_rounded_corner() {
+        // Aimed at the top right corner of the paddle along the diagonal.
+        let corner = [4.0, 58.0];
+        let start = [corner[0] + 20.0, corner[1] + 20.0];
+        let hit = sweep_circle_rect(start, [-40.0, -40.0], RADIUS, &left_paddle()).unwrap();
+
+        let half_sqrt_2 = std::f32::consts::FRAC_1_SQRT_2;
+        assert!((hit.normal[0] - half_sqrt_2).abs() < 1e-4);
+        assert!((hit.normal[1] - half_sqrt_2).abs() < 1e-4);
+        let impact = [start[0] - 40.0 * hit.time, start[1] - 40.0 * hit.time];
+        let distance = ((impact[0] - corner[0]).powi(2) + (impact[1] - corner[1]).powi(2)).sqrt();
+        assert!((distance - RADIUS).abs() < 1e-3);
+    }
+
+    #[test]
+    fn ball_passing_next_to_the_corner_misses() {
+        // Crosses the square corner of the grown rectangle, but stays further
+        // than the radius from the paddle corner.
+        let hit = sweep_circle_rect([0.5, 65.0], [10.0, -10.0], RADIUS, &left_paddle());
+        assert_eq!(hit, None);
+    }
+
+    #[test]
+    fn reflect_flips_the_velocity_along_the_normal() {
+        assert_eq!(reflect([-30.0, 15.0], [1.0, 0.0]), [30.0, 15.0]);
+        assert_eq!(reflect([30.0, 15.0], [0.0, -1.0]), [30.0, -15.0]);
+    }
+}
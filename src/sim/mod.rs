@@ -0,0 +1,1002 @@
+//! Renderer free simulation of a match.
+//!
+//! Everything that decides how the game plays lives here: the paddles, the
+//! balls, their collisions and the score. It knows nothing about rendering or
+//! audio. The simulation is advanced with `Simulation::step` and tells what
+//! happened through `SimEvent`s, the amethyst systems only feed it the player
+//! inputs and show its state, so whole matches can be played in tests.
+
+pub use self::ball::{Ball, BallState};
+pub use self::paddle::{Paddle, Side};
+pub use self::powerup::{Effect, PowerUp, PowerUpKind, SHIELD_WIDTH};
+pub use self::replay::{Replay, ReplayCursor, REPLAY_VERSION};
+pub use self::rng::SimRng;
+pub use self::score::ScoreBoard;
+
+use serde::{Deserialize, Serialize};
+
+use crate::level::Level;
+use crate::rules::GameRules;
+
+mod ball;
+mod bounce;
+pub mod collision;
+mod paddle;
+pub mod powerup;
+mod replay;
+mod rng;
+mod score;
+
+/// Simulation ticks per second.
+pub const TICK_RATE: u32 = 120;
+/// Duration of a tick, in seconds.
+pub const TICK: f32 = 1.0 / TICK_RATE as f32;
+/// A frame longer than that is not caught up, the game slows down instead.
+const MAX_TICKS_PER_FRAME: u32 = 12;
+
+/// FixedStep turns the variable frame times into a whole number of ticks, so
+/// the simulation always advances by `TICK` whatever the frame rate is.
+#[derive(Clone, Debug, Default)]
+pub struct FixedStep {
+    accumulator: f32,
+}
+
+impl FixedStep {
+    /// Adds the time of a frame and returns how many ticks to run for it.
+    pub fn advance(&mut self, frame_time: f32) -> u32 {
+        self.accumulator = (self.accumulator + frame_time).min(TICK * MAX_TICKS_PER_FRAME as f32);
+        let mut ticks = 0;
+        while self.accumulator >= TICK {
+            self.accumulator -= TICK;
+            ticks += 1;
+        }
+        ticks
+    }
+
+    /// How far the frame is between the last tick and the next one, from 0 to
+    /// 1. Sprites are drawn that far between their last two positions.
+    pub fn alpha(&self) -> f32 {
+        self.accumulator / TICK
+    }
+}
+
+/// Returns the point `alpha` of the way from `from` to `to`.
+pub fn lerp(from: [f32; 2], to: [f32; 2], alpha: f32) -> [f32; 2] {
+    [
+        from[0] + (to[0] - from[0]) * alpha,
+        from[1] + (to[1] - from[1]) * alpha,
+    ]
+}
+
+/// Paddle inputs of every player for one step, between -1 (down or left) and
+/// 1 (up or right).
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq)]
+pub struct Inputs {
+    pub left: f32,
+    pub right: f32,
+    #[serde(default)]
+    pub top: f32,
+    #[serde(default)]
+    pub bottom: f32,
+}
+
+impl Inputs {
+    pub fn get(&self, side: Side) -> f32 {
+        match side {
+            Side::Left => self.left,
+            Side::Right => self.right,
+            Side::Top => self.top,
+            Side::Bottom => self.bottom,
+        }
+    }
+
+    pub fn set(&mut self, side: Side, value: f32) {
+        match side {
+            Side::Left => self.left = value,
+            Side::Right => self.right = value,
+            Side::Top => self.top = value,
+            Side::Bottom => self.bottom = value,
+        }
+    }
+}
+
+/// Something that happened during a step.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SimEvent {
+    /// A ball bounced on a wall of the arena that is not a goal.
+    WallBounce { ball: usize },
+    /// A ball was returned by a paddle.
+    PaddleHit { ball: usize, side: Side },
+    /// Two balls bounced on each other.
+    BallHit { ball: usize, other: usize },
+    /// A ball left the arena and `scorer` got the point.
+    Goal { ball: usize, scorer: Side },
+    /// A ball waiting in the middle of the arena was served.
+    Serve { ball: usize },
+    /// `winner` won the match, nothing moves after that.
+    MatchOver { winner: Side },
+    /// A power-up appeared in the arena.
+    PowerUpSpawned { kind: PowerUpKind },
+    /// `side` picked up a power-up, its effect starts.
+    PowerUpCollected { kind: PowerUpKind, side: Side },
+    /// The effect of a power-up picked up by `side` ran out and was undone.
+    PowerUpExpired { kind: PowerUpKind, side: Side },
+    /// A ball bounced on an obstacle of the level.
+    ObstacleHit { ball: usize },
+    /// A ball was sent back by a bumper of the level.
+    BumperHit { ball: usize },
+    /// A ball went through a portal of the level.
+    PortalUsed { ball: usize },
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Simulation {
+    pub rules: GameRules,
+    pub paddles: Vec<Paddle>,
+    pub balls: Vec<Ball>,
+    pub score: ScoreBoard,
+    /// Power-ups waiting to be picked up.
+    pub powerups: Vec<PowerUp>,
+    /// Power-ups picked up and still active.
+    pub effects: Vec<Effect>,
+    /// Seconds before the next power-up appears.
+    pub next_spawn: f32,
+    /// Decides where and which power-ups appear.
+    pub rng: SimRng,
+    /// Obstacles, portals and bumpers of the arena.
+    pub level: Level,
+    /// Seconds played since the start of the match, they move the obstacles.
+    pub clock: f32,
+}
+
+impl Simulation {
+    /// Creates a match with a paddle on every side played and the balls
+    /// waiting to be served, one after the other.
+    pub fn new(rules: GameRules) -> Simulation {
+        Simulation::seeded(rules, 0)
+    }
+
+    /// Creates a match whose power-ups are drawn from `seed`.
+    pub fn seeded(rules: GameRules, seed: u64) -> Simulation {
+        let balls = (0..rules.ball_count.max(1) as usize)
+            .map(|index| {
+                let mut ball = Ball::new(&rules);
+                ball.waiting_time += rules.serve_stagger * index as f32;
+                // Serves alternate between the two players.
+                if index % 2 == 1 {
+                    ball.velocity[0] = -ball.velocity[0];
+                }
+                ball
+            })
+            .collect();
+        Simulation {
+            paddles: rules
+                .sides()
+                .iter()
+                .map(|side| Paddle::new(*side, &rules))
+                .collect(),
+            balls,
+            score: ScoreBoard::default(),
+            powerups: Vec::new(),
+            effects: Vec::new(),
+            next_spawn: rules.powerups.spawn_interval,
+            rng: SimRng::new(seed),
+            level: Level::default(),
+            clock: 0.0,
+            rules,
+        }
+    }
+
+    /// Plays the match in the arena of `level` instead of an empty one.
+    pub fn with_level(mut self, level: Level) -> Simulation {
+        self.level = level;
+        self
+    }
+
+    pub fn paddle(&self, side: Side) -> Option<&Paddle> {
+        self.paddles.iter().find(|paddle| paddle.side == side)
+    }
+
+    pub fn is_over(&self) -> bool {
+        self.score.winner.is_some()
+    }
+
+    /// Advances the match by `time_delta` seconds and returns what happened.
+    /// Matches played a `TICK` at a time, as the game and replays do, follow
+    /// only the inputs of every tick: the same inputs give the same match, bit
+    /// for bit, at any frame rate.
+    pub fn step(&mut self, time_delta: f32, inputs: &Inputs) -> Vec<SimEvent> {
+        let mut events = Vec::new();
+        if self.is_over() {
+            return events;
+        }
+
+        let Simulation {
+            rules,
+            paddles,
+            balls,
+            score,
+            powerups,
+            effects,
+            next_spawn,
+            rng,
+            level,
+            clock,
+        } = self;
+
+        *clock += time_delta;
+        let obstacles = level
+            .obstacles
+            .iter()
+            .map(|obstacle| obstacle.rect(*clock))
+            .collect::<Vec<_>>();
+
+        powerup::expire(effects, balls, time_delta, &mut events);
+
+        for paddle in paddles.iter_mut() {
+            paddle.set_length(
+                rules.paddle_height * powerup::paddle_scale(effects, paddle.side, rules),
+            );
+            let mut input = inputs.get(paddle.side);
+            if powerup::is_reversed(effects, paddle.side) {
+                input = -input;
+            }
+            paddle.update(input, time_delta, rules);
+        }
+
+        let shields = powerup::shields(effects);
+        let surfaces = bounce::Surfaces {
+            paddles,
+            obstacles: &obstacles,
+            shields: &shields,
+        };
+        let speed_scale = powerup::ball_speed_scale(effects, rules);
+        let mut scored = Vec::new();
+        for (index, ball) in balls.iter_mut().enumerate() {
+            match ball.state {
+                BallState::Waiting => {
+                    if ball.waiting_time == 0. {
+                        ball.state = BallState::Moving;
+                        events.push(SimEvent::Serve { ball: index });
+                    } else {
+                        ball.waiting_time = (ball.waiting_time - time_delta).max(0.);
+                    }
+                }
+                BallState::Moving => {
+                    ball.last_position = ball.position;
+                    let time = time_delta * speed_scale;
+                    let motion = [ball.velocity[0] * time, ball.velocity[1] * time];
+                    bounce::move_ball(index, ball, motion, &surfaces, rules, &mut events);
+                    bounce::bump(index, ball, &level.bumpers, rules, &mut events);
+                    bounce::teleport(index, ball, &level.portals, &mut events);
+                }
+            }
+
+            let conceder = match goal(ball, rules) {
+                Some(side) => side,
+                None => continue,
+            };
+            // The last player who returned the ball scores, an own goal or a
+            // ball nobody returned goes to the player across the arena.
+            let scorer = match ball.last_hit {
+                Some(side) if side != conceder => side,
+                _ => conceder.opponent(),
+            };
+
+            // No more points are played once the match is won.
+            if score.winner.is_none() {
+                events.push(SimEvent::Goal {
+                    ball: index,
+                    scorer,
+                });
+                if let Some(winner) = score.award_point(scorer, rules) {
+                    events.push(SimEvent::MatchOver { winner });
+                }
+            }
+            scored.push(index);
+        }
+
+        for index in scored.into_iter().rev() {
+            // The copy of a split ball leaves once it scored.
+            if balls[index].extra {
+                balls.remove(index);
+                continue;
+            }
+            // A ball back in the middle is served after the ones already
+            // waiting there, they would overlap otherwise.
+            let last_serve = balls
+                .iter()
+                .enumerate()
+                .filter(|(other, ball)| *other != index && ball.state == BallState::Waiting)
+                .map(|(_, ball)| ball.waiting_time)
+                .fold(None, |last: Option<f32>, time| {
+                    Some(last.map_or(time, |last| last.max(time)))
+                });
+            let ball = &mut balls[index];
+            ball.reset(rules);
+            if let Some(last_serve) = last_serve {
+                ball.waiting_time = ball.waiting_time.max(last_serve + rules.serve_stagger);
+            }
+        }
+
+        if rules.ball_collisions {
+            bounce::collide_balls(balls, rules, &mut events);
+        }
+
+        powerup::collect(powerups, effects, balls, rules, &mut events);
+        powerup::spawn(powerups, next_spawn, rng, rules, time_delta, &mut events);
+
+        events
+    }
+}
+
+/// Returns the side whose goal the ball went into. The top and bottom walls
+/// are only goals when a player defends them.
+fn goal(ball: &Ball, rules: &GameRules) -> Option<Side> {
+    let [x, y] = ball.position;
+    let radius = ball.radius;
+    rules.sides().iter().copied().find(|side| match side {
+        Side::Left => x <= radius,
+        Side::Right => x >= rules.arena_width - radius,
+        Side::Top => y >= rules.arena_height - radius,
+        Side::Bottom => y <= radius,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::level::{Bumper, Obstacle, Portal};
+
+    const TIME_DELTA: f32 = 1.0 / 60.0;
+
+    /// Steps the simulation until `done` returns true, or panics after `seconds`.
+    fn run_until<F>(
+        sim: &mut Simulation,
+        inputs: &Inputs,
+        seconds: f32,
+        mut done: F,
+    ) -> Vec<SimEvent>
+    where
+        F: FnMut(&Simulation, &[SimEvent]) -> bool,
+    {
+        let mut time = 0.0;
+        while time < seconds {
+            let events = sim.step(TIME_DELTA, inputs);
+            if done(sim, &events) {
+                return events;
+            }
+            time += TIME_DELTA;
+        }
+        panic!("nothing happened in {} seconds", seconds);
+    }
+
+    #[test]
+    fn ball_is_served_after_the_serve_wait() {
+        let mut sim = Simulation::new(GameRules::default());
+        let serve_wait = sim.rules.serve_wait;
+
+        let mut time = 0.0;
+        run_until(&mut sim, &Inputs::default(), 5.0, |_, events| {
+            time += TIME_DELTA;
+            events.contains(&SimEvent::Serve { ball: 0 })
+        });
+        assert!(time >= serve_wait);
+        assert_eq!(sim.balls[0].state, BallState::Moving);
+    }
+
+    #[test]
+    fn unreturned_ball_scores_a_point() {
+        let mut sim = Simulation::new(GameRules::default());
+        // The ball is served to the right, the right player runs away from it.
+        let inputs = Inputs {
+            left: 0.0,
+            right: -1.0,
+            ..Inputs::default()
+        };
+
+        let events = run_until(&mut sim, &inputs, 10.0, |_, events| {
+            events.iter().any(|e| matches!(e, SimEvent::Goal { .. }))
+        });
+        assert!(events.contains(&SimEvent::Goal {
+            ball: 0,
+            scorer: Side::Left
+        }));
+        assert_eq!(sim.score.points(Side::Left), 1);
+        assert_eq!(sim.balls[0].state, BallState::Waiting);
+    }
+
+    #[test]
+    fn paddle_in_the_way_returns_the_ball() {
+        let rules = GameRules {
+            ball_velocity_y: 0.0,
+            ..GameRules::default()
+        };
+        let mut sim = Simulation::new(rules);
+
+        run_until(&mut sim, &Inputs::default(), 10.0, |_, events| {
+            events.contains(&SimEvent::PaddleHit {
+                ball: 0,
+                side: Side::Right,
+            })
+        });
+        assert!(sim.balls[0].velocity[0] < 0.0);
+        assert_eq!(sim.score, ScoreBoard::default());
+    }
+
+    #[test]
+    fn large_time_delta_does_not_tunnel_through_the_paddle() {
+        let rules = GameRules {
+            ball_velocity_y: 0.0,
+            ..GameRules::default()
+        };
+        let mut sim = Simulation::new(rules);
+        sim.balls[0].state = BallState::Moving;
+
+        // A single two second hitch moves the ball 60 units, well past the paddle.
+        let events = sim.step(2.0, &Inputs::default());
+        assert!(events.contains(&SimEvent::PaddleHit {
+            ball: 0,
+            side: Side::Right
+        }));
+        assert!(sim.balls[0].position[0] < sim.rules.arena_width);
+        assert_eq!(sim.score, ScoreBoard::default());
+    }
+
+    #[test]
+    fn match_is_over_once_a_player_wins() {
+        let rules = GameRules {
+            points_to_win: 2,
+            win_by_two: false,
+            serve_wait: 0.0,
+            ..GameRules::default()
+        };
+        let mut sim = Simulation::new(rules);
+        // Nobody defends, every serve ends in a goal.
+        sim.paddles.clear();
+
+        let events = run_until(&mut sim, &Inputs::default(), 30.0, |_, events| {
+            events
+                .iter()
+                .any(|e| matches!(e, SimEvent::MatchOver { .. }))
+        });
+        let winner = sim.score.winner.unwrap();
+        assert!(events.contains(&SimEvent::MatchOver { winner }));
+        assert!(sim.is_over());
+        assert!(sim.step(TIME_DELTA, &Inputs::default()).is_empty());
+    }
+
+    #[test]
+    fn deuce_needs_a_lead_of_two_points() {
+        let rules = GameRules {
+            points_to_win: 3,
+            win_by_two: true,
+            ..GameRules::default()
+        };
+        let mut score = ScoreBoard::default();
+        for _ in 0..2 {
+            score.award_point(Side::Left, &rules);
+            score.award_point(Side::Right, &rules);
+        }
+        // 3 - 2 is not enough, 3 - 3 is deuce again.
+        assert_eq!(score.award_point(Side::Left, &rules), None);
+        assert_eq!(score.award_point(Side::Right, &rules), None);
+        assert_eq!(score.award_point(Side::Right, &rules), None);
+        assert_eq!(score.points, [3, 4, 0, 0]);
+        assert_eq!(score.award_point(Side::Right, &rules), Some(Side::Right));
+
+        // Without it, the first to the points wins.
+        let rules = GameRules {
+            win_by_two: false,
+            ..rules
+        };
+        let mut score = ScoreBoard {
+            points: [2, 2, 0, 0],
+            ..ScoreBoard::default()
+        };
+        assert_eq!(score.award_point(Side::Left, &rules), Some(Side::Left));
+    }
+
+    #[test]
+    fn match_is_won_with_most_of_the_sets() {
+        let rules = GameRules {
+            points_to_win: 2,
+            win_by_two: false,
+            best_of_sets: 3,
+            ..GameRules::default()
+        };
+        let mut score = ScoreBoard::default();
+        let win_set = |score: &mut ScoreBoard, side: Side| {
+            let first = score.award_point(side, &rules);
+            assert_eq!(first, None);
+            score.award_point(side, &rules)
+        };
+        assert_eq!(win_set(&mut score, Side::Left), None);
+        assert_eq!(score.sets, [1, 0, 0, 0]);
+        assert_eq!(score.points, [0; 4]);
+        assert_eq!(win_set(&mut score, Side::Right), None);
+        assert_eq!(score.sets, [1, 1, 0, 0]);
+        assert_eq!(win_set(&mut score, Side::Right), Some(Side::Right));
+        assert_eq!(score.sets, [1, 2, 0, 0]);
+        assert_eq!(score.winner, Some(Side::Right));
+    }
+
+    #[test]
+    fn paddle_speeds_up_and_slows_down_over_time() {
+        let rules = GameRules::default();
+        let mut paddle = Paddle::new(Side::Left, &rules);
+        let top_speed = rules.paddle_max_speed;
+
+        // Pushed, the paddle gains `paddle_acceleration` every second.
+        paddle.update(1.0, 0.05, &rules);
+        assert!((paddle.velocity - rules.paddle_acceleration * 0.05).abs() < 1e-4);
+        assert!(paddle.velocity < top_speed);
+        let start = paddle.position[1];
+        for _ in 0..20 {
+            paddle.update(1.0, 0.01, &rules);
+        }
+        assert_eq!(paddle.velocity, top_speed);
+        assert!(paddle.position[1] > start);
+
+        // Pushed halfway, it heads for half its top speed.
+        paddle.update(0.5, 0.01, &rules);
+        let halfway = top_speed - rules.paddle_acceleration * 0.01;
+        assert!((paddle.velocity - halfway.max(top_speed * 0.5)).abs() < 1e-4);
+        paddle.steer(0.5, 1.0);
+        assert_eq!(paddle.velocity, top_speed * 0.5);
+
+        // Released, it loses `paddle_deceleration` every second until it stops.
+        paddle.update(0.0, 0.01, &rules);
+        let released = top_speed * 0.5 - rules.paddle_deceleration * 0.01;
+        assert!((paddle.velocity - released.max(0.0)).abs() < 1e-4);
+        paddle.steer(0.0, 1.0);
+        assert_eq!(paddle.velocity, 0.0);
+
+        // Against the end of its side, it stops dead.
+        paddle.update(1.0, 1.0, &rules);
+        assert_eq!(paddle.velocity, 0.0);
+        assert_eq!(paddle.position[1], rules.arena_height - paddle.length() * 0.5);
+    }
+
+    #[test]
+    fn every_ball_scores_on_its_own() {
+        let rules = GameRules {
+            ball_count: 3,
+            serve_stagger: 0.5,
+            ..GameRules::default()
+        };
+        let mut sim = Simulation::new(rules);
+        sim.paddles.clear();
+
+        let mut scored = Vec::new();
+        run_until(&mut sim, &Inputs::default(), 30.0, |_, events| {
+            for event in events {
+                if let SimEvent::Goal { ball, .. } = *event {
+                    scored.push(ball);
+                }
+            }
+            scored.len() == 3
+        });
+        scored.sort();
+        assert_eq!(scored, vec![0, 1, 2]);
+        assert_eq!(sim.score.points.iter().sum::<i32>(), 3);
+    }
+
+    #[test]
+    fn serves_are_staggered() {
+        let rules = GameRules {
+            ball_count: 3,
+            serve_stagger: 0.5,
+            ..GameRules::default()
+        };
+        let mut sim = Simulation::new(rules);
+        let waits = sim
+            .balls
+            .iter()
+            .map(|ball| ball.waiting_time)
+            .collect::<Vec<_>>();
+        assert_eq!(waits, vec![2.0, 2.5, 3.0]);
+        assert!(sim.balls[0].velocity[0] > 0.0 && sim.balls[1].velocity[0] < 0.0);
+
+        // A ball scoring while another one waits is served after it.
+        sim.balls[0].state = BallState::Moving;
+        sim.balls[0].position = [sim.rules.arena_width, 50.0];
+        sim.step(TIME_DELTA, &Inputs::default());
+        let others = sim.balls[1].waiting_time.max(sim.balls[2].waiting_time);
+        assert!(sim.balls[0].waiting_time >= others + 0.5);
+    }
+
+    /// Two balls heading at each other in the middle of the arena.
+    fn head_on(ball_collisions: bool) -> (Simulation, Vec<SimEvent>) {
+        let rules = GameRules {
+            ball_count: 2,
+            ball_collisions,
+            ..GameRules::default()
+        };
+        let mut sim = Simulation::new(rules);
+        for (ball, (x, velocity_x)) in sim
+            .balls
+            .iter_mut()
+            .zip([(40.0, 30.0), (60.0, -30.0)].iter())
+        {
+            ball.state = BallState::Moving;
+            ball.position = [*x, 50.0];
+            ball.velocity = [*velocity_x, 0.0];
+        }
+        let events = run_until(&mut sim, &Inputs::default(), 1.0, |sim, events| {
+            !events.is_empty() || sim.balls[0].position[0] > 55.0
+        });
+        (sim, events)
+    }
+
+    #[test]
+    fn balls_bounce_on_each_other() {
+        let (sim, events) = head_on(true);
+        assert_eq!(events, vec![SimEvent::BallHit { ball: 0, other: 1 }]);
+        assert!(sim.balls[0].velocity[0] < 0.0 && sim.balls[1].velocity[0] > 0.0);
+        assert!(sim.balls[0].position[0] < sim.balls[1].position[0]);
+    }
+
+    #[test]
+    fn balls_pass_through_each_other_without_collisions() {
+        let (sim, events) = head_on(false);
+        assert!(events.is_empty());
+        assert!(sim.balls[0].velocity[0] > 0.0 && sim.balls[1].velocity[0] < 0.0);
+    }
+
+    fn four_player_rules() -> GameRules {
+        GameRules {
+            players: 4,
+            ..GameRules::default()
+        }
+    }
+
+    #[test]
+    fn top_and_bottom_paddles_move_sideways() {
+        let mut sim = Simulation::new(four_player_rules());
+        assert_eq!(sim.paddles.len(), 4);
+        let inputs = Inputs {
+            top: 1.0,
+            bottom: -1.0,
+            ..Inputs::default()
+        };
+        for _ in 0..60 {
+            sim.step(TIME_DELTA, &inputs);
+        }
+        let top = sim.paddle(Side::Top).unwrap();
+        let bottom = sim.paddle(Side::Bottom).unwrap();
+        assert_eq!(top.width, 16.0);
+        assert_eq!(top.position[0], sim.rules.arena_width - 8.0);
+        assert_eq!(bottom.position[0], 8.0);
+        assert_eq!(top.position[1], sim.rules.arena_height - 2.0);
+    }
+
+    #[test]
+    fn top_goal_gives_the_point_to_the_last_hitter() {
+        let mut sim = Simulation::new(four_player_rules());
+        sim.paddles.retain(|paddle| paddle.side != Side::Top);
+        let ball = &mut sim.balls[0];
+        ball.state = BallState::Moving;
+        ball.velocity = [0.0, 30.0];
+        ball.last_hit = Some(Side::Left);
+
+        let events = run_until(&mut sim, &Inputs::default(), 3.0, |_, events| {
+            events.iter().any(|e| matches!(e, SimEvent::Goal { .. }))
+        });
+        assert!(events.contains(&SimEvent::Goal {
+            ball: 0,
+            scorer: Side::Left
+        }));
+        assert!(!events.iter().any(|e| matches!(e, SimEvent::WallBounce { .. })));
+        assert_eq!(sim.score.points, [1, 0, 0, 0]);
+
+        // Nobody returned this one, the bottom player gets the point.
+        let ball = &mut sim.balls[0];
+        ball.state = BallState::Moving;
+        ball.velocity = [0.0, 30.0];
+        run_until(&mut sim, &Inputs::default(), 3.0, |_, events| {
+            events.iter().any(|e| matches!(e, SimEvent::Goal { .. }))
+        });
+        assert_eq!(sim.score.points(Side::Bottom), 1);
+    }
+
+    #[test]
+    fn set_needs_a_lead_over_every_player() {
+        let mut rules = four_player_rules();
+        rules.points_to_win = 2;
+        let mut score = ScoreBoard {
+            points: [1, 0, 1, 0],
+            ..ScoreBoard::default()
+        };
+        score.award_point(Side::Left, &rules);
+        assert_eq!(score.sets(Side::Left), 0);
+        score.award_point(Side::Left, &rules);
+        assert_eq!(score.sets(Side::Left), 1);
+        assert_eq!(score.points, [0; 4]);
+    }
+
+    fn powerup_rules() -> GameRules {
+        let mut rules = GameRules::default();
+        rules.powerups.enabled = PowerUpKind::ALL.to_vec();
+        rules.powerups.spawn_interval = 1.0;
+        rules
+    }
+
+    #[test]
+    fn powerups_spawn_from_the_seed() {
+        let spawned = |seed| {
+            let mut sim = Simulation::seeded(powerup_rules(), seed);
+            run_until(&mut sim, &Inputs::default(), 5.0, |sim, _| {
+                !sim.powerups.is_empty()
+            });
+            sim.powerups[0]
+        };
+        assert_eq!(spawned(7), spawned(7));
+        assert_ne!(spawned(7), spawned(8));
+
+        let powerup = spawned(7);
+        assert!((powerup.position[0] - 50.0).abs() <= 10.0);
+        assert!(powerup.position[1] >= 3.0 && powerup.position[1] <= 97.0);
+    }
+
+    /// A match with a `kind` power-up right in front of a moving ball.
+    fn ball_heading_to(kind: PowerUpKind, last_hit: Option<Side>) -> Simulation {
+        let mut rules = powerup_rules();
+        rules.ball_velocity_y = 0.0;
+        let mut sim = Simulation::new(rules);
+        let ball = &mut sim.balls[0];
+        ball.state = BallState::Moving;
+        ball.last_hit = last_hit;
+        sim.powerups.push(PowerUp {
+            kind,
+            position: [60.0, 50.0],
+        });
+        sim
+    }
+
+    #[test]
+    fn ball_gives_the_powerup_to_the_last_hitter() {
+        let mut sim = ball_heading_to(PowerUpKind::LongPaddle, Some(Side::Left));
+        run_until(&mut sim, &Inputs::default(), 1.0, |_, events| {
+            events.contains(&SimEvent::PowerUpCollected {
+                kind: PowerUpKind::LongPaddle,
+                side: Side::Left,
+            })
+        });
+        assert!(sim.powerups.is_empty());
+        assert_eq!(sim.effects[0].side, Side::Left);
+
+        // Nobody returned this ball yet, it goes through.
+        let mut sim = ball_heading_to(PowerUpKind::LongPaddle, None);
+        run_until(&mut sim, &Inputs::default(), 1.0, |sim, _| {
+            sim.balls[0].position[0] > 70.0
+        });
+        assert_eq!(sim.powerups.len(), 1);
+        assert!(sim.effects.is_empty());
+    }
+
+    #[test]
+    fn effects_are_undone_when_they_expire() {
+        let mut sim = Simulation::new(powerup_rules());
+        sim.effects.push(Effect {
+            kind: PowerUpKind::LongPaddle,
+            side: Side::Left,
+            remaining: 0.5,
+        });
+        sim.step(TIME_DELTA, &Inputs::default());
+        assert_eq!(sim.paddle(Side::Left).unwrap().length(), 24.0);
+        assert_eq!(sim.paddle(Side::Right).unwrap().length(), 16.0);
+
+        run_until(&mut sim, &Inputs::default(), 1.0, |_, events| {
+            events.contains(&SimEvent::PowerUpExpired {
+                kind: PowerUpKind::LongPaddle,
+                side: Side::Left,
+            })
+        });
+        assert!(sim.effects.is_empty());
+        assert_eq!(sim.paddle(Side::Left).unwrap().length(), 16.0);
+    }
+
+    #[test]
+    fn split_ball_copy_leaves_with_the_effect() {
+        let mut sim = ball_heading_to(PowerUpKind::SplitBall, Some(Side::Left));
+        run_until(&mut sim, &Inputs::default(), 1.0, |sim, _| {
+            sim.balls.len() == 2
+        });
+        assert!(sim.balls[1].extra);
+        assert_eq!(sim.balls[1].position, sim.balls[0].position);
+
+        sim.effects[0].remaining = TIME_DELTA * 0.5;
+        sim.step(TIME_DELTA, &Inputs::default());
+        assert_eq!(sim.balls.len(), 1);
+        assert!(!sim.balls[0].extra);
+    }
+
+    #[test]
+    fn shield_closes_the_goal() {
+        let mut rules = powerup_rules();
+        rules.ball_velocity_y = 0.0;
+        let mut sim = Simulation::new(rules);
+        sim.paddles.clear();
+        sim.balls[0].state = BallState::Moving;
+        sim.effects.push(Effect {
+            kind: PowerUpKind::Shield,
+            side: Side::Right,
+            remaining: 10.0,
+        });
+
+        run_until(&mut sim, &Inputs::default(), 3.0, |sim, _| {
+            sim.balls[0].velocity[0] < 0.0
+        });
+        assert_eq!(sim.score, ScoreBoard::default());
+    }
+
+    #[test]
+    fn reversed_controls_move_the_other_paddle_backwards() {
+        let mut sim = Simulation::new(powerup_rules());
+        sim.effects.push(Effect {
+            kind: PowerUpKind::ReversedControls,
+            side: Side::Left,
+            remaining: 10.0,
+        });
+        let inputs = Inputs {
+            left: 1.0,
+            right: 1.0,
+            ..Inputs::default()
+        };
+        sim.step(TIME_DELTA, &inputs);
+        assert!(sim.paddle(Side::Left).unwrap().velocity > 0.0);
+        assert!(sim.paddle(Side::Right).unwrap().velocity < 0.0);
+    }
+
+    /// A match in `level` with the ball served straight to the right.
+    fn level_match(level: Level) -> Simulation {
+        let rules = GameRules {
+            ball_velocity_y: 0.0,
+            ..GameRules::default()
+        };
+        let mut sim = Simulation::new(rules).with_level(level);
+        sim.balls[0].state = BallState::Moving;
+        sim
+    }
+
+    #[test]
+    fn ball_bounces_on_an_obstacle() {
+        let mut sim = level_match(Level {
+            obstacles: vec![Obstacle {
+                position: [70.0, 50.0],
+                size: [4.0, 20.0],
+                travel: [0.0, 0.0],
+                period: 0.0,
+            }],
+            ..Level::default()
+        });
+        run_until(&mut sim, &Inputs::default(), 2.0, |_, events| {
+            events.contains(&SimEvent::ObstacleHit { ball: 0 })
+        });
+        assert!(sim.balls[0].velocity[0] < 0.0);
+        assert!(sim.balls[0].position[0] < 68.0);
+    }
+
+    #[test]
+    fn bumper_sends_the_ball_back_faster() {
+        let mut sim = level_match(Level {
+            bumpers: vec![Bumper {
+                position: [70.0, 50.0],
+                radius: 4.0,
+                boost: 1.5,
+            }],
+            ..Level::default()
+        });
+        let speed = sim.balls[0].velocity[0];
+        run_until(&mut sim, &Inputs::default(), 2.0, |_, events| {
+            events.contains(&SimEvent::BumperHit { ball: 0 })
+        });
+        assert!(sim.balls[0].velocity[0] < -speed);
+    }
+
+    #[test]
+    fn portal_moves_the_ball_to_the_other_end() {
+        let mut sim = level_match(Level {
+            portals: vec![Portal {
+                ends: [[70.0, 50.0], [20.0, 50.0]],
+                radius: 4.0,
+            }],
+            ..Level::default()
+        });
+        let velocity = sim.balls[0].velocity;
+        run_until(&mut sim, &Inputs::default(), 2.0, |_, events| {
+            events.contains(&SimEvent::PortalUsed { ball: 0 })
+        });
+        assert_eq!(sim.balls[0].position, [20.0, 50.0]);
+        assert_eq!(sim.balls[0].velocity, velocity);
+
+        // The ball leaves the end it came out of without going back.
+        let events = sim.step(TIME_DELTA, &Inputs::default());
+        assert!(!events.contains(&SimEvent::PortalUsed { ball: 0 }));
+    }
+
+    /// Plays ten seconds of a match a frame at a time, as the
+    /// `SimulationSystem` does. `inputs` gives the inputs of every tick from
+    /// the time the frame started at and the number of the tick. Returns the
+    /// simulation at the end and the inputs every tick played.
+    fn play_frames<F>(frame_times: &[f32], mut inputs: F) -> (Simulation, Vec<Inputs>)
+    where
+        F: FnMut(f32, usize) -> Inputs,
+    {
+        let ticks = (10 * TICK_RATE) as usize;
+        let mut sim = Simulation::new(GameRules::default());
+        let mut step = FixedStep::default();
+        let mut played = Vec::new();
+        let mut time = 0.0;
+        let mut frames = frame_times.iter().cycle();
+        while played.len() < ticks {
+            let frame_time = *frames.next().unwrap();
+            for _ in 0..step.advance(frame_time) {
+                if played.len() == ticks {
+                    break;
+                }
+                let tick_inputs = inputs(time, played.len());
+                sim.step(TICK, &tick_inputs);
+                played.push(tick_inputs);
+            }
+            time += frame_time;
+        }
+        (sim, played)
+    }
+
+    #[test]
+    fn fixed_step_runs_whole_ticks() {
+        let mut step = FixedStep::default();
+        assert_eq!(step.advance(TICK * 0.5), 0);
+        assert!((step.alpha() - 0.5).abs() < 1e-4);
+        assert_eq!(step.advance(TICK * 2.0), 2);
+        // A long hitch is capped instead of being caught up.
+        assert_eq!(step.advance(10.0), MAX_TICKS_PER_FRAME);
+    }
+
+    #[test]
+    fn same_inputs_give_the_same_match_at_any_frame_rate() {
+        // Players are read once a frame, every tick of the frame plays what
+        // they did.
+        let players = |time: f32, _| Inputs {
+            left: (time * 3.0).sin(),
+            right: (time * 2.0).cos(),
+            ..Inputs::default()
+        };
+        let uneven = [1.0 / 144.0, 1.0 / 30.0, 0.0, 1.0 / 75.0, 0.05];
+        let (live, played) = play_frames(&uneven, players);
+        let (_, smooth) = play_frames(&[1.0 / 60.0], players);
+        assert_ne!(played, smooth);
+
+        // Replayed a tick at a time at another frame rate, as a replay is,
+        // the inputs of every tick give the same match.
+        let (replayed, _) = play_frames(&[1.0 / 60.0], |_, tick| played[tick]);
+        assert_eq!(replayed, live);
+    }
+
+    #[test]
+    fn same_seed_gives_the_same_numbers() {
+        use rand::Rng;
+
+        let mut a = SimRng::new(42);
+        let mut b = SimRng::new(42);
+        let mut c = SimRng::new(43);
+        let a = (0..16).map(|_| a.gen::<u64>()).collect::<Vec<_>>();
+        let b = (0..16).map(|_| b.gen::<u64>()).collect::<Vec<_>>();
+        let c = (0..16).map(|_| c.gen::<u64>()).collect::<Vec<_>>();
+        assert_eq!(a, b);
+        assert_ne!(a, c);
+    }
+
+    #[test]
+    fn computer_players_do_not_draw_the_numbers_of_the_match() {
+        use rand::Rng;
+
+        let mut sim = SimRng::new(42);
+        let mut computer = SimRng::computer(42);
+        let sim = (0..64).map(|_| sim.gen::<u64>()).collect::<Vec<_>>();
+        let computer = (0..64).map(|_| computer.gen::<u64>()).collect::<Vec<_>>();
+        assert!(computer.iter().all(|number| !sim.contains(number)));
+    }
+}
@@ -0,0 +1,153 @@
+use serde::{Deserialize, Serialize};
+
+use crate::rules::GameRules;
+
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Eq)]
+pub enum Side {
+    Left,
+    Right,
+    Top,
+    Bottom,
+}
+
+impl Side {
+    /// Every side, the first two are played in every match, the last two only
+    /// by four players.
+    pub const ALL: [Side; 4] = [Side::Left, Side::Right, Side::Top, Side::Bottom];
+
+    /// Position of the side in `Side::ALL`.
+    pub fn index(self) -> usize {
+        match self {
+            Side::Left => 0,
+            Side::Right => 1,
+            Side::Top => 2,
+            Side::Bottom => 3,
+        }
+    }
+
+    /// Coordinate the paddle of this side moves along: 1 (y) for the left and
+    /// right paddles, 0 (x) for the top and bottom ones.
+    pub fn axis(self) -> usize {
+        match self {
+            Side::Left | Side::Right => 1,
+            Side::Top | Side::Bottom => 0,
+        }
+    }
+
+    /// Coordinate across the paddle of this side, the one a ball returned by
+    /// it goes along.
+    pub fn across(self) -> usize {
+        1 - self.axis()
+    }
+
+    /// Direction, on the `across` axis, of a ball returned by this side.
+    pub fn direction(self) -> f32 {
+        match self {
+            Side::Left | Side::Bottom => 1.0,
+            Side::Right | Side::Top => -1.0,
+        }
+    }
+
+    /// The side across the arena.
+    pub fn opponent(self) -> Side {
+        match self {
+            Side::Left => Side::Right,
+            Side::Right => Side::Left,
+            Side::Top => Side::Bottom,
+            Side::Bottom => Side::Top,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Paddle {
+    pub side: Side,
+    /// Center of the paddle.
+    pub position: [f32; 2],
+    /// Center of the paddle before it moved during the last step.
+    pub last_position: [f32; 2],
+    pub width: f32,
+    pub height: f32,
+    /// Speed of the paddle along its axis, in units per second.
+    pub velocity: f32,
+    pub max_speed: f32,
+    pub acceleration: f32,
+    pub deceleration: f32,
+}
+
+impl Paddle {
+    /// Creates a paddle in the middle of its side. The top and bottom paddles
+    /// lie along their wall, `paddle_height` is their width.
+    pub fn new(side: Side, rules: &GameRules) -> Paddle {
+        let pivot = rules.paddle_width * 0.5;
+        let position = match side {
+            Side::Left => [pivot, rules.halve_height()],
+            Side::Right => [rules.arena_width - pivot, rules.halve_height()],
+            Side::Top => [rules.halve_width(), rules.arena_height - pivot],
+            Side::Bottom => [rules.halve_width(), pivot],
+        };
+        let mut paddle = Paddle {
+            side,
+            position,
+            last_position: position,
+            width: rules.paddle_width,
+            height: rules.paddle_width,
+            velocity: 0.0,
+            max_speed: rules.paddle_max_speed,
+            acceleration: rules.paddle_acceleration,
+            deceleration: rules.paddle_deceleration,
+        };
+        paddle.set_length(rules.paddle_height);
+        paddle
+    }
+
+    /// Size of the paddle along its axis.
+    pub fn length(&self) -> f32 {
+        match self.side.axis() {
+            0 => self.width,
+            _ => self.height,
+        }
+    }
+
+    pub fn set_length(&mut self, length: f32) {
+        match self.side.axis() {
+            0 => self.width = length,
+            _ => self.height = length,
+        }
+    }
+
+    /// Moves the velocity towards `input` times the top speed, an input between
+    /// -1 and 1. The paddle accelerates while pushed and slows down once released.
+    pub fn steer(&mut self, input: f32, time_delta: f32) {
+        let target = input.clamp(-1.0, 1.0) * self.max_speed;
+        let rate = if input == 0.0 {
+            self.deceleration
+        } else {
+            self.acceleration
+        };
+        let step = rate * time_delta;
+        self.velocity = if self.velocity < target {
+            (self.velocity + step).min(target)
+        } else {
+            (self.velocity - step).max(target)
+        };
+    }
+
+    /// Steers the paddle and moves it for `time_delta` seconds, a positive
+    /// input moves it up or right. The paddle stops dead against the ends of
+    /// its side of the arena.
+    pub fn update(&mut self, input: f32, time_delta: f32, rules: &GameRules) {
+        self.last_position = self.position;
+        self.steer(input, time_delta);
+
+        let axis = self.side.axis();
+        let span = [rules.arena_width, rules.arena_height][axis];
+        let min = self.length() * 0.5;
+        let max = span - self.length() * 0.5;
+        let coordinate = self.position[axis] + self.velocity * time_delta;
+        if coordinate <= min || coordinate >= max {
+            self.velocity = 0.0;
+        }
+        self.position[axis] = coordinate.min(max).max(min);
+    }
+}
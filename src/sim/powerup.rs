@@ -0,0 +1,224 @@
+//! Power-ups that appear in the middle of the arena.
+//!
+//! A ball touching a power-up gives it to the side that hit the ball last, a
+//! ball nobody returned yet goes through it. Every effect lasts `duration`
+//! seconds and is undone when it runs out.
+
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::rules::{GameRules, MAX_BALLS};
+use crate::sim::{Ball, BallState, Side, SimEvent, SimRng};
+
+/// Width of the wall a shield puts in front of a goal.
+pub const SHIELD_WIDTH: f32 = 1.0;
+
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Eq)]
+pub enum PowerUpKind {
+    /// The paddle of the player gets longer.
+    LongPaddle,
+    /// The paddles of the opponents get shorter.
+    ShortPaddle,
+    /// Every ball moves faster.
+    FastBall,
+    /// The ball splits in two, the copy leaves the match when the effect ends.
+    SplitBall,
+    /// The controls of the opponents go the other way.
+    ReversedControls,
+    /// A wall closes the goal of the player.
+    Shield,
+}
+
+impl PowerUpKind {
+    pub const ALL: [PowerUpKind; 6] = [
+        PowerUpKind::LongPaddle,
+        PowerUpKind::ShortPaddle,
+        PowerUpKind::FastBall,
+        PowerUpKind::SplitBall,
+        PowerUpKind::ReversedControls,
+        PowerUpKind::Shield,
+    ];
+}
+
+/// A power-up waiting in the arena to be picked up.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PowerUp {
+    pub kind: PowerUpKind,
+    pub position: [f32; 2],
+}
+
+/// A power-up picked up by `side`, active for `remaining` more seconds.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Effect {
+    pub kind: PowerUpKind,
+    pub side: Side,
+    pub remaining: f32,
+}
+
+/// Length of the paddle of `side`, as a fraction of `paddle_height`.
+pub fn paddle_scale(effects: &[Effect], side: Side, rules: &GameRules) -> f32 {
+    effects.iter().fold(1.0, |scale, effect| match effect.kind {
+        PowerUpKind::LongPaddle if effect.side == side => scale * rules.powerups.long_paddle_scale,
+        PowerUpKind::ShortPaddle if effect.side != side => {
+            scale * rules.powerups.short_paddle_scale
+        }
+        _ => scale,
+    })
+}
+
+/// Whether an opponent of `side` reversed its controls.
+pub fn is_reversed(effects: &[Effect], side: Side) -> bool {
+    effects
+        .iter()
+        .any(|effect| effect.kind == PowerUpKind::ReversedControls && effect.side != side)
+}
+
+/// Speed of the balls, as a fraction of their velocity.
+pub fn ball_speed_scale(effects: &[Effect], rules: &GameRules) -> f32 {
+    if effects
+        .iter()
+        .any(|effect| effect.kind == PowerUpKind::FastBall)
+    {
+        rules.powerups.fast_ball_scale
+    } else {
+        1.0
+    }
+}
+
+/// Sides whose goal is closed by a shield.
+pub fn shields(effects: &[Effect]) -> Vec<Side> {
+    effects
+        .iter()
+        .filter(|effect| effect.kind == PowerUpKind::Shield)
+        .map(|effect| effect.side)
+        .collect()
+}
+
+/// Counts the effects down and removes the ones that ran out. The copies of
+/// the split balls leave the match with the last split effect.
+pub(super) fn expire(
+    effects: &mut Vec<Effect>,
+    balls: &mut Vec<Ball>,
+    time_delta: f32,
+    events: &mut Vec<SimEvent>,
+) {
+    let mut split_ended = false;
+    effects.retain(|effect| {
+        if effect.remaining > time_delta {
+            return true;
+        }
+        events.push(SimEvent::PowerUpExpired {
+            kind: effect.kind,
+            side: effect.side,
+        });
+        split_ended |= effect.kind == PowerUpKind::SplitBall;
+        false
+    });
+    for effect in effects.iter_mut() {
+        effect.remaining -= time_delta;
+    }
+
+    if split_ended
+        && !effects
+            .iter()
+            .any(|effect| effect.kind == PowerUpKind::SplitBall)
+    {
+        balls.retain(|ball| !ball.extra);
+    }
+}
+
+/// Gives the power-ups touched by a ball to the side that hit it last.
+pub(super) fn collect(
+    powerups: &mut Vec<PowerUp>,
+    effects: &mut Vec<Effect>,
+    balls: &mut Vec<Ball>,
+    rules: &GameRules,
+    events: &mut Vec<SimEvent>,
+) {
+    let settings = &rules.powerups;
+    let mut index = 0;
+    while index < powerups.len() {
+        let PowerUp { kind, position } = powerups[index];
+        let collector = balls.iter().enumerate().find_map(|(ball_index, ball)| {
+            let side = ball.last_hit?;
+            let offset = [
+                ball.position[0] - position[0],
+                ball.position[1] - position[1],
+            ];
+            let reach = ball.radius + settings.radius;
+            if ball.state == BallState::Moving
+                && offset[0] * offset[0] + offset[1] * offset[1] < reach * reach
+            {
+                Some((ball_index, side))
+            } else {
+                None
+            }
+        });
+        let (ball_index, side) = match collector {
+            Some(collector) => collector,
+            None => {
+                index += 1;
+                continue;
+            }
+        };
+
+        powerups.remove(index);
+        events.push(SimEvent::PowerUpCollected { kind, side });
+        if kind == PowerUpKind::SplitBall && balls.len() < MAX_BALLS as usize {
+            let mut copy = balls[ball_index].clone();
+            copy.velocity[1] = if copy.velocity[1] == 0.0 {
+                rules.ball_velocity_y
+            } else {
+                -copy.velocity[1]
+            };
+            copy.extra = true;
+            balls.push(copy);
+        }
+
+        // Picking up an active power-up again only makes it last longer.
+        match effects
+            .iter_mut()
+            .find(|effect| effect.kind == kind && effect.side == side)
+        {
+            Some(effect) => effect.remaining = settings.duration,
+            None => effects.push(Effect {
+                kind,
+                side,
+                remaining: settings.duration,
+            }),
+        }
+    }
+}
+
+/// Counts down to the next power-up and puts it near the middle of the arena.
+pub(super) fn spawn(
+    powerups: &mut Vec<PowerUp>,
+    next_spawn: &mut f32,
+    rng: &mut SimRng,
+    rules: &GameRules,
+    time_delta: f32,
+    events: &mut Vec<SimEvent>,
+) {
+    let settings = &rules.powerups;
+    if settings.enabled.is_empty() {
+        return;
+    }
+    *next_spawn -= time_delta;
+    if *next_spawn > 0.0 {
+        return;
+    }
+    *next_spawn = settings.spawn_interval * rng.gen_range(0.5, 1.5);
+    // A full arena skips this power-up.
+    if powerups.len() >= settings.max_pickups as usize {
+        return;
+    }
+
+    let kind = settings.enabled[rng.gen_range(0, settings.enabled.len())];
+    let spread = rules.arena_width * 0.1;
+    let position = [
+        rules.halve_width() + rng.gen_range(-spread, spread),
+        rng.gen_range(settings.radius, rules.arena_height - settings.radius),
+    ];
+    powerups.push(PowerUp { kind, position });
+    events.push(SimEvent::PowerUpSpawned { kind });
+}
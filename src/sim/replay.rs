@@ -0,0 +1,160 @@
+use serde::{Deserialize, Serialize};
+
+use crate::level::Level;
+use crate::rules::GameRules;
+use crate::sim::{Inputs, ScoreBoard};
+
+/// Version of the replay format. Replays of another version are refused,
+/// they would not play the same match.
+pub const REPLAY_VERSION: u32 = 3;
+
+/// A recorded match: the rules, the level and the inputs of every tick are enough to
+/// play it again exactly, the final score tells whether it did. The inputs of
+/// the computer players are recorded as well, the seed is only kept to play
+/// the same match again with them.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct Replay {
+    pub version: u32,
+    pub seed: u64,
+    pub rules: GameRules,
+    pub level: Level,
+    /// Inputs of every tick, consecutive ticks with the same inputs are stored once.
+    pub inputs: Vec<InputRun>,
+    /// Score at the end of the recording.
+    pub score: ScoreBoard,
+}
+
+/// The same inputs held for `ticks` ticks in a row.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq)]
+pub struct InputRun {
+    pub ticks: u32,
+    pub left: f32,
+    pub right: f32,
+    #[serde(default)]
+    pub top: f32,
+    #[serde(default)]
+    pub bottom: f32,
+}
+
+impl InputRun {
+    fn inputs(&self) -> Inputs {
+        Inputs {
+            left: self.left,
+            right: self.right,
+            top: self.top,
+            bottom: self.bottom,
+        }
+    }
+}
+
+impl Replay {
+    /// Starts an empty recording of a match.
+    pub fn new(seed: u64, rules: GameRules, level: Level) -> Replay {
+        Replay {
+            version: REPLAY_VERSION,
+            seed,
+            rules,
+            level,
+            inputs: Vec::new(),
+            score: ScoreBoard::default(),
+        }
+    }
+
+    /// Appends the inputs of one tick.
+    pub fn record(&mut self, inputs: &Inputs) {
+        match self.inputs.last_mut() {
+            Some(run) if run.inputs() == *inputs => run.ticks += 1,
+            _ => self.inputs.push(InputRun {
+                ticks: 1,
+                left: inputs.left,
+                right: inputs.right,
+                top: inputs.top,
+                bottom: inputs.bottom,
+            }),
+        }
+    }
+
+    /// Number of recorded ticks.
+    pub fn ticks(&self) -> u64 {
+        self.inputs.iter().map(|run| u64::from(run.ticks)).sum()
+    }
+}
+
+/// Position of a replay being played, one tick at a time.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ReplayCursor {
+    run: usize,
+    tick: u32,
+}
+
+impl ReplayCursor {
+    /// Returns the inputs of the next tick, or `None` once the replay is over.
+    pub fn next(&mut self, replay: &Replay) -> Option<Inputs> {
+        while let Some(run) = replay.inputs.get(self.run) {
+            if self.tick < run.ticks {
+                self.tick += 1;
+                return Some(run.inputs());
+            }
+            self.run += 1;
+            self.tick = 0;
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sim::{Simulation, TICK};
+
+    /// Plays the whole replay without rendering it and returns the match at
+    /// the end, its score should be the recorded one.
+    fn simulate(replay: &Replay) -> Simulation {
+        let mut sim =
+            Simulation::seeded(replay.rules.clone(), replay.seed).with_level(replay.level.clone());
+        let mut cursor = ReplayCursor::default();
+        while let Some(inputs) = cursor.next(replay) {
+            sim.step(TICK, &inputs);
+        }
+        sim
+    }
+
+    fn inputs(tick: u32) -> Inputs {
+        Inputs {
+            left: if tick % 240 < 120 { 1.0 } else { -1.0 },
+            right: if tick % 100 < 30 { 0.0 } else { -1.0 },
+            ..Inputs::default()
+        }
+    }
+
+    #[test]
+    fn consecutive_identical_inputs_are_stored_once() {
+        let mut replay = Replay::new(0, GameRules::default(), Level::default());
+        for tick in 0..240 {
+            replay.record(&inputs(tick));
+        }
+        assert_eq!(replay.ticks(), 240);
+        assert!(replay.inputs.len() < 10);
+
+        let mut cursor = ReplayCursor::default();
+        for tick in 0..240 {
+            assert_eq!(cursor.next(&replay), Some(inputs(tick)));
+        }
+        assert_eq!(cursor.next(&replay), None);
+    }
+
+    #[test]
+    fn replay_plays_the_recorded_match_again() {
+        let rules = GameRules::default();
+        let mut replay = Replay::new(7, rules.clone(), Level::default());
+        let mut sim = Simulation::seeded(rules, replay.seed);
+        for tick in 0..60 * crate::sim::TICK_RATE {
+            let inputs = inputs(tick);
+            replay.record(&inputs);
+            sim.step(TICK, &inputs);
+        }
+        replay.score = sim.score.clone();
+
+        assert_eq!(simulate(&replay), sim);
+    }
+}
@@ -0,0 +1,50 @@
+use rand::{Error, RngCore};
+
+/// Mixed into the seed of a match for the generator of the computer players.
+const COMPUTER_STREAM: u64 = 0x6a09_e667_f3bc_c909;
+
+/// SimRng gives the random numbers of a match, to the simulation and to the
+/// computer players. It is a SplitMix64 generator, so a seed gives the same
+/// numbers on every platform and with every version of `rand`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SimRng {
+    state: u64,
+}
+
+impl SimRng {
+    pub fn new(seed: u64) -> SimRng {
+        SimRng { state: seed }
+    }
+
+    /// Generator of the computer players of the match seeded with `seed`,
+    /// apart from the one of the simulation so they do not draw the same numbers.
+    pub fn computer(seed: u64) -> SimRng {
+        SimRng::new(seed ^ COMPUTER_STREAM)
+    }
+}
+
+impl RngCore for SimRng {
+    fn next_u32(&mut self) -> u32 {
+        (self.next_u64() >> 32) as u32
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        for chunk in dest.chunks_mut(8) {
+            let bytes = self.next_u64().to_le_bytes();
+            chunk.copy_from_slice(&bytes[..chunk.len()]);
+        }
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
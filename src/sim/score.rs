@@ -0,0 +1,67 @@
+use serde::{Deserialize, Serialize};
+
+use crate::rules::GameRules;
+use crate::sim::Side;
+
+/// ScoreBoard contains the actual score data, the points and won sets of
+/// every player are indexed by `Side::index`.
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq)]
+pub struct ScoreBoard {
+    pub points: [i32; 4],
+    pub sets: [i32; 4],
+    /// Set once a player has won the match, no more points are played after that.
+    pub winner: Option<Side>,
+}
+
+impl ScoreBoard {
+    pub fn points(&self, side: Side) -> i32 {
+        self.points[side.index()]
+    }
+
+    pub fn sets(&self, side: Side) -> i32 {
+        self.sets[side.index()]
+    }
+
+    /// The points of `sides`, like "11 - 7", and their sets when some were won.
+    pub fn describe(&self, sides: &[Side]) -> String {
+        let join = |values: &[i32; 4]| {
+            sides
+                .iter()
+                .map(|side| values[side.index()].to_string())
+                .collect::<Vec<_>>()
+                .join(" - ")
+        };
+        if self.sets.iter().any(|sets| *sets > 0) {
+            format!("sets {}, points {}", join(&self.sets), join(&self.points))
+        } else {
+            join(&self.points)
+        }
+    }
+
+    /// Gives a point to `scorer`. The points go back to zero when the set is won,
+    /// and the winner is returned once the match is won. A set needs a lead
+    /// over every other player.
+    pub fn award_point(&mut self, scorer: Side, rules: &GameRules) -> Option<Side> {
+        let index = scorer.index();
+        self.points[index] += 1;
+
+        let score = self.points[index];
+        let best_other = rules
+            .sides()
+            .iter()
+            .filter(|side| **side != scorer)
+            .map(|side| self.points(*side))
+            .max()
+            .unwrap_or(0);
+        let lead = if rules.win_by_two { 2 } else { 1 };
+        if score >= rules.points_to_win && score - best_other >= lead {
+            self.sets[index] += 1;
+            if self.sets[index] >= rules.sets_to_win() {
+                self.winner = Some(scorer);
+            }
+            self.points = [0; 4];
+        }
+
+        self.winner
+    }
+}
@@ -0,0 +1,187 @@
+use amethyst::{
+    ecs::prelude::Entity,
+    input::{
+        is_close_requested, Button, ControllerButton, InputEvent, InputHandler, StringBindings,
+        VirtualKeyCode,
+    },
+    prelude::*,
+    ui::UiText,
+};
+
+use crate::bindings::{button_name, rebind, BindingsPath, Control};
+use crate::states::{create_label, delete_entities, is_action, load_font, ACCEPT, BACK};
+
+const LINE_HEIGHT: f32 = 26.;
+const FONT_SIZE: f32 = 18.;
+
+/// ControlsMenu lists the keys and buttons of `config/bindings.ron`. Up and
+/// down pick a control, accepting it waits for the next key or button pressed
+/// and binds it to the control. The new bindings are used and saved at once.
+#[derive(Default)]
+pub struct ControlsMenu {
+    ui: Vec<Entity>,
+    controls: Vec<Control>,
+    /// Label of every control, in the same order.
+    lines: Vec<Entity>,
+    message: Option<Entity>,
+    selected: usize,
+    /// Whether the next button pressed is bound to the selected control.
+    capturing: bool,
+    /// The button just bound. The actions it triggers are ignored until it
+    /// is released, it would leave the screen otherwise.
+    held: Option<Button>,
+}
+
+impl ControlsMenu {
+    fn create_ui(&mut self, world: &mut World) {
+        let font = load_font(world);
+        self.controls = Control::all(
+            &world
+                .read_resource::<InputHandler<StringBindings>>()
+                .bindings,
+        );
+        self.ui.push(create_label(
+            world,
+            font.clone(),
+            "controls_title",
+            "CONTROLS",
+            210.,
+            30.,
+        ));
+        let top = LINE_HEIGHT * (self.controls.len() as f32 - 1.) * 0.5 + 20.;
+        for index in 0..self.controls.len() {
+            let line = create_label(
+                world,
+                font.clone(),
+                &format!("control_{}", index),
+                "",
+                top - LINE_HEIGHT * index as f32,
+                FONT_SIZE,
+            );
+            self.lines.push(line);
+            self.ui.push(line);
+        }
+        let message = create_label(world, font, "controls_message", "", -215., FONT_SIZE);
+        self.message = Some(message);
+        self.ui.push(message);
+        self.refresh(world, "Enter or A: change, Escape or B: back");
+    }
+
+    /// Shows the current binding of every control, and `message` at the bottom.
+    fn refresh(&self, world: &mut World, message: &str) {
+        let input = world.read_resource::<InputHandler<StringBindings>>();
+        let mut texts = world.write_storage::<UiText>();
+        for (index, (control, line)) in self.controls.iter().zip(self.lines.iter()).enumerate() {
+            let button = match control.button(&input.bindings) {
+                Some(_) if self.capturing && index == self.selected => "...".to_string(),
+                Some(button) => button_name(button),
+                None => "-".to_string(),
+            };
+            let marker = if index == self.selected { ">" } else { " " };
+            if let Some(text) = texts.get_mut(*line) {
+                text.text = format!("{} {}: {}", marker, control.label(), button);
+            }
+        }
+        if let Some(text) = self.message.and_then(|message| texts.get_mut(message)) {
+            text.text = message.to_string();
+        }
+    }
+
+    /// Binds `button` to the selected control, then saves the bindings.
+    fn bind(&mut self, world: &mut World, button: Button) {
+        self.capturing = false;
+        self.held = Some(button);
+        let control = &self.controls[self.selected];
+        let result = {
+            let input = world.read_resource::<InputHandler<StringBindings>>();
+            rebind(&input.bindings, control, button)
+        };
+        let message = match result {
+            Ok((bindings, swapped)) => {
+                let path = world.read_resource::<BindingsPath>().0.clone();
+                let saved = bindings.write(&path);
+                world
+                    .write_resource::<InputHandler<StringBindings>>()
+                    .bindings = bindings;
+                match (saved, swapped) {
+                    (Err(e), _) => format!("could not save {}: {}", path.display(), e),
+                    (Ok(()), Some(other)) => format!("swapped with {}", other.label()),
+                    (Ok(()), None) => "saved".to_string(),
+                }
+            }
+            Err(e) => e.to_string(),
+        };
+        self.refresh(world, &message);
+    }
+}
+
+impl SimpleState for ControlsMenu {
+    fn on_start(&mut self, data: StateData<'_, GameData<'_, '_>>) {
+        self.create_ui(data.world);
+    }
+
+    fn on_stop(&mut self, data: StateData<'_, GameData<'_, '_>>) {
+        delete_entities(data.world, &mut self.ui);
+        self.lines.clear();
+        self.message = None;
+    }
+
+    fn handle_event(
+        &mut self,
+        data: StateData<'_, GameData<'_, '_>>,
+        event: StateEvent,
+    ) -> SimpleTrans {
+        let world = data.world;
+        if let StateEvent::Window(event) = &event {
+            if is_close_requested(event) {
+                return Trans::Quit;
+            }
+        }
+
+        let button = match &event {
+            StateEvent::Input(InputEvent::ButtonPressed(button)) => Some(*button),
+            StateEvent::Input(InputEvent::ButtonReleased(button)) => {
+                if self.held == Some(*button) {
+                    self.held = None;
+                }
+                None
+            }
+            _ => None,
+        };
+        if self.capturing {
+            if let Some(button) = button {
+                self.bind(world, button);
+            }
+            return Trans::None;
+        }
+        // The actions of the button just bound are not meant for the menu.
+        if self.held.is_some() {
+            return Trans::None;
+        }
+        if is_action(&event, BACK) {
+            return Trans::Pop;
+        }
+        if self.controls.is_empty() {
+            return Trans::None;
+        }
+
+        match button {
+            Some(Button::Key(VirtualKeyCode::Up))
+            | Some(Button::Controller(_, ControllerButton::DPadUp)) => {
+                self.selected = (self.selected + self.controls.len() - 1) % self.controls.len();
+                self.refresh(world, "");
+            }
+            Some(Button::Key(VirtualKeyCode::Down))
+            | Some(Button::Controller(_, ControllerButton::DPadDown)) => {
+                self.selected = (self.selected + 1) % self.controls.len();
+                self.refresh(world, "");
+            }
+            _ => {}
+        }
+        if is_action(&event, ACCEPT) {
+            self.capturing = true;
+            self.refresh(world, "Press the new key or button");
+        }
+        Trans::None
+    }
+}
@@ -0,0 +1,90 @@
+use amethyst::{ecs::prelude::Entity, input::is_close_requested, prelude::*};
+
+use crate::pong::MatchStats;
+use crate::sim::Side;
+use crate::states::{create_label, delete_entities, is_action, load_font, ACCEPT};
+
+/// GameOver replaces the match once it has a winner. Popping it goes back to
+/// the main menu, which is right below on the state stack.
+pub struct GameOver {
+    winner: Side,
+    ui: Vec<Entity>,
+}
+
+impl GameOver {
+    pub fn new(winner: Side) -> GameOver {
+        GameOver {
+            winner,
+            ui: Vec::new(),
+        }
+    }
+}
+
+impl SimpleState for GameOver {
+    fn on_start(&mut self, data: StateData<'_, GameData<'_, '_>>) {
+        let world = data.world;
+        let winner = match self.winner {
+            Side::Left => "Left player wins!",
+            Side::Right => "Right player wins!",
+            Side::Top => "Top player wins!",
+            Side::Bottom => "Bottom player wins!",
+        };
+
+        let font = load_font(world);
+        self.ui.push(create_label(
+            world,
+            font.clone(),
+            "game_over",
+            "GAME OVER",
+            60.,
+            50.,
+        ));
+        self.ui
+            .push(create_label(world, font.clone(), "winner", winner, 0., 30.));
+        // The rallies of the match, counted by the `StatsSystem`.
+        let stats = world
+            .try_fetch::<MatchStats>()
+            .map(|stats| (*stats).clone())
+            .unwrap_or_default();
+        let stats = format!(
+            "Longest rally: {} hits, fastest return: {:.0}",
+            stats.longest_rally, stats.fastest_hit
+        );
+        self.ui.push(create_label(
+            world,
+            font.clone(),
+            "stats",
+            &stats,
+            -30.,
+            15.,
+        ));
+        self.ui.push(create_label(
+            world,
+            font,
+            "back",
+            "Press Enter or A to go back to the menu",
+            -60.,
+            20.,
+        ));
+    }
+
+    fn on_stop(&mut self, data: StateData<'_, GameData<'_, '_>>) {
+        delete_entities(data.world, &mut self.ui);
+    }
+
+    fn handle_event(
+        &mut self,
+        _data: StateData<'_, GameData<'_, '_>>,
+        event: StateEvent,
+    ) -> SimpleTrans {
+        if let StateEvent::Window(event) = &event {
+            if is_close_requested(event) {
+                return Trans::Quit;
+            }
+        }
+        if is_action(&event, ACCEPT) {
+            return Trans::Pop;
+        }
+        Trans::None
+    }
+}
@@ -0,0 +1,134 @@
+use amethyst::{ecs::prelude::Entity, input::is_close_requested, prelude::*};
+
+use crate::audio::{initialise_audio, Sounds};
+use crate::pong::PongGame;
+use crate::settings;
+use crate::states::{
+    create_label, delete_entities, is_action, load_font, ControlsMenu, SettingsMenu, ACCEPT, BACK,
+    CONTROLS, PAUSE, SETTINGS,
+};
+
+/// MainMenu is the title screen. It starts a match with Enter, or A or Start on
+/// a gamepad, opens the `ControlsMenu` with C or Y, the `SettingsMenu` with O
+/// or X, quits with Escape or B, and is shown again once the match is over.
+#[derive(Default)]
+pub struct MainMenu {
+    ui: Vec<Entity>,
+    /// Whether a match starts right away, for the modes of the command line
+    /// that skip the title screen.
+    start_match: bool,
+}
+
+impl MainMenu {
+    /// A title screen starting a match as soon as it is shown. It comes back
+    /// once the match is over.
+    pub fn starting_match() -> MainMenu {
+        MainMenu {
+            start_match: true,
+            ..MainMenu::default()
+        }
+    }
+
+    fn create_ui(&mut self, world: &mut World) {
+        let font = load_font(world);
+        self.ui.push(create_label(
+            world,
+            font.clone(),
+            "title",
+            "PONG",
+            100.,
+            80.,
+        ));
+        self.ui.push(create_label(
+            world,
+            font.clone(),
+            "play",
+            "Press Enter or A to play",
+            -40.,
+            25.,
+        ));
+        self.ui.push(create_label(
+            world,
+            font.clone(),
+            "quit",
+            "Press Escape or B to quit",
+            -80.,
+            25.,
+        ));
+        self.ui.push(create_label(
+            world,
+            font.clone(),
+            "controls",
+            "Press C or Y to change the controls",
+            -120.,
+            20.,
+        ));
+        self.ui.push(create_label(
+            world,
+            font,
+            "settings",
+            "Press O or X for the settings",
+            -150.,
+            20.,
+        ));
+    }
+}
+
+impl SimpleState for MainMenu {
+    fn on_start(&mut self, data: StateData<'_, GameData<'_, '_>>) {
+        let world = data.world;
+
+        // The music keeps playing between states, it is only loaded once,
+        // along with the window and the volumes of the saved settings.
+        if !world.has_value::<Sounds>() {
+            initialise_audio(world);
+            settings::apply(world);
+        }
+        self.create_ui(world);
+    }
+
+    fn on_stop(&mut self, data: StateData<'_, GameData<'_, '_>>) {
+        delete_entities(data.world, &mut self.ui);
+    }
+
+    fn on_pause(&mut self, data: StateData<'_, GameData<'_, '_>>) {
+        delete_entities(data.world, &mut self.ui);
+    }
+
+    fn on_resume(&mut self, data: StateData<'_, GameData<'_, '_>>) {
+        self.create_ui(data.world);
+    }
+
+    fn handle_event(
+        &mut self,
+        _data: StateData<'_, GameData<'_, '_>>,
+        event: StateEvent,
+    ) -> SimpleTrans {
+        if let StateEvent::Window(event) = &event {
+            if is_close_requested(event) {
+                return Trans::Quit;
+            }
+        }
+        if is_action(&event, BACK) {
+            return Trans::Quit;
+        }
+        if is_action(&event, ACCEPT) || is_action(&event, PAUSE) {
+            return Trans::Push(Box::new(PongGame::new()));
+        }
+        if is_action(&event, CONTROLS) {
+            return Trans::Push(Box::new(ControlsMenu::default()));
+        }
+        if is_action(&event, SETTINGS) {
+            return Trans::Push(Box::new(SettingsMenu::default()));
+        }
+        Trans::None
+    }
+
+    fn update(&mut self, _data: &mut StateData<'_, GameData<'_, '_>>) -> SimpleTrans {
+        if self.start_match {
+            self.start_match = false;
+            return Trans::Push(Box::new(PongGame::new()));
+        }
+        Trans::None
+    }
+}
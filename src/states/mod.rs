@@ -0,0 +1,94 @@
+use amethyst::{
+    assets::Loader,
+    ecs::prelude::Entity,
+    input::InputEvent,
+    prelude::*,
+    ui::{Anchor, FontHandle, TtfFormat, UiText, UiTransform},
+};
+
+pub use self::controls::ControlsMenu;
+pub use self::game_over::GameOver;
+pub use self::menu::MainMenu;
+pub use self::pause::PauseMenu;
+pub use self::replay::ReplayViewer;
+pub use self::settings::SettingsMenu;
+
+mod controls;
+mod game_over;
+mod menu;
+mod pause;
+mod replay;
+mod settings;
+
+/// RunState tells the gameplay systems whether the match is being played.
+/// Gameplay systems are registered as pausable on `RunState::Running`, so
+/// they stand still in the menus and while the game is paused.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum RunState {
+    Running,
+    #[default]
+    Paused,
+}
+
+/// Menu actions of `config/bindings.ron`, bound to keys and gamepad buttons.
+pub const ACCEPT: &str = "accept";
+pub const BACK: &str = "back";
+pub const PAUSE: &str = "pause";
+pub const CONTROLS: &str = "controls";
+pub const SETTINGS: &str = "settings";
+
+const FONT: &str = "font/square.ttf";
+
+/// Returns whether `event` is a press of one of the bindings of `action`.
+pub fn is_action(event: &StateEvent, action: &str) -> bool {
+    match event {
+        StateEvent::Input(InputEvent::ActionPressed(pressed)) => pressed == action,
+        _ => false,
+    }
+}
+
+pub fn load_font(world: &mut World) -> FontHandle {
+    world
+        .read_resource::<Loader>()
+        .load(FONT, TtfFormat, (), &world.read_resource())
+}
+
+/// Creates a line of text centered horizontally, `y` units above the middle of the window.
+pub fn create_label(
+    world: &mut World,
+    font: FontHandle,
+    id: &str,
+    text: &str,
+    y: f32,
+    font_size: f32,
+) -> Entity {
+    let transform = UiTransform::new(
+        id.to_string(),
+        Anchor::Middle,
+        Anchor::Middle,
+        0.,
+        y,
+        1.,
+        500.,
+        font_size * 1.5,
+    );
+
+    world
+        .create_entity()
+        .with(transform)
+        .with(UiText::new(
+            font,
+            text.to_string(),
+            [1., 1., 1., 1.],
+            font_size,
+        ))
+        .build()
+}
+
+/// Deletes the UI entities a state created, so the next state starts on a clean screen.
+pub fn delete_entities(world: &mut World, entities: &mut Vec<Entity>) {
+    world
+        .delete_entities(&entities[..])
+        .expect("Failed to delete state entities");
+    entities.clear();
+}
@@ -0,0 +1,55 @@
+use amethyst::{ecs::prelude::Entity, input::is_close_requested, prelude::*};
+
+use crate::states::{create_label, delete_entities, is_action, load_font, RunState, BACK, PAUSE};
+
+/// PauseMenu is pushed on top of the match. The gameplay systems are pausable,
+/// so they stop while it is shown and resume once it is popped.
+#[derive(Default)]
+pub struct PauseMenu {
+    ui: Vec<Entity>,
+}
+
+impl SimpleState for PauseMenu {
+    fn on_start(&mut self, data: StateData<'_, GameData<'_, '_>>) {
+        let world = data.world;
+        *world.write_resource::<RunState>() = RunState::Paused;
+
+        let font = load_font(world);
+        self.ui.push(create_label(
+            world,
+            font.clone(),
+            "paused",
+            "PAUSED",
+            40.,
+            50.,
+        ));
+        self.ui.push(create_label(
+            world,
+            font,
+            "resume",
+            "Press Escape or Start to resume",
+            -20.,
+            25.,
+        ));
+    }
+
+    fn on_stop(&mut self, data: StateData<'_, GameData<'_, '_>>) {
+        delete_entities(data.world, &mut self.ui);
+    }
+
+    fn handle_event(
+        &mut self,
+        _data: StateData<'_, GameData<'_, '_>>,
+        event: StateEvent,
+    ) -> SimpleTrans {
+        if let StateEvent::Window(event) = &event {
+            if is_close_requested(event) {
+                return Trans::Quit;
+            }
+        }
+        if is_action(&event, BACK) || is_action(&event, PAUSE) {
+            return Trans::Pop;
+        }
+        Trans::None
+    }
+}
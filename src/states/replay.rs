@@ -0,0 +1,118 @@
+use amethyst::{ecs::prelude::Entity, input::is_close_requested, prelude::*};
+
+use crate::audio::{initialise_audio, Sounds};
+use crate::pong::PongGame;
+use crate::replay::ReplayCheck;
+use crate::settings;
+use crate::sim::Replay;
+use crate::states::{create_label, delete_entities, is_action, load_font, ACCEPT, BACK};
+
+/// ReplayViewer is the first state when the game is started in the replay mode.
+/// It plays the replay right away, then tells whether the final score matches
+/// the recorded one. Enter or Escape, or A or B on a gamepad, quits.
+pub struct ReplayViewer {
+    replay: Option<Replay>,
+    ui: Vec<Entity>,
+}
+
+impl ReplayViewer {
+    pub fn new(replay: Replay) -> ReplayViewer {
+        ReplayViewer {
+            replay: Some(replay),
+            ui: Vec::new(),
+        }
+    }
+}
+
+impl SimpleState for ReplayViewer {
+    fn on_start(&mut self, data: StateData<'_, GameData<'_, '_>>) {
+        let world = data.world;
+
+        // The music keeps playing between states, it is only loaded once,
+        // along with the window and the volumes of the saved settings.
+        if !world.has_value::<Sounds>() {
+            initialise_audio(world);
+            settings::apply(world);
+        }
+    }
+
+    fn on_stop(&mut self, data: StateData<'_, GameData<'_, '_>>) {
+        delete_entities(data.world, &mut self.ui);
+    }
+
+    fn on_resume(&mut self, data: StateData<'_, GameData<'_, '_>>) {
+        let world = data.world;
+        let check = match world.try_fetch::<ReplayCheck>() {
+            Some(check) => (*check).clone(),
+            None => return,
+        };
+
+        let verdict = if check.matches() {
+            "The final score matches the recording".to_string()
+        } else if !check.finished {
+            "The replay was stopped before the end".to_string()
+        } else {
+            format!(
+                "The final score does not match the recording ({})",
+                check.expected.describe(check.sides)
+            )
+        };
+
+        let font = load_font(world);
+        self.ui.push(create_label(
+            world,
+            font.clone(),
+            "replay_over",
+            "REPLAY OVER",
+            80.,
+            50.,
+        ));
+        self.ui.push(create_label(
+            world,
+            font.clone(),
+            "score",
+            &format!("Final score {}", check.actual.describe(check.sides)),
+            20.,
+            25.,
+        ));
+        self.ui.push(create_label(
+            world,
+            font.clone(),
+            "verdict",
+            &verdict,
+            -20.,
+            20.,
+        ));
+        self.ui.push(create_label(
+            world,
+            font,
+            "quit",
+            "Press Enter or A to quit",
+            -80.,
+            20.,
+        ));
+    }
+
+    fn handle_event(
+        &mut self,
+        _data: StateData<'_, GameData<'_, '_>>,
+        event: StateEvent,
+    ) -> SimpleTrans {
+        if let StateEvent::Window(event) = &event {
+            if is_close_requested(event) {
+                return Trans::Quit;
+            }
+        }
+        if is_action(&event, ACCEPT) || is_action(&event, BACK) {
+            return Trans::Quit;
+        }
+        Trans::None
+    }
+
+    fn update(&mut self, _data: &mut StateData<'_, GameData<'_, '_>>) -> SimpleTrans {
+        match self.replay.take() {
+            Some(replay) => Trans::Push(Box::new(PongGame::replay(replay))),
+            None => Trans::None,
+        }
+    }
+}
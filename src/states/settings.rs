@@ -0,0 +1,148 @@
+use amethyst::{
+    ecs::prelude::Entity,
+    input::{is_close_requested, Button, ControllerButton, InputEvent, VirtualKeyCode},
+    prelude::*,
+    ui::UiText,
+};
+
+use crate::ai::AiConfig;
+use crate::rules::GameRules;
+use crate::settings::{self, Setting, Settings};
+use crate::states::{create_label, delete_entities, is_action, load_font, ACCEPT, BACK};
+
+const LINE_HEIGHT: f32 = 30.;
+const FONT_SIZE: f32 = 20.;
+
+/// SettingsMenu changes the `Settings`. Up and down pick a setting, left and
+/// right change it. Every change is applied and saved at once.
+#[derive(Default)]
+pub struct SettingsMenu {
+    ui: Vec<Entity>,
+    /// Label of every setting, in the order of `Setting::ALL`.
+    lines: Vec<Entity>,
+    message: Option<Entity>,
+    selected: usize,
+}
+
+impl SettingsMenu {
+    fn create_ui(&mut self, world: &mut World) {
+        let font = load_font(world);
+        self.ui.push(create_label(
+            world,
+            font.clone(),
+            "settings_title",
+            "SETTINGS",
+            180.,
+            30.,
+        ));
+        let top = LINE_HEIGHT * (Setting::ALL.len() as f32 - 1.) * 0.5 + 20.;
+        for index in 0..Setting::ALL.len() {
+            let line = create_label(
+                world,
+                font.clone(),
+                &format!("setting_{}", index),
+                "",
+                top - LINE_HEIGHT * index as f32,
+                FONT_SIZE,
+            );
+            self.lines.push(line);
+            self.ui.push(line);
+        }
+        let message = create_label(world, font, "settings_message", "", -180., 18.);
+        self.message = Some(message);
+        self.ui.push(message);
+        self.refresh(world, "Left and right: change, Escape or B: back");
+    }
+
+    /// Shows the value of every setting, and `message` at the bottom.
+    fn refresh(&self, world: &mut World, message: &str) {
+        let settings = world.read_resource::<Settings>();
+        let mut texts = world.write_storage::<UiText>();
+        for (index, (setting, line)) in Setting::ALL.iter().zip(self.lines.iter()).enumerate() {
+            let marker = if index == self.selected { ">" } else { " " };
+            if let Some(text) = texts.get_mut(*line) {
+                text.text = format!(
+                    "{} {}: {}",
+                    marker,
+                    setting.label(),
+                    settings.value(*setting)
+                );
+            }
+        }
+        if let Some(text) = self.message.and_then(|message| texts.get_mut(message)) {
+            text.text = message.to_string();
+        }
+    }
+
+    /// Changes the selected setting, then applies and saves the settings.
+    fn change(&mut self, world: &mut World, step: i32) {
+        let settings = {
+            let mut settings = world.write_resource::<Settings>();
+            settings.change(Setting::ALL[self.selected], step);
+            settings.clone()
+        };
+        settings.apply_to_match(
+            &mut world.write_resource::<GameRules>(),
+            &mut world.write_resource::<AiConfig>(),
+        );
+        settings::apply(world);
+        let message = match settings.save() {
+            Ok(()) => String::new(),
+            Err(e) => format!("could not save the settings: {}", e),
+        };
+        self.refresh(world, &message);
+    }
+}
+
+impl SimpleState for SettingsMenu {
+    fn on_start(&mut self, data: StateData<'_, GameData<'_, '_>>) {
+        self.create_ui(data.world);
+    }
+
+    fn on_stop(&mut self, data: StateData<'_, GameData<'_, '_>>) {
+        delete_entities(data.world, &mut self.ui);
+        self.lines.clear();
+        self.message = None;
+    }
+
+    fn handle_event(
+        &mut self,
+        data: StateData<'_, GameData<'_, '_>>,
+        event: StateEvent,
+    ) -> SimpleTrans {
+        let world = data.world;
+        if let StateEvent::Window(event) = &event {
+            if is_close_requested(event) {
+                return Trans::Quit;
+            }
+        }
+        if is_action(&event, BACK) {
+            return Trans::Pop;
+        }
+        if is_action(&event, ACCEPT) {
+            self.change(world, 1);
+        }
+
+        let count = Setting::ALL.len();
+        if let StateEvent::Input(InputEvent::ButtonPressed(button)) = event {
+            match button {
+                Button::Key(VirtualKeyCode::Up)
+                | Button::Controller(_, ControllerButton::DPadUp) => {
+                    self.selected = (self.selected + count - 1) % count;
+                    self.refresh(world, "");
+                }
+                Button::Key(VirtualKeyCode::Down)
+                | Button::Controller(_, ControllerButton::DPadDown) => {
+                    self.selected = (self.selected + 1) % count;
+                    self.refresh(world, "");
+                }
+                Button::Key(VirtualKeyCode::Left)
+                | Button::Controller(_, ControllerButton::DPadLeft) => self.change(world, -1),
+                Button::Key(VirtualKeyCode::Right)
+                | Button::Controller(_, ControllerButton::DPadRight) => self.change(world, 1),
+                _ => {}
+            }
+        }
+        Trans::None
+    }
+}
@@ -1,25 +1,55 @@
 use std::mem;
 
 use amethyst::{
-    core::timing::Time,
-    derive::SystemDesc,
-    ecs::prelude::{Join, Read, System, SystemData, WriteStorage},
+    core::{
+        shrev::{EventChannel, ReaderId},
+        timing::Time,
+    },
+    ecs::prelude::{Join, Read, ReadStorage, System, SystemData, World, WriteStorage},
     renderer::{palette::Srgba, resources::Tint},
 };
 
-use crate::pong::{CyclingColor, CyclingState};
+use crate::pong::{BallSprite, CyclingColor, CyclingState, GameEvent};
 
-#[derive(SystemDesc)]
-pub struct CyclingColorSystem;
+/// Cycles the colours of the `CyclingColor` sprites. A ball flashes while it
+/// waits to be served: from a goal to its next serve.
+#[derive(Default)]
+pub struct CyclingColorSystem {
+    reader: Option<ReaderId<GameEvent>>,
+}
 
 impl<'s> System<'s> for CyclingColorSystem {
     type SystemData = (
+        Read<'s, EventChannel<GameEvent>>,
+        ReadStorage<'s, BallSprite>,
         WriteStorage<'s, Tint>,
         WriteStorage<'s, CyclingColor>,
         Read<'s, Time>,
     );
 
-    fn run(&mut self, (mut tints, mut cycling_colors, time): Self::SystemData) {
+    fn run(&mut self, (events, balls, mut tints, mut cycling_colors, time): Self::SystemData) {
+        let reader = self
+            .reader
+            .as_mut()
+            .expect("`CyclingColorSystem::setup` registers the reader");
+        for event in events.read(reader) {
+            let (index, flashing) = match *event {
+                GameEvent::ServeStart { ball } => (ball, false),
+                GameEvent::Goal { ball, .. } => (ball, true),
+                _ => continue,
+            };
+            for (sprite, cycling) in (&balls, &mut cycling_colors).join() {
+                if sprite.index != index {
+                    continue;
+                }
+                if flashing {
+                    cycling.start();
+                } else {
+                    cycling.stop();
+                }
+            }
+        }
+
         let time_delta = time.delta_seconds();
         for (tint, cycle) in (&mut tints, &mut cycling_colors).join() {
             match cycle.state {
@@ -46,4 +76,13 @@ impl<'s> System<'s> for CyclingColorSystem {
             }
         }
     }
+
+    fn setup(&mut self, world: &mut World) {
+        Self::SystemData::setup(world);
+        self.reader = Some(
+            world
+                .fetch_mut::<EventChannel<GameEvent>>()
+                .register_reader(),
+        );
+    }
 }
@@ -0,0 +1,26 @@
+use amethyst::{
+    core::shrev::EventChannel,
+    ecs::prelude::{System, Write},
+    prelude::*,
+    TransEvent,
+};
+
+/// Quits the game once it has run `frames` frames, for `--frames`. It runs in
+/// every state, menus included.
+pub struct FrameBudgetSystem {
+    pub frames: u64,
+}
+
+impl<'s> System<'s> for FrameBudgetSystem {
+    type SystemData = Write<'s, EventChannel<TransEvent<GameData<'static, 'static>, StateEvent>>>;
+
+    fn run(&mut self, mut transitions: Self::SystemData) {
+        if self.frames == 0 {
+            return;
+        }
+        self.frames -= 1;
+        if self.frames == 0 {
+            transitions.single_write(Box::new(|| Trans::Quit));
+        }
+    }
+}
@@ -1,11 +1,17 @@
-pub use self::bounce::BounceSystem;
 pub use self::cycling_color::CyclingColorSystem;
-pub use self::move_balls::MoveBallsSystem;
+pub use self::frame_budget::FrameBudgetSystem;
 pub use self::paddle::PaddleSystem;
-pub use self::winner::WinnerSystem;
+pub use self::powerups::PowerUpSystem;
+pub use self::score::ScoreSystem;
+pub use self::simulation::SimulationSystem;
+pub use self::sound::SoundSystem;
+pub use self::stats::StatsSystem;
 
-mod bounce;
 mod cycling_color;
-mod move_balls;
+mod frame_budget;
 mod paddle;
-mod winner;
+mod powerups;
+mod score;
+mod simulation;
+mod sound;
+mod stats;
@@ -1,40 +1,218 @@
 use amethyst::{
-    core::Transform,
+    core::{math::Point3, transform::Transform},
     derive::SystemDesc,
-    ecs::{Join, Read, ReadStorage, System, SystemData, WriteStorage},
+    ecs::{Join, Read, ReadExpect, ReadStorage, System, SystemData, Write},
     input::{InputHandler, StringBindings},
+    renderer::Camera,
+    window::ScreenDimensions,
 };
 
-// You'll have to mark PADDLE_HEIGHT as public in pong.rs
-use crate::pong::{Paddle, Side, ARENA_HEIGHT};
+use crate::ai::AiController;
+use crate::bot::Bot;
+use crate::controls::{follow, ControlConfig, InputSource};
+use crate::pong::PaddleSprite;
+use crate::sim::{Inputs, Paddle, Side, Simulation};
 
+/// Collects the movement of every human player into the `Inputs` of the next
+/// simulation ticks, from the `InputSource` each one plays with. A stick pushed
+/// halfway moves the paddle at half its speed, and a paddle played with the
+/// mouse heads for the pointer. Paddles played by the computer or by a `Bot`
+/// are moved by the `SimulationSystem` instead.
 #[derive(SystemDesc)]
 pub struct PaddleSystem;
 
 impl<'s> System<'s> for PaddleSystem {
     type SystemData = (
-        WriteStorage<'s, Transform>,
-        ReadStorage<'s, Paddle>,
+        ReadStorage<'s, PaddleSprite>,
+        ReadStorage<'s, AiController>,
+        ReadStorage<'s, Bot>,
+        ReadStorage<'s, Camera>,
+        ReadStorage<'s, Transform>,
         Read<'s, InputHandler<StringBindings>>,
+        ReadExpect<'s, ControlConfig>,
+        ReadExpect<'s, ScreenDimensions>,
+        Option<ReadExpect<'s, Simulation>>,
+        Write<'s, Inputs>,
     );
 
-    fn run(&mut self, (mut transforms, paddles, input): Self::SystemData) {
-        for (paddle, transform) in (&paddles, &mut transforms).join() {
-            let movement = match paddle.side {
-                Side::Left => input.axis_value("left_paddle"),
-                Side::Right => input.axis_value("right_paddle"),
-            };
-            if let Some(mv_amount) = movement {
-                if mv_amount != 0.0 {
-                    let scaled_amount = 1.2 * mv_amount as f32;
-                    let paddle_y = transform.translation().y;
-                    transform.set_translation_y(
-                        (paddle_y + scaled_amount)
-                            .min(ARENA_HEIGHT - paddle.height * 0.5)
-                            .max(paddle.height * 0.5),
+    fn run(
+        &mut self,
+        (
+            paddles,
+            controllers,
+            bots,
+            cameras,
+            transforms,
+            input,
+            controls,
+            screen,
+            sim,
+            mut inputs,
+        ): Self::SystemData,
+    ) {
+        // The pointer is turned into arena coordinates with the camera of the match.
+        let pointer = input.mouse_position().and_then(|(x, y)| {
+            (&cameras, &transforms)
+                .join()
+                .next()
+                .map(|(camera, transform)| {
+                    let point = camera.projection().screen_to_world_point(
+                        Point3::new(x, y, 0.0),
+                        screen.diagonal(),
+                        transform,
                     );
+                    [point.x, point.y]
+                })
+        });
+        let frame = InputFrame {
+            input: &input,
+            pointer,
+            controls: &controls,
+        };
+
+        for (paddle, _, _) in (&paddles, !&controllers, !&bots).join() {
+            let movement = frame.movement(
+                paddle.side,
+                sim.as_ref().and_then(|sim| sim.paddle(paddle.side)),
+            );
+            inputs.set(paddle.side, movement);
+        }
+    }
+}
+
+/// What the players did during the frame, read once for every paddle.
+struct InputFrame<'a> {
+    input: &'a InputHandler<StringBindings>,
+    /// Mouse pointer in arena coordinates, while it is over the window.
+    pointer: Option<[f32; 2]>,
+    controls: &'a ControlConfig,
+}
+
+impl InputFrame<'_> {
+    /// Movement of the paddle of `side`, in the range of an input axis.
+    fn movement(&self, side: Side, paddle: Option<&Paddle>) -> f32 {
+        match self.controls.get(side) {
+            InputSource::Bindings => {
+                let axis = match side {
+                    Side::Left => "left_paddle",
+                    Side::Right => "right_paddle",
+                    Side::Top => "top_paddle",
+                    Side::Bottom => "bottom_paddle",
+                };
+                bound_movement(self.input, axis)
+            }
+            // The paddle stays where it is while the pointer is out of the window.
+            InputSource::Mouse => match (self.pointer, paddle) {
+                (Some(pointer), Some(paddle)) => {
+                    follow(paddle, pointer[side.axis()], self.controls.mouse_speed)
                 }
+                _ => 0.0,
+            },
+            // Bots move their paddle themselves, this one could not be started.
+            InputSource::Bot(_) => 0.0,
+        }
+    }
+}
+
+/// Movement of the bound axis `axis`. The keys, the stick and the D-pad are
+/// axes of their own, the one pushed the furthest moves the paddle.
+fn bound_movement(input: &InputHandler<StringBindings>, axis: &str) -> f32 {
+    ["", "_stick", "_dpad"]
+        .iter()
+        .filter_map(|device| input.axis_value(&format!("{}{}", axis, device)))
+        .fold(0.0, |movement, value| {
+            if value.abs() > movement.abs() {
+                value
+            } else {
+                movement
             }
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use amethyst::{
+        config::Config,
+        input::{Bindings, ControllerAxis, ControllerButton, ControllerEvent, InputEvent},
+        shrev::EventChannel,
+    };
+
+    use super::*;
+
+    /// Input handler with the shipped bindings and two pads plugged in.
+    fn handler() -> (
+        InputHandler<StringBindings>,
+        EventChannel<InputEvent<StringBindings>>,
+    ) {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("config/bindings.ron");
+        let mut input = InputHandler::new();
+        input.bindings = Bindings::load(path).unwrap();
+        input.bindings.check_invariants().unwrap();
+        let mut events = EventChannel::new();
+        for which in 0..2 {
+            input.send_controller_event(
+                &ControllerEvent::ControllerConnected { which },
+                &mut events,
+            );
         }
+        (input, events)
+    }
+
+    fn push_stick(
+        input: &mut InputHandler<StringBindings>,
+        events: &mut EventChannel<InputEvent<StringBindings>>,
+        which: u32,
+        value: f32,
+    ) {
+        let event = ControllerEvent::ControllerAxisMoved {
+            which,
+            axis: ControllerAxis::LeftY,
+            value,
+        };
+        input.send_controller_event(&event, events);
+    }
+
+    #[test]
+    fn every_paddle_has_keys_a_stick_and_a_dpad() {
+        let (input, _) = handler();
+        for side in &["left", "right", "top", "bottom"] {
+            for device in &["", "_stick", "_dpad"] {
+                let axis = format!("{}_paddle{}", side, device);
+                assert!(
+                    input.bindings.axis(&axis).is_some(),
+                    "{} is not bound",
+                    axis
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn stick_moves_the_paddle_past_its_dead_zone() {
+        let (mut input, mut events) = handler();
+        // The stick is pushed up with negative values, the axis is inverted.
+        push_stick(&mut input, &mut events, 0, -0.1);
+        assert_eq!(bound_movement(&input, "left_paddle"), 0.0);
+        push_stick(&mut input, &mut events, 0, -0.575);
+        assert!((bound_movement(&input, "left_paddle") - 0.5).abs() < 1e-5);
+        push_stick(&mut input, &mut events, 0, 1.0);
+        assert!((bound_movement(&input, "left_paddle") + 1.0).abs() < 1e-5);
+        // The second pad plays the right paddle.
+        assert_eq!(bound_movement(&input, "right_paddle"), 0.0);
+    }
+
+    #[test]
+    fn device_pushed_the_furthest_moves_the_paddle() {
+        let (mut input, mut events) = handler();
+        push_stick(&mut input, &mut events, 1, 0.575);
+        assert!((bound_movement(&input, "right_paddle") + 0.5).abs() < 1e-5);
+        let event = ControllerEvent::ControllerButtonPressed {
+            which: 1,
+            button: ControllerButton::DPadUp,
+        };
+        input.send_controller_event(&event, &mut events);
+        assert_eq!(bound_movement(&input, "right_paddle"), 1.0);
     }
 }
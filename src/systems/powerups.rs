@@ -0,0 +1,88 @@
+use amethyst::{
+    core::{transform::Transform, Hidden},
+    derive::SystemDesc,
+    ecs::prelude::{
+        Entities, Entity, Join, ReadExpect, ReadStorage, System, SystemData, WriteStorage,
+    },
+    renderer::{palette::Srgba, resources::Tint},
+};
+
+use crate::pong::{BallSprite, PowerUpSprite, ShieldSprite};
+use crate::sim::{PowerUpKind, Simulation};
+
+/// Shows the power-ups waiting in the arena, the shields that are up and the
+/// balls added by splits, and hides them once they are gone.
+#[derive(SystemDesc)]
+pub struct PowerUpSystem;
+
+impl<'s> System<'s> for PowerUpSystem {
+    type SystemData = (
+        Entities<'s>,
+        Option<ReadExpect<'s, Simulation>>,
+        ReadStorage<'s, PowerUpSprite>,
+        ReadStorage<'s, ShieldSprite>,
+        ReadStorage<'s, BallSprite>,
+        WriteStorage<'s, Transform>,
+        WriteStorage<'s, Tint>,
+        WriteStorage<'s, Hidden>,
+    );
+
+    fn run(
+        &mut self,
+        (entities, sim, powerups, shields, balls, mut transforms, mut tints, mut hidden): Self::SystemData,
+    ) {
+        let sim = match sim {
+            Some(sim) => sim,
+            None => return,
+        };
+
+        for (entity, sprite, transform, tint) in
+            (&entities, &powerups, &mut transforms, &mut tints).join()
+        {
+            let visible = match sim.powerups.get(sprite.index) {
+                Some(powerup) => {
+                    transform.set_translation_x(powerup.position[0]);
+                    transform.set_translation_y(powerup.position[1]);
+                    tint.0 = color(powerup.kind);
+                    true
+                }
+                None => false,
+            };
+            show(&mut hidden, entity, visible);
+        }
+
+        for (entity, sprite) in (&entities, &shields).join() {
+            let up = sim
+                .effects
+                .iter()
+                .any(|effect| effect.kind == PowerUpKind::Shield && effect.side == sprite.side);
+            show(&mut hidden, entity, up);
+        }
+
+        for (entity, sprite) in (&entities, &balls).join() {
+            show(&mut hidden, entity, sprite.index < sim.balls.len());
+        }
+    }
+}
+
+fn show(hidden: &mut WriteStorage<'_, Hidden>, entity: Entity, visible: bool) {
+    if visible {
+        hidden.remove(entity);
+    } else if !hidden.contains(entity) {
+        hidden
+            .insert(entity, Hidden)
+            .expect("joined entities are alive");
+    }
+}
+
+/// Every kind of power-up has its own colour.
+fn color(kind: PowerUpKind) -> Srgba {
+    match kind {
+        PowerUpKind::LongPaddle => Srgba::new(0.3, 1.0, 0.3, 1.0),
+        PowerUpKind::ShortPaddle => Srgba::new(1.0, 0.3, 0.3, 1.0),
+        PowerUpKind::FastBall => Srgba::new(1.0, 0.85, 0.2, 1.0),
+        PowerUpKind::SplitBall => Srgba::new(1.0, 0.45, 0.9, 1.0),
+        PowerUpKind::ReversedControls => Srgba::new(0.7, 0.4, 1.0, 1.0),
+        PowerUpKind::Shield => Srgba::new(0.3, 0.8, 1.0, 1.0),
+    }
+}
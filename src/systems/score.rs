@@ -0,0 +1,70 @@
+use amethyst::{
+    core::shrev::{EventChannel, ReaderId},
+    ecs::prelude::{Read, ReadExpect, System, SystemData, World, WriteStorage},
+    ui::UiText,
+};
+
+use crate::pong::{GameEvent, ScoreText};
+use crate::sim::Simulation;
+
+/// Shows the score after every goal.
+#[derive(Default)]
+pub struct ScoreSystem {
+    reader: Option<ReaderId<GameEvent>>,
+}
+
+impl<'s> System<'s> for ScoreSystem {
+    type SystemData = (
+        Read<'s, EventChannel<GameEvent>>,
+        Option<ReadExpect<'s, Simulation>>,
+        WriteStorage<'s, UiText>,
+        Option<ReadExpect<'s, ScoreText>>,
+    );
+
+    fn run(&mut self, (events, sim, mut ui_text, score_text): Self::SystemData) {
+        let reader = self
+            .reader
+            .as_mut()
+            .expect("`ScoreSystem::setup` registers the reader");
+        let goal = events
+            .read(reader)
+            .any(|event| matches!(event, GameEvent::Goal { .. }));
+
+        // The score texts only exist once a match has started.
+        let (sim, score_text) = match (sim, score_text) {
+            (Some(sim), Some(score_text)) if goal => (sim, score_text),
+            _ => return,
+        };
+
+        // A won set resets every score, so every text is refreshed.
+        let scores = &sim.score;
+        let mut texts = score_text
+            .points
+            .iter()
+            .map(|(side, entity)| (*entity, scores.points(*side)))
+            .collect::<Vec<_>>();
+        // Set texts are left empty when a single set is played.
+        if sim.rules.best_of_sets > 1 {
+            texts.extend(
+                score_text
+                    .sets
+                    .iter()
+                    .map(|(side, entity)| (*entity, scores.sets(*side))),
+            );
+        }
+        for (entity, value) in texts {
+            if let Some(text) = ui_text.get_mut(entity) {
+                text.text = value.to_string();
+            }
+        }
+    }
+
+    fn setup(&mut self, world: &mut World) {
+        Self::SystemData::setup(world);
+        self.reader = Some(
+            world
+                .fetch_mut::<EventChannel<GameEvent>>()
+                .register_reader(),
+        );
+    }
+}
@@ -0,0 +1,211 @@
+use amethyst::{
+    core::{shrev::EventChannel, timing::Time, transform::Transform},
+    ecs::prelude::{Join, Read, ReadStorage, System, Write, WriteExpect, WriteStorage},
+};
+
+use crate::ai::AiController;
+use crate::bot::Bot;
+use crate::environment::Observation;
+use crate::net::{Host, NetSession};
+use crate::pong::{
+    BallSprite, GameEvent, ObstacleSprite, PaddleSprite, PADDLE_SPRITE_HEIGHT, PADDLE_SPRITE_WIDTH,
+};
+use crate::replay::{Playback, Recording};
+use crate::sim::{lerp, FixedStep, Inputs, SimEvent, SimRng, Simulation, TICK};
+
+/// Advances the `Simulation` by fixed ticks and moves the sprites to match,
+/// interpolated between the last two ticks. What happened during every tick is
+/// sent on the `EventChannel<GameEvent>` for the other systems.
+///
+/// Paddles played by the computer decide their movement on every tick, with
+/// their own seeded `SimRng`, so the same seed and inputs always play the same
+/// match. A `Bot` is sent the state of every tick and plays its last answer,
+/// the frame never waits for it.
+/// During a `Playback` every input comes from the replay instead, and the
+/// inputs of every tick go to the `Recording` when there is one.
+///
+/// In a `NetSession`, the host plays the input of the client on its paddle and
+/// sends a snapshot after every tick. The client does not simulate the match,
+/// it sends its input and shows what the host sent. A peer plays the ticks in
+/// its own rollback session and shows its match.
+#[derive(Default)]
+pub struct SimulationSystem {
+    step: FixedStep,
+}
+
+impl<'s> System<'s> for SimulationSystem {
+    type SystemData = (
+        Option<WriteExpect<'s, Simulation>>,
+        Option<WriteExpect<'s, SimRng>>,
+        Option<WriteExpect<'s, Playback>>,
+        Option<WriteExpect<'s, Recording>>,
+        Option<WriteExpect<'s, NetSession>>,
+        Read<'s, Inputs>,
+        Write<'s, EventChannel<GameEvent>>,
+        ReadStorage<'s, PaddleSprite>,
+        WriteStorage<'s, AiController>,
+        WriteStorage<'s, Bot>,
+        ReadStorage<'s, BallSprite>,
+        ReadStorage<'s, ObstacleSprite>,
+        WriteStorage<'s, Transform>,
+        Read<'s, Time>,
+    );
+
+    fn run(
+        &mut self,
+        (
+            sim,
+            rng,
+            mut playback,
+            mut recording,
+            mut net,
+            inputs,
+            mut events,
+            paddles,
+            mut controllers,
+            mut bots,
+            balls,
+            obstacles,
+            mut transforms,
+            time,
+        ): Self::SystemData,
+    ) {
+        // The simulation only exists once a match has started.
+        let (mut sim, mut rng) = match (sim, rng) {
+            (Some(sim), Some(rng)) => (sim, rng),
+            _ => return,
+        };
+
+        let speed = playback.as_ref().map_or(1.0, |playback| playback.speed());
+        let mut ticks = self.step.advance(time.delta_seconds() * speed);
+
+        // Both sets of keys move the paddle of a client or a peer.
+        let axis = if inputs.left != 0.0 {
+            inputs.left
+        } else {
+            inputs.right
+        };
+        let mut host = match net.as_deref_mut() {
+            Some(NetSession::Client(client)) => {
+                for _ in 0..ticks {
+                    report(client.tick(axis));
+                }
+                report(client.poll());
+                *sim = client.simulation().clone();
+                send(&mut events, &client.take_events(), &sim);
+                ticks = 0;
+                None
+            }
+            Some(NetSession::Peer(peer)) => {
+                report(peer.poll());
+                for _ in 0..ticks {
+                    report(peer.tick(axis));
+                }
+                *sim = peer.simulation().clone();
+                send(&mut events, &peer.take_events(), &sim);
+                ticks = 0;
+                None
+            }
+            Some(NetSession::Host(host)) => {
+                report(host.poll());
+                // The match starts once a client joined.
+                if !host.is_connected() {
+                    return;
+                }
+                Some(host)
+            }
+            None => None,
+        };
+
+        for _ in 0..ticks {
+            let tick_inputs = match playback.as_mut() {
+                Some(playback) => match playback.next_inputs() {
+                    Some(tick_inputs) => tick_inputs,
+                    None => break,
+                },
+                None => {
+                    let mut tick_inputs = *inputs;
+                    for (paddle, controller) in (&paddles, &mut controllers).join() {
+                        controller.update(&sim, paddle.side, TICK, &mut *rng);
+                        tick_inputs.set(paddle.side, controller.movement);
+                    }
+                    let tick = (sim.clock / TICK).round() as u64;
+                    for (paddle, bot) in (&paddles, &mut bots).join() {
+                        let playing = bot.forfeit().is_none();
+                        let movement = bot.play(&Observation::new(&sim, tick, paddle.side));
+                        // A bot that forfeits leaves its paddle still for the rest of the match.
+                        if let (true, Some(forfeit)) = (playing, bot.forfeit()) {
+                            eprintln!(
+                                "the bot `{}` of the {:?} paddle forfeits: {}",
+                                bot.name, paddle.side, forfeit
+                            );
+                        }
+                        tick_inputs.set(paddle.side, movement);
+                    }
+                    if let Some(host) = host.as_mut() {
+                        tick_inputs.set(Host::REMOTE_SIDE, host.remote_input());
+                    }
+                    tick_inputs
+                }
+            };
+            if let Some(recording) = recording.as_mut() {
+                recording.replay.record(&tick_inputs);
+            }
+            let tick_events = sim.step(TICK, &tick_inputs);
+            send(&mut events, &tick_events, &sim);
+            if let Some(host) = host.as_mut() {
+                report(host.send_snapshot(&sim, &tick_events));
+            }
+        }
+
+        let alpha = self.step.alpha();
+        for (sprite, transform) in (&paddles, &mut transforms).join() {
+            if let Some(paddle) = sim.paddle(sprite.side) {
+                let [x, y] = lerp(paddle.last_position, paddle.position, alpha);
+                transform.set_translation_x(x);
+                transform.set_translation_y(y);
+                // Power-ups change the paddle length during the match.
+                let scale = transform.scale_mut();
+                scale.x = paddle.width / PADDLE_SPRITE_WIDTH;
+                scale.y = paddle.height / PADDLE_SPRITE_HEIGHT;
+            }
+        }
+
+        // Moving obstacles are where they were `alpha` ticks after the last one.
+        let clock = sim.clock - (1.0 - alpha) * TICK;
+        for (sprite, transform) in (&obstacles, &mut transforms).join() {
+            if let Some(obstacle) = sim.level.obstacles.get(sprite.index) {
+                let [x, y] = obstacle.center(clock);
+                transform.set_translation_x(x);
+                transform.set_translation_y(y);
+            }
+        }
+
+        for (sprite, transform) in (&balls, &mut transforms).join() {
+            let ball = match sim.balls.get(sprite.index) {
+                Some(ball) => ball,
+                None => continue,
+            };
+            let [x, y] = lerp(ball.last_position, ball.position, alpha);
+            transform.set_translation_x(x);
+            transform.set_translation_y(y);
+        }
+    }
+}
+
+/// Sends the game events of the simulation events of a tick, `sim` is the
+/// match right after it.
+fn send(channel: &mut EventChannel<GameEvent>, events: &[SimEvent], sim: &Simulation) {
+    let mut events = events
+        .iter()
+        .filter_map(|event| GameEvent::from_sim(event, sim))
+        .collect();
+    channel.drain_vec_write(&mut events);
+}
+
+/// Network errors do not stop the match, a lost packet is only a late one.
+fn report(result: std::io::Result<()>) {
+    if let Err(e) = result {
+        eprintln!("network error: {}", e);
+    }
+}
@@ -0,0 +1,53 @@
+use amethyst::{
+    assets::AssetStorage,
+    audio::{output::Output, Source},
+    core::shrev::{EventChannel, ReaderId},
+    ecs::prelude::{Read, ReadExpect, System, SystemData, World},
+};
+
+use crate::audio::{play_bounce, play_score_sound, Sounds};
+use crate::pong::GameEvent;
+
+/// Plays the bounce sound for every wall, paddle, other ball or piece of the
+/// level a ball bounced on or went through, and the score sound for every
+/// goal.
+#[derive(Default)]
+pub struct SoundSystem {
+    reader: Option<ReaderId<GameEvent>>,
+}
+
+impl<'s> System<'s> for SoundSystem {
+    type SystemData = (
+        Read<'s, EventChannel<GameEvent>>,
+        Read<'s, AssetStorage<Source>>,
+        ReadExpect<'s, Sounds>,
+        Option<Read<'s, Output>>,
+    );
+
+    fn run(&mut self, (events, storage, sounds, audio_output): Self::SystemData) {
+        let reader = self
+            .reader
+            .as_mut()
+            .expect("`SoundSystem::setup` registers the reader");
+        let output = audio_output.as_deref();
+        for event in events.read(reader) {
+            match event {
+                GameEvent::WallBounce { .. }
+                | GameEvent::PaddleHit { .. }
+                | GameEvent::BallHit { .. }
+                | GameEvent::LevelHit { .. } => play_bounce(&sounds, &storage, output),
+                GameEvent::Goal { .. } => play_score_sound(&sounds, &storage, output),
+                GameEvent::ServeStart { .. } | GameEvent::MatchOver { .. } => {}
+            }
+        }
+    }
+
+    fn setup(&mut self, world: &mut World) {
+        Self::SystemData::setup(world);
+        self.reader = Some(
+            world
+                .fetch_mut::<EventChannel<GameEvent>>()
+                .register_reader(),
+        );
+    }
+}
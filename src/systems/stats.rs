@@ -0,0 +1,101 @@
+use amethyst::{
+    core::shrev::{EventChannel, ReaderId},
+    ecs::prelude::{Read, System, SystemData, World, Write},
+};
+
+use crate::pong::{GameEvent, MatchStats};
+
+/// Counts the paddle hits of every rally into the `MatchStats` of the match.
+#[derive(Default)]
+pub struct StatsSystem {
+    reader: Option<ReaderId<GameEvent>>,
+}
+
+impl<'s> System<'s> for StatsSystem {
+    type SystemData = (Read<'s, EventChannel<GameEvent>>, Write<'s, MatchStats>);
+
+    fn run(&mut self, (events, mut stats): Self::SystemData) {
+        let reader = self
+            .reader
+            .as_mut()
+            .expect("`StatsSystem::setup` registers the reader");
+        for event in events.read(reader) {
+            match *event {
+                GameEvent::ServeStart { ball } => stats.serve(ball),
+                GameEvent::PaddleHit { ball, speed, .. } => stats.hit(ball, speed),
+                _ => {}
+            }
+        }
+    }
+
+    fn setup(&mut self, world: &mut World) {
+        Self::SystemData::setup(world);
+        self.reader = Some(
+            world
+                .fetch_mut::<EventChannel<GameEvent>>()
+                .register_reader(),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use amethyst::ecs::{RunNow, WorldExt};
+
+    use super::*;
+    use crate::rules::GameRules;
+    use crate::sim::{PowerUpKind, Side, SimEvent, Simulation};
+
+    /// Runs a `StatsSystem` over the events of `sim`, returns the stats.
+    fn stats_of(sim: &Simulation, events: &[SimEvent]) -> MatchStats {
+        let mut world = World::new();
+        let mut system = StatsSystem::default();
+        System::setup(&mut system, &mut world);
+        let events = events
+            .iter()
+            .filter_map(|event| GameEvent::from_sim(event, sim))
+            .collect::<Vec<_>>();
+        world
+            .write_resource::<EventChannel<GameEvent>>()
+            .iter_write(events);
+        system.run_now(&world);
+        let stats = world.read_resource::<MatchStats>();
+        (*stats).clone()
+    }
+
+    #[test]
+    fn power_ups_are_not_game_events() {
+        let sim = Simulation::new(GameRules::default());
+        let event = SimEvent::PowerUpExpired {
+            kind: PowerUpKind::LongPaddle,
+            side: Side::Left,
+        };
+        assert_eq!(GameEvent::from_sim(&event, &sim), None);
+    }
+
+    #[test]
+    fn rallies_are_counted_from_the_serve() {
+        let mut sim = Simulation::new(GameRules::default());
+        sim.balls[0].velocity = [30.0, 40.0];
+        let hit = |side| SimEvent::PaddleHit { ball: 0, side };
+        let stats = stats_of(
+            &sim,
+            &[
+                SimEvent::Serve { ball: 0 },
+                hit(Side::Right),
+                hit(Side::Left),
+                hit(Side::Right),
+                SimEvent::Goal {
+                    ball: 0,
+                    scorer: Side::Right,
+                },
+                SimEvent::Serve { ball: 0 },
+                hit(Side::Left),
+            ],
+        );
+        assert_eq!(stats.rallies, vec![1]);
+        assert_eq!(stats.longest_rally, 3);
+        assert_eq!(stats.paddle_hits, 4);
+        assert_eq!(stats.fastest_hit, 50.0);
+    }
+}
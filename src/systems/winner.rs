@@ -1,81 +0,0 @@
-use std::ops::Deref;
-
-use amethyst::{
-    assets::AssetStorage,
-    audio::{output::Output, Source},
-    core::transform::Transform,
-    derive::SystemDesc,
-    ecs::prelude::{Join, Read, ReadExpect, System, SystemData, Write, WriteStorage},
-    ui::UiText,
-};
-
-use crate::audio::{play_score_sound, Sounds};
-use crate::pong::{
-    ARENA_WIDTH, Ball, BALL_Z, CyclingColor, HALVE_HEIGHT, HALVE_WIDTH, ScoreBoard, ScoreText,
-};
-
-#[derive(SystemDesc)]
-pub struct WinnerSystem;
-
-impl<'s> System<'s> for WinnerSystem {
-    type SystemData = (
-        WriteStorage<'s, Ball>,
-        WriteStorage<'s, Transform>,
-        WriteStorage<'s, CyclingColor>,
-        WriteStorage<'s, UiText>,
-        Write<'s, ScoreBoard>,
-        ReadExpect<'s, ScoreText>,
-        Read<'s, AssetStorage<Source>>,
-        ReadExpect<'s, Sounds>,
-        Option<Read<'s, Output>>,
-    );
-
-    fn run(
-        &mut self,
-        (
-            mut balls,
-            mut locals,
-            mut cyclings,
-            mut ui_text,
-            mut scores,
-            score_text,
-            storage,
-            sounds,
-            audio_output,
-        ): Self::SystemData,
-    ) {
-        for (ball, transform, cycling) in (&mut balls, &mut locals, &mut cyclings).join() {
-            let ball_x = transform.translation().x;
-
-            let did_hit = if ball_x <= ball.radius {
-                // Right player scored on the left side.
-                // We top the score at 999 to avoid text overlap.
-                scores.score_right = (scores.score_right + 1).min(999);
-
-                if let Some(text) = ui_text.get_mut(score_text.p2_score) {
-                    text.text = scores.score_right.to_string();
-                }
-                true
-            } else if ball_x >= ARENA_WIDTH - ball.radius {
-                // Left player scored on the right side.
-                // We top the score at 999 to avoid text overlap.
-                scores.score_left = (scores.score_left + 1).min(999);
-                if let Some(text) = ui_text.get_mut(score_text.p1_score) {
-                    text.text = scores.score_left.to_string();
-                }
-                true
-            } else {
-                false
-            };
-
-            if did_hit {
-                transform.set_translation_xyz(HALVE_WIDTH, HALVE_HEIGHT, BALL_Z);
-                let direction = ball.velocity[0] / -ball.velocity[0]; // Reverse X-Direction
-                ball.wait();
-                cycling.start();
-                ball.velocity = [ball.velocity[0] * direction, ball.velocity[1]];
-                play_score_sound(&*sounds, &storage, audio_output.as_ref().map(|o| o.deref()));
-            }
-        }
-    }
-}
@@ -0,0 +1,342 @@
+//! Tournaments between bots, played headless: the `tournament` mode of the
+//! command line. Every bot plays every other one, on the left and the right
+//! paddle in turn, and the standings rank them by wins.
+
+use std::{
+    cmp::Reverse,
+    io::{self, Write},
+};
+
+use crate::ai::AiConfig;
+use crate::bot::{Bot, BotPolicy, Forfeit};
+use crate::environment::Observation;
+use crate::headless::AiMatch;
+use crate::level::Level;
+use crate::rules::GameRules;
+use crate::sim::{Inputs, Side, SimEvent};
+
+/// Matches every pair of bots plays when `--matches` is not given, one with
+/// each bot on the left.
+pub const DEFAULT_MATCHES: u32 = 2;
+
+const SIDES: [Side; 2] = [Side::Left, Side::Right];
+
+/// Outcome is how a match between two bots ended.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Outcome {
+    /// `None` for a match still running after the last tick, or one both
+    /// bots forfeited.
+    pub winner: Option<Side>,
+    /// Bots that forfeited, the other one wins the match.
+    pub forfeits: Vec<(Side, Forfeit)>,
+    /// Points scored by the left and the right paddle.
+    pub points: [u32; 2],
+    pub ticks: u64,
+}
+
+/// Plays `game` between the bots of the left and the right paddle, until it
+/// has a winner, a bot forfeits or `max_ticks` ticks are played.
+pub fn play(mut game: AiMatch, bots: &mut [Bot; 2], max_ticks: u64) -> Outcome {
+    let mut outcome = Outcome::default();
+    while !game.sim.is_over() && game.ticks < max_ticks {
+        let mut inputs = Inputs::default();
+        for (side, bot) in SIDES.iter().zip(bots.iter_mut()) {
+            let state = Observation::new(&game.sim, game.ticks, *side);
+            inputs.set(*side, bot.movement(&state));
+            if let Some(forfeit) = bot.forfeit() {
+                outcome.forfeits.push((*side, forfeit.clone()));
+            }
+        }
+        if !outcome.forfeits.is_empty() {
+            break;
+        }
+        let (_, events) = game.step_with(inputs);
+        for event in events {
+            if let SimEvent::Goal { scorer, .. } = event {
+                outcome.points[scorer.index()] += 1;
+            }
+        }
+    }
+    outcome.ticks = game.ticks;
+    outcome.winner = if outcome.forfeits.is_empty() {
+        game.sim.score.winner
+    } else {
+        forfeit_winner(&outcome.forfeits)
+    };
+    outcome
+}
+
+/// The winner of a match one bot forfeited, nobody wins when both did.
+fn forfeit_winner(forfeits: &[(Side, Forfeit)]) -> Option<Side> {
+    match forfeits {
+        [(side, _)] => Some(side.opponent()),
+        _ => None,
+    }
+}
+
+/// A match played in a tournament.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MatchResult {
+    /// Bots of the left and the right paddle, as indices of the entrants.
+    pub bots: [usize; 2],
+    pub seed: u64,
+    pub outcome: Outcome,
+}
+
+impl MatchResult {
+    /// One line telling how the match went, with the names of the bots.
+    pub fn describe(&self, names: &[String]) -> String {
+        let left = &names[self.bots[0]];
+        let right = &names[self.bots[1]];
+        let outcome = &self.outcome;
+        let mut line = format!(
+            "`{}` {} - {} `{}`, seed {}: ",
+            left, outcome.points[0], outcome.points[1], right, self.seed
+        );
+        line.push_str(&match outcome.winner {
+            Some(side) => format!("`{}` wins", names[self.bots[side.index()]]),
+            None => "draw".to_string(),
+        });
+        for (side, forfeit) in &outcome.forfeits {
+            line.push_str(&format!(
+                ", `{}` forfeits: {}",
+                names[self.bots[side.index()]],
+                forfeit
+            ));
+        }
+        line
+    }
+}
+
+/// Standing of a bot after a tournament.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Standing {
+    pub bot: String,
+    pub played: u32,
+    pub wins: u32,
+    pub draws: u32,
+    pub losses: u32,
+    /// Matches lost by forfeit, counted in `losses` as well.
+    pub forfeits: u32,
+    pub points_for: u32,
+    pub points_against: u32,
+}
+
+/// Tournament pits bots against each other, each one started from its spec:
+/// a command or `tcp:<ADDRESS>`, see the `bot` module.
+pub struct Tournament {
+    pub bots: Vec<String>,
+    pub rules: GameRules,
+    pub level: Level,
+    pub policy: BotPolicy,
+    /// Matches played by every pair of bots.
+    pub matches: u32,
+    /// Ticks after which a match still running is a draw.
+    pub max_ticks: u64,
+}
+
+impl Tournament {
+    /// Plays every match, and returns the standings from the first bot to the
+    /// last one. Every match starts its bots again, so a bot that crashed
+    /// plays the next one, and has a seed of its own following `seed`.
+    /// `report` is called after every match.
+    pub fn run<F: FnMut(&MatchResult)>(&self, seed: u64, mut report: F) -> Vec<Standing> {
+        let mut standings: Vec<Standing> = self
+            .bots
+            .iter()
+            .map(|bot| Standing {
+                bot: bot.clone(),
+                ..Standing::default()
+            })
+            .collect();
+        let mut seed = seed;
+        for first in 0..self.bots.len() {
+            for second in first + 1..self.bots.len() {
+                for index in 0..self.matches {
+                    let bots = if index % 2 == 0 {
+                        [first, second]
+                    } else {
+                        [second, first]
+                    };
+                    let result = MatchResult {
+                        bots,
+                        seed,
+                        outcome: self.play_match(bots, seed),
+                    };
+                    record(&mut standings, &result);
+                    report(&result);
+                    seed = seed.wrapping_add(1);
+                }
+            }
+        }
+        // Ties go to the bot that drew more, then to the best point difference.
+        standings.sort_by_key(|standing| {
+            Reverse((
+                standing.wins,
+                standing.draws,
+                standing.points_for as i64 - standing.points_against as i64,
+            ))
+        });
+        standings
+    }
+
+    fn play_match(&self, bots: [usize; 2], seed: u64) -> Outcome {
+        let start = |bot: usize| Bot::start(&self.bots[bot], self.policy);
+        let mut bots = match (start(bots[0]), start(bots[1])) {
+            (Ok(left), Ok(right)) => [left, right],
+            // A bot that does not start forfeits before the first tick.
+            (left, right) => {
+                let forfeits: Vec<_> = vec![(Side::Left, left.err()), (Side::Right, right.err())]
+                    .into_iter()
+                    .filter_map(|(side, e)| e.map(|e| (side, Forfeit::NotStarted(e.to_string()))))
+                    .collect();
+                return Outcome {
+                    winner: forfeit_winner(&forfeits),
+                    forfeits,
+                    ..Outcome::default()
+                };
+            }
+        };
+        let game = AiMatch::new(
+            self.rules.clone(),
+            self.level.clone(),
+            &AiConfig::default(),
+            seed,
+        );
+        play(game, &mut bots, self.max_ticks)
+    }
+}
+
+fn record(standings: &mut [Standing], result: &MatchResult) {
+    let outcome = &result.outcome;
+    for (index, side) in SIDES.iter().enumerate() {
+        let standing = &mut standings[result.bots[index]];
+        standing.played += 1;
+        standing.points_for += outcome.points[index];
+        standing.points_against += outcome.points[1 - index];
+        match outcome.winner {
+            Some(winner) if winner == *side => standing.wins += 1,
+            Some(_) => standing.losses += 1,
+            None => standing.draws += 1,
+        }
+        if outcome.forfeits.iter().any(|(forfeit, _)| forfeit == side) {
+            standing.forfeits += 1;
+        }
+    }
+}
+
+/// Writes `standings` as a table, one bot per line from the first one.
+pub fn write_standings<W: Write>(standings: &[Standing], out: &mut W) -> io::Result<()> {
+    writeln!(
+        out,
+        "{:>4}  {:>6} {:>4} {:>5} {:>6} {:>8} {:>6} {:>7}  bot",
+        "rank", "played", "wins", "draws", "losses", "forfeits", "points", "against"
+    )?;
+    for (rank, standing) in standings.iter().enumerate() {
+        writeln!(
+            out,
+            "{:>4}  {:>6} {:>4} {:>5} {:>6} {:>8} {:>6} {:>7}  {}",
+            rank + 1,
+            standing.played,
+            standing.wins,
+            standing.draws,
+            standing.losses,
+            standing.forfeits,
+            standing.points_for,
+            standing.points_against,
+            standing.bot
+        )?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{BufRead, BufReader},
+        net::TcpListener,
+        thread,
+    };
+
+    use super::*;
+
+    /// Address of a bot that answers every state by following the first ball,
+    /// for as many matches as it is started for.
+    fn following_bot() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let stream = stream.unwrap();
+                thread::spawn(move || {
+                    let mut writer = stream.try_clone().unwrap();
+                    for line in BufReader::new(stream).lines() {
+                        let state: serde_json::Value = match line {
+                            Ok(line) => serde_json::from_str(&line).unwrap(),
+                            Err(_) => return,
+                        };
+                        let side = state["side"].as_str().unwrap();
+                        let paddle = &state["paddles"]
+                            .as_array()
+                            .unwrap()
+                            .iter()
+                            .find(|paddle| paddle["side"] == side)
+                            .unwrap()["position"][1];
+                        let ball = &state["balls"][0]["position"][1];
+                        let movement = (ball.as_f64().unwrap() - paddle.as_f64().unwrap()).signum();
+                        if writeln!(writer, "{}", movement).is_err() {
+                            return;
+                        }
+                    }
+                });
+            }
+        });
+        format!("tcp:{}", address)
+    }
+
+    #[test]
+    fn bots_that_do_not_start_forfeit() {
+        let tournament = Tournament {
+            bots: vec![following_bot(), "./no-such-bot".to_string()],
+            rules: GameRules {
+                points_to_win: 1,
+                ..GameRules::default()
+            },
+            level: Level::default(),
+            policy: BotPolicy::default(),
+            matches: 2,
+            max_ticks: 120,
+        };
+        let mut results = Vec::new();
+        let standings = tournament.run(3, |result| results.push(result.clone()));
+
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].bots, [0, 1]);
+        assert_eq!(results[1].bots, [1, 0]);
+        assert_eq!(results[1].seed, 4);
+        assert_eq!(standings[0].bot, tournament.bots[0]);
+        assert_eq!((standings[0].wins, standings[0].forfeits), (2, 0));
+        assert_eq!((standings[1].losses, standings[1].forfeits), (2, 2));
+        assert!(results[0].describe(&tournament.bots).contains("forfeits"));
+    }
+
+    #[test]
+    fn played_matches_count_the_points() {
+        let bot = following_bot();
+        let mut bots = [
+            Bot::start(&bot, BotPolicy::default()).unwrap(),
+            Bot::start(&bot, BotPolicy::default()).unwrap(),
+        ];
+        let game = AiMatch::new(
+            GameRules::default(),
+            Level::default(),
+            &AiConfig::default(),
+            1,
+        );
+        let outcome = play(game, &mut bots, 60);
+
+        assert_eq!(outcome.ticks, 60);
+        assert_eq!(outcome.winner, None);
+        assert!(outcome.forfeits.is_empty());
+    }
+}